@@ -0,0 +1,137 @@
+use nalgebra::{Vector2, Vector3};
+
+use crate::backend::{Boundaries, BoundaryField, GridLayout, Integrator, Slip, SolverBackend};
+use crate::brush::Brush;
+use crate::cpu_fluid::{self, Field};
+use crate::diagnostics::Norms;
+use crate::obstacle::RigidBody;
+use crate::pcg::{self, PcgReport};
+
+/// Runs the solver passes on the CPU through `cpu_fluid`.
+#[derive(Default)]
+pub struct CpuBackend;
+
+impl CpuBackend {
+    pub fn new() -> CpuBackend {
+        CpuBackend
+    }
+}
+
+impl SolverBackend for CpuBackend {
+    type Field = Field;
+    type Error = String;
+
+    fn create_field(&mut self, width: i32, height: i32) -> Result<Field, String> {
+        Ok(Field::new(width, height))
+    }
+
+    fn create_field_with_data(&mut self, width: i32, height: i32, data: Vec<f32>) -> Result<Field, String> {
+        Field::create_with_data(width, height, data)
+    }
+
+    fn delete_field(&mut self, _field: Field) {}
+
+    fn read_field(&mut self, field: &Field) -> Result<Vec<f32>, String> {
+        Ok(field.data().to_vec())
+    }
+
+    fn clear_field(&mut self, field: &mut Field) {
+        field.clear();
+    }
+
+    fn set_periodic(&mut self, field: &mut Field, periodic_x: bool, periodic_y: bool) {
+        field.set_periodic(periodic_x, periodic_y);
+    }
+
+    fn resample(&mut self, src: &Field, dst: &mut Field) {
+        cpu_fluid::resample(src, dst);
+    }
+
+    fn norms(&mut self, field: &Field) -> Result<Norms, String> {
+        Ok(cpu_fluid::norms(field))
+    }
+
+    fn max_speed(&mut self, field: &Field) -> Result<f32, String> {
+        Ok(cpu_fluid::max_speed(field))
+    }
+
+    fn advection(&mut self, delta_x: f32, delta_y: f32, aspect: f32, delta_t: f32, dissipation: f32, integrator: Integrator, layout: GridLayout, src_layout: GridLayout, obstacles: &Field, src: &Field, vector_field: &Field, dst: &mut Field) {
+        cpu_fluid::advection(delta_x, delta_y, aspect, delta_t, dissipation, integrator, layout, src_layout, obstacles, src, vector_field, dst);
+    }
+
+    fn maccormack(&mut self, delta_x: f32, delta_y: f32, aspect: f32, delta_t: f32, dissipation: f32, integrator: Integrator, layout: GridLayout, src_layout: GridLayout, obstacles: &Field, src: &Field, vector_field: &Field, forward: &Field, backward: &Field, dst: &mut Field) {
+        cpu_fluid::maccormack(delta_x, delta_y, aspect, delta_t, dissipation, integrator, layout, src_layout, obstacles, src, vector_field, forward, backward, dst);
+    }
+
+    fn jacobi_method(&mut self, iter: usize, delta_x: f32, delta_y: f32, aspect: f32, alpha: f32, r_beta: f32, obstacles: &Field, x: &mut Field, b: &Field, dst: &mut Field) {
+        cpu_fluid::jacobi_method(iter, delta_x, delta_y, aspect, alpha, r_beta, obstacles, x, b, dst);
+    }
+
+    fn jacobi_iteration(&mut self, delta_x: f32, delta_y: f32, aspect: f32, alpha: f32, r_beta: f32, obstacles: &Field, x: &Field, b: &Field, dst: &mut Field) {
+        cpu_fluid::jacobi_iteration(delta_x, delta_y, aspect, alpha, r_beta, obstacles, x, b, dst);
+    }
+
+    fn weighted_jacobi_iteration(&mut self, delta_x: f32, delta_y: f32, aspect: f32, alpha: f32, r_beta: f32, omega: f32, obstacles: &Field, x: &Field, b: &Field, dst: &mut Field) {
+        cpu_fluid::weighted_jacobi_iteration(delta_x, delta_y, aspect, alpha, r_beta, omega, obstacles, x, b, dst);
+    }
+
+    fn red_black_iteration(&mut self, delta_x: f32, delta_y: f32, aspect: f32, alpha: f32, r_beta: f32, omega: f32, obstacles: &Field, x: &mut Field, b: &Field, scratch: &mut Field) {
+        cpu_fluid::red_black_iteration(delta_x, delta_y, aspect, alpha, r_beta, omega, obstacles, x, b, scratch);
+    }
+
+    fn residual(&mut self, delta_x: f32, delta_y: f32, aspect: f32, obstacles: &Field, x: &Field, b: &Field, dst: &mut Field) {
+        cpu_fluid::residual(delta_x, delta_y, aspect, obstacles, x, b, dst);
+    }
+
+    fn conjugate_gradient(&mut self, delta_x: f32, delta_y: f32, aspect: f32, tolerance: f32, max_iterations: usize, boundaries: &Boundaries, obstacles: &Field, x: &mut Field, b: &Field) -> Option<PcgReport> {
+        Some(pcg::solve_pressure(delta_x, delta_y, aspect, tolerance, max_iterations, boundaries, obstacles, x, b))
+    }
+
+    fn restrict(&mut self, src: &Field, dst: &mut Field) {
+        cpu_fluid::restrict(src, dst);
+    }
+
+    fn prolongate(&mut self, x: &Field, e: &Field, dst: &mut Field) {
+        cpu_fluid::prolongate(x, e, dst);
+    }
+
+    fn divergence(&mut self, delta_x: f32, delta_y: f32, aspect: f32, layout: GridLayout, boundaries: &Boundaries, obstacles: &Field, w: &Field, dst: &mut Field) {
+        cpu_fluid::divergence(delta_x, delta_y, aspect, layout, boundaries, obstacles, w, dst);
+    }
+
+    fn subtract(&mut self, delta_x: f32, delta_y: f32, aspect: f32, layout: GridLayout, obstacles: &Field, p: &Field, w: &Field, dst: &mut Field) {
+        cpu_fluid::subtract(delta_x, delta_y, aspect, layout, obstacles, p, w, dst);
+    }
+
+    fn boundary(&mut self, delta_x: f32, delta_y: f32, boundaries: &Boundaries, field: BoundaryField, layout: GridLayout, x: &Field, dst: &mut Field) {
+        cpu_fluid::boundary(delta_x, delta_y, boundaries, field, layout, x, dst);
+    }
+
+    fn obstacle_boundary(&mut self, delta_x: f32, delta_y: f32, slip: Slip, layout: GridLayout, obstacles: &Field, x: &Field, dst: &mut Field) {
+        cpu_fluid::obstacle_boundary(delta_x, delta_y, slip, layout, obstacles, x, dst);
+    }
+
+    fn body(&mut self, aspect: f32, body: &RigidBody, obstacles: &Field, dst: &mut Field) {
+        cpu_fluid::body(aspect, body, obstacles, dst);
+    }
+
+    fn force(&mut self, delta_t: f32, brush: &Brush, aspect: f32, force: &Vector2<f32>, impulse_pos: &Vector2<f32>, velocity_field: &Field, dst: &mut Field) {
+        cpu_fluid::force(delta_t, brush, aspect, force, impulse_pos, velocity_field, dst);
+    }
+
+    fn color(&mut self, delta_t: f32, brush: &Brush, aspect: f32, color: &Vector3<f32>, impulse_pos: &Vector2<f32>, color_field: &Field, dst: &mut Field) {
+        cpu_fluid::color(delta_t, brush, aspect, color, impulse_pos, color_field, dst);
+    }
+
+    fn vorticity_confinement(&mut self, delta_t: f32, delta_x: f32, delta_y: f32, aspect: f32, vorticity: f32, layout: GridLayout, v: &Field, dst: &mut Field) {
+        cpu_fluid::vorticity_confinement(delta_t, delta_x, delta_y, aspect, vorticity, layout, v, dst);
+    }
+
+    fn buoyancy(&mut self, delta_t: f32, delta_y: f32, ambient: f32, lift: f32, weight: f32, layout: GridLayout, smoke_field: &Field, velocity_field: &Field, dst: &mut Field) {
+        cpu_fluid::buoyancy(delta_t, delta_y, ambient, lift, weight, layout, smoke_field, velocity_field, dst);
+    }
+
+    fn heat(&mut self, delta_t: f32, brush: &Brush, aspect: f32, heat: &Vector2<f32>, impulse_pos: &Vector2<f32>, smoke_field: &Field, dst: &mut Field) {
+        cpu_fluid::heat(delta_t, brush, aspect, heat, impulse_pos, smoke_field, dst);
+    }
+}
@@ -0,0 +1,441 @@
+#![allow(clippy::too_many_arguments)]
+
+use nalgebra::{Vector2, Vector3};
+
+use crate::brush::Brush;
+use crate::diagnostics::Norms;
+use crate::obstacle::RigidBody;
+use crate::pcg::PcgReport;
+
+mod cpu;
+mod webgl;
+
+pub use cpu::CpuBackend;
+pub use webgl::WebGlBackend;
+
+/// How the advection passes trace a texel back along the velocity field.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Integrator {
+    Euler       = 0,
+    Midpoint    = 1,
+    /// Ralston's third order Runge-Kutta.
+    Rk3         = 2,
+}
+
+/// Where the components of a velocity field are stored.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GridLayout {
+    /// Both components at the texel center.
+    Collocated  = 0,
+    /// A MAC grid: the texel at cell `(i, j)` holds `u` on the cell's left
+    /// face and `v` on its bottom face. The faces on the right and top walls
+    /// are not stored and always zero.
+    Staggered   = 1,
+}
+
+/// What solid surfaces do to the velocity along them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Slip {
+    /// The fluid sticks to the surface.
+    NoSlip      = 0,
+    /// The fluid slides along the surface, only the flow through it stops.
+    FreeSlip    = 1,
+}
+
+/// What one edge of the domain does to the flow.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Edge {
+    /// A wall the fluid sticks to.
+    NoSlip,
+    /// A wall the fluid slides along.
+    FreeSlip,
+    /// The fluid leaving through the edge comes back in through the opposite
+    /// one. Either edge of a pair being periodic makes both wrap around.
+    Periodic,
+    /// Fluid enters with this velocity.
+    Inflow(Vector2<f32>),
+    /// Fluid leaves freely: the velocity carries on across the edge and the
+    /// pressure there is held at zero.
+    Outflow,
+}
+
+impl Edge {
+    /// The code the boundary passes know the edge by.
+    pub fn mode(&self) -> i32 {
+        match self {
+            Edge::NoSlip        => 0,
+            Edge::FreeSlip      => 1,
+            Edge::Periodic      => 2,
+            Edge::Inflow(_)     => 3,
+            Edge::Outflow       => 4,
+        }
+    }
+
+    /// The velocity an inflow edge sets, zero for the others.
+    pub fn inflow(&self) -> Vector2<f32> {
+        match self {
+            Edge::Inflow(velocity) => *velocity,
+            _ => Vector2::zeros(),
+        }
+    }
+}
+
+/// The conditions on the four edges of the domain.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Boundaries {
+    pub left:   Edge,
+    pub right:  Edge,
+    pub bottom: Edge,
+    pub top:    Edge,
+}
+
+impl Boundaries {
+    /// The same condition on every edge.
+    pub fn all(edge: Edge) -> Boundaries {
+        Boundaries { left: edge, right: edge, bottom: edge, top: edge }
+    }
+
+    /// Whether the left and right edges wrap around to each other.
+    pub fn periodic_x(&self) -> bool {
+        self.left == Edge::Periodic || self.right == Edge::Periodic
+    }
+
+    /// Whether the bottom and top edges wrap around to each other.
+    pub fn periodic_y(&self) -> bool {
+        self.bottom == Edge::Periodic || self.top == Edge::Periodic
+    }
+}
+
+impl Default for Boundaries {
+    fn default() -> Boundaries {
+        Boundaries::all(Edge::NoSlip)
+    }
+}
+
+/// The field a `boundary` pass is applied to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BoundaryField {
+    Velocity    = 0,
+    Pressure    = 1,
+}
+
+/// Weight of the vertical neighbours in the jacobi stencil relative to the
+/// horizontal ones, `(hx / hy)^2` for cells `hx = delta_x` wide and
+/// `hy = delta_y / aspect` tall. A pressure solve uses
+/// `r_beta = 1 / (2 + 2 * wy)`.
+pub fn vertical_weight(delta_x: f32, delta_y: f32, aspect: f32) -> f32 {
+    let hy = delta_y / aspect;
+    (delta_x * delta_x) / (hy * hy)
+}
+
+/// A pair of fields that passes read from and write into alternately.
+pub struct DoubleField<F> {
+    pub read:   F,
+    pub write:  F,
+}
+
+impl<F> DoubleField<F> {
+    pub fn new(read: F, write: F) -> DoubleField<F> {
+        DoubleField { read, write }
+    }
+
+    pub fn swap(&mut self) {
+        std::mem::swap(&mut self.read, &mut self.write);
+    }
+}
+
+/// The operations the solver needs from a device: allocating and reading
+/// back fields, and running each pass of `render_fluid` from one field into
+/// another. Passes never write into the fields they read from.
+///
+/// `delta_x` and `delta_y` are the size of a texel in uv, and `aspect` the
+/// width of the domain over its height, so a cell is `delta_x` wide and
+/// `delta_y / aspect` tall in units of the domain width.
+///
+/// `obstacles` is a mask on the same grid as the fields: cells whose red
+/// channel is above 0.5 are solid, and green and blue hold the velocity of
+/// the solid there. Nothing is advected into them, they hold no divergence,
+/// the pressure passes see a neumann boundary on their faces, and the flow
+/// through those faces is the solid's own.
+pub trait SolverBackend {
+    type Field;
+    type Error;
+
+    fn create_field(&mut self, width: i32, height: i32) -> Result<Self::Field, Self::Error>;
+    fn create_field_with_data(&mut self, width: i32, height: i32, data: Vec<f32>) -> Result<Self::Field, Self::Error>;
+    fn delete_field(&mut self, field: Self::Field);
+    fn read_field(&mut self, field: &Self::Field) -> Result<Vec<f32>, Self::Error>;
+    fn clear_field(&mut self, field: &mut Self::Field);
+
+    /// Makes samples past the left and right, or bottom and top, edges of
+    /// `field` wrap around to the opposite edge instead of clamping.
+    fn set_periodic(&mut self, field: &mut Self::Field, periodic_x: bool, periodic_y: bool);
+
+    /// Bilinearly resamples `src` onto `dst`, which may have another size.
+    fn resample(&mut self, src: &Self::Field, dst: &mut Self::Field);
+
+    /// L2 (root mean square) and max norms of the red channel of `field`.
+    fn norms(&mut self, field: &Self::Field) -> Result<Norms, Self::Error>;
+
+    /// Largest length of the xy vector over the texels of `field`.
+    fn max_speed(&mut self, field: &Self::Field) -> Result<f32, Self::Error>;
+
+    /// `layout` is that of `vector_field` and `src_layout` that of `src`,
+    /// which is only staggered when advecting the velocity itself. The
+    /// result decays by `exp(-dissipation * delta_t)`.
+    fn advection(&mut self,
+        delta_x:        f32,
+        delta_y:        f32,
+        aspect:         f32,
+        delta_t:        f32,
+        dissipation:    f32,
+        integrator:     Integrator,
+        layout:         GridLayout,
+        src_layout:     GridLayout,
+        obstacles:      &Self::Field,
+        src:            &Self::Field,
+        vector_field:   &Self::Field,
+        dst:            &mut Self::Field,
+    );
+
+    /// Corrects the semi-lagrangian `forward` step of `src` using the
+    /// `backward` step of `forward`, limited to the values around the
+    /// backtraced position, then decays it like `advection`.
+    fn maccormack(&mut self,
+        delta_x:        f32,
+        delta_y:        f32,
+        aspect:         f32,
+        delta_t:        f32,
+        dissipation:    f32,
+        integrator:     Integrator,
+        layout:         GridLayout,
+        src_layout:     GridLayout,
+        obstacles:      &Self::Field,
+        src:            &Self::Field,
+        vector_field:   &Self::Field,
+        forward:        &Self::Field,
+        backward:       &Self::Field,
+        dst:            &mut Self::Field,
+    );
+
+    /// Runs `iter` iterations alternating between `x` and `dst`; the result
+    /// is read from `x` afterwards.
+    fn jacobi_method(&mut self,
+        iter:       usize,
+        delta_x:    f32,
+        delta_y:    f32,
+        aspect:     f32,
+        alpha:      f32,
+        r_beta:     f32,
+        obstacles:  &Self::Field,
+        x:          &mut Self::Field,
+        b:          &Self::Field,
+        dst:        &mut Self::Field,
+    );
+
+    fn jacobi_iteration(&mut self,
+        delta_x:    f32,
+        delta_y:    f32,
+        aspect:     f32,
+        alpha:      f32,
+        r_beta:     f32,
+        obstacles:  &Self::Field,
+        x:          &Self::Field,
+        b:          &Self::Field,
+        dst:        &mut Self::Field,
+    );
+
+    fn weighted_jacobi_iteration(&mut self,
+        delta_x:    f32,
+        delta_y:    f32,
+        aspect:     f32,
+        alpha:      f32,
+        r_beta:     f32,
+        omega:      f32,
+        obstacles:  &Self::Field,
+        x:          &Self::Field,
+        b:          &Self::Field,
+        dst:        &mut Self::Field,
+    );
+
+    /// One red-black successive over-relaxation sweep, using `scratch` for
+    /// the half sweep over the red texels. The result is left in `x`.
+    fn red_black_iteration(&mut self,
+        delta_x:    f32,
+        delta_y:    f32,
+        aspect:     f32,
+        alpha:      f32,
+        r_beta:     f32,
+        omega:      f32,
+        obstacles:  &Self::Field,
+        x:          &mut Self::Field,
+        b:          &Self::Field,
+        scratch:    &mut Self::Field,
+    );
+
+    /// Writes `b - laplacian(x)`.
+    fn residual(&mut self,
+        delta_x:    f32,
+        delta_y:    f32,
+        aspect:     f32,
+        obstacles:  &Self::Field,
+        x:          &Self::Field,
+        b:          &Self::Field,
+        dst:        &mut Self::Field,
+    );
+
+    /// Solves `laplacian(x) = b` in place with preconditioned conjugate
+    /// gradient, holding the pressure past the outflow edges of `boundaries`
+    /// at zero. Backends without a CG solve return `None` and leave `x`
+    /// untouched.
+    fn conjugate_gradient(&mut self,
+        _delta_x:           f32,
+        _delta_y:           f32,
+        _aspect:            f32,
+        _tolerance:         f32,
+        _max_iterations:    usize,
+        _boundaries:        &Boundaries,
+        _obstacles:         &Self::Field,
+        _x:                 &mut Self::Field,
+        _b:                 &Self::Field,
+    ) -> Option<PcgReport> {
+        None
+    }
+
+    /// Full weighting restriction of `src` onto the coarser `dst`: each of
+    /// its texels is the average of the fine texels its cell covers, weighted
+    /// by how much of each it covers, so odd sizes restrict as well as even.
+    fn restrict(&mut self,
+        src:        &Self::Field,
+        dst:        &mut Self::Field,
+    );
+
+    /// Writes `x` plus the coarser correction `e` interpolated onto its grid.
+    fn prolongate(&mut self,
+        x:          &Self::Field,
+        e:          &Self::Field,
+        dst:        &mut Self::Field,
+    );
+
+    /// Divergence of `w`. On a staggered field the flow through the faces on
+    /// the edges of the domain follows `boundaries`.
+    fn divergence(&mut self,
+        delta_x:    f32,
+        delta_y:    f32,
+        aspect:     f32,
+        layout:     GridLayout,
+        boundaries: &Boundaries,
+        obstacles:  &Self::Field,
+        w:          &Self::Field,
+        dst:        &mut Self::Field,
+    );
+
+    fn subtract(&mut self,
+        delta_x:    f32,
+        delta_y:    f32,
+        aspect:     f32,
+        layout:     GridLayout,
+        obstacles:  &Self::Field,
+        p:          &Self::Field,
+        w:          &Self::Field,
+        dst:        &mut Self::Field,
+    );
+
+    /// Sets the edge texels of a collocated field from their neighbours
+    /// inside as `boundaries` asks of `field`; periodic edges are left alone.
+    /// On a staggered velocity field it sets the faces on the left and bottom
+    /// edges instead.
+    fn boundary(&mut self,
+        delta_x:    f32,
+        delta_y:    f32,
+        boundaries: &Boundaries,
+        field:      BoundaryField,
+        layout:     GridLayout,
+        x:          &Self::Field,
+        dst:        &mut Self::Field,
+    );
+
+    /// Applies `slip` at the solid cells of `obstacles`. A solid cell of a
+    /// collocated field takes the mirror image of the velocity of its fluid
+    /// neighbours about the velocity of the solid, so interpolating across
+    /// the surface meets the condition; on a staggered field the faces of
+    /// solid cells take the velocity of the solid, and `slip` is unused.
+    fn obstacle_boundary(&mut self,
+        delta_x:    f32,
+        delta_y:    f32,
+        slip:       Slip,
+        layout:     GridLayout,
+        obstacles:  &Self::Field,
+        x:          &Self::Field,
+        dst:        &mut Self::Field,
+    );
+
+    /// Copies the mask `obstacles` into `dst` with the footprint of `body`
+    /// made solid and moving with the body.
+    fn body(&mut self,
+        aspect:     f32,
+        body:       &RigidBody,
+        obstacles:  &Self::Field,
+        dst:        &mut Self::Field,
+    );
+
+    /// Draws `force` into the velocity around `impulse_pos` with `brush`.
+    fn force(&mut self,
+        delta_t:        f32,
+        brush:          &Brush,
+        aspect:         f32,
+        force:          &Vector2<f32>,
+        impulse_pos:    &Vector2<f32>,
+        velocity_field: &Self::Field,
+        dst:            &mut Self::Field,
+    );
+
+    /// Draws `color` into the dye around `impulse_pos` with `brush`.
+    fn color(&mut self,
+        delta_t:        f32,
+        brush:          &Brush,
+        aspect:         f32,
+        color:          &Vector3<f32>,
+        impulse_pos:    &Vector2<f32>,
+        color_field:    &Self::Field,
+        dst:            &mut Self::Field,
+    );
+
+    fn vorticity_confinement(&mut self,
+        delta_t:    f32,
+        delta_x:    f32,
+        delta_y:    f32,
+        aspect:     f32,
+        vorticity:  f32,
+        layout:     GridLayout,
+        v:          &Self::Field,
+        dst:        &mut Self::Field,
+    );
+
+    /// Adds the buoyancy of `smoke_field`, temperature in red and smoke
+    /// density in green, to the vertical velocity: `lift` per degree above
+    /// `ambient` up and `weight` per unit of density down.
+    fn buoyancy(&mut self,
+        delta_t:        f32,
+        delta_y:        f32,
+        ambient:        f32,
+        lift:           f32,
+        weight:         f32,
+        layout:         GridLayout,
+        smoke_field:    &Self::Field,
+        velocity_field: &Self::Field,
+        dst:            &mut Self::Field,
+    );
+
+    /// Adds `heat`, temperature and smoke density per second, around
+    /// `impulse_pos` with `brush`.
+    fn heat(&mut self,
+        delta_t:        f32,
+        brush:          &Brush,
+        aspect:         f32,
+        heat:           &Vector2<f32>,
+        impulse_pos:    &Vector2<f32>,
+        smoke_field:    &Self::Field,
+        dst:            &mut Self::Field,
+    );
+}
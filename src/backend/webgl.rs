@@ -0,0 +1,444 @@
+use web_sys::WebGlRenderingContext as GL;
+use wasm_bindgen::JsValue;
+use nalgebra::{Vector2, Vector3};
+
+use crate::backend::{Boundaries, BoundaryField, GridLayout, Integrator, Slip, SolverBackend};
+use crate::brush::Brush;
+use crate::diagnostics::Norms;
+use crate::geometry;
+use crate::obstacle::RigidBody;
+use crate::render;
+use crate::render_fluid::{self, Reduction};
+use crate::shader;
+use crate::texture;
+
+use std::rc::Rc;
+
+/// Runs the solver passes as WebGL fragment shaders through `render_fluid`.
+pub struct WebGlBackend {
+    gl: GL,
+
+    advect_pass:        render::RenderPass<'static>,
+    maccormack_pass:    render::RenderPass<'static>,
+    quad_pass:          render::RenderPass<'static>,
+    jacobi_pass:        render::RenderPass<'static>,
+    residual_pass:      render::RenderPass<'static>,
+    restrict_pass:      render::RenderPass<'static>,
+    prolong_pass:       render::RenderPass<'static>,
+    reduce_pass:        render::RenderPass<'static>,
+    pack_pass:          render::RenderPass<'static>,
+    divergence_pass:    render::RenderPass<'static>,
+    subtract_pass:      render::RenderPass<'static>,
+    boundary_pass:      render::RenderPass<'static>,
+    obstacle_pass:      render::RenderPass<'static>,
+    body_pass:          render::RenderPass<'static>,
+    force_pass:         render::RenderPass<'static>,
+    color_pass:         render::RenderPass<'static>,
+    vorticity_pass:     render::RenderPass<'static>,
+    buoyancy_pass:      render::RenderPass<'static>,
+    heat_pass:          render::RenderPass<'static>,
+
+    // halving chain for the norm reductions, ending at a single texel
+    reduce_levels:      Vec<Field>,
+    // whether fields can be read back as floats; without it they are packed
+    // into bytes first
+    float_readback:     bool,
+}
+
+impl WebGlBackend {
+    pub fn new(gl: &GL) -> Result<WebGlBackend, JsValue> {
+        let standard_vert_shader = shader::compile_shader(gl, GL::VERTEX_SHADER, shader::STANDARD_VERTEX_SHADER)?;
+        let quad_frag_shader = shader::compile_shader(gl, GL::FRAGMENT_SHADER, shader::QUAD_FRAGMENT_SHADER)?;
+        let advect_frag_shader = shader::compile_shader(gl, GL::FRAGMENT_SHADER, &shader::with_wrap(shader::ADVECT_FRAGMENT_SHADER))?;
+        let maccormack_frag_shader = shader::compile_shader(gl, GL::FRAGMENT_SHADER, &shader::with_wrap(shader::MACCORMACK_FRAGMENT_SHADER))?;
+        let jacobi_frag_shader = shader::compile_shader(gl, GL::FRAGMENT_SHADER, &shader::with_wrap(shader::JACOBI_FRAGMENT_SHADER))?;
+        let residual_frag_shader = shader::compile_shader(gl, GL::FRAGMENT_SHADER, &shader::with_wrap(shader::RESIDUAL_FRAGMENT_SHADER))?;
+        let restrict_frag_shader = shader::compile_shader(gl, GL::FRAGMENT_SHADER, shader::RESTRICT_FRAGMENT_SHADER)?;
+        let prolong_frag_shader = shader::compile_shader(gl, GL::FRAGMENT_SHADER, &shader::with_wrap(shader::PROLONG_FRAGMENT_SHADER))?;
+        let reduce_frag_shader = shader::compile_shader(gl, GL::FRAGMENT_SHADER, shader::REDUCE_FRAGMENT_SHADER)?;
+        let pack_frag_shader = shader::compile_shader(gl, GL::FRAGMENT_SHADER, shader::PACK_FRAGMENT_SHADER)?;
+        let divergence_frag_shader = shader::compile_shader(gl, GL::FRAGMENT_SHADER, &shader::with_wrap(shader::DIVERGE_FRAGMENT_SHADER))?;
+        let subtract_frag_shader = shader::compile_shader(gl, GL::FRAGMENT_SHADER, &shader::with_wrap(shader::SUB_FRAGMENT_SHADER))?;
+        let bound_frag_shader = shader::compile_shader(gl, GL::FRAGMENT_SHADER, &shader::with_wrap(shader::BOUND_FRAGMENT_SHADER))?;
+        let obstacle_frag_shader = shader::compile_shader(gl, GL::FRAGMENT_SHADER, &shader::with_wrap(shader::OBSTACLE_FRAGMENT_SHADER))?;
+        let body_frag_shader = shader::compile_shader(gl, GL::FRAGMENT_SHADER, shader::BODY_FRAGMENT_SHADER)?;
+        let force_frag_shader = shader::compile_shader(gl, GL::FRAGMENT_SHADER, &shader::with_wrap(&shader::with_brush(shader::FORCE_FRAGMENT_SHADER)))?;
+        let color_frag_shader = shader::compile_shader(gl, GL::FRAGMENT_SHADER, &shader::with_wrap(&shader::with_brush(shader::COLOR_FRAGMENT_SHADER)))?;
+        let vorticity_frag_shader = shader::compile_shader(gl, GL::FRAGMENT_SHADER, &shader::with_wrap(shader::VORT_FRAGMENT_SHADER))?;
+        let buoyancy_frag_shader = shader::compile_shader(gl, GL::FRAGMENT_SHADER, &shader::with_wrap(shader::BUOYANCY_FRAGMENT_SHADER))?;
+        let heat_frag_shader = shader::compile_shader(gl, GL::FRAGMENT_SHADER, &shader::with_wrap(&shader::with_brush(shader::HEAT_FRAGMENT_SHADER)))?;
+
+        let advect_pass = render::RenderPass::new(gl,
+            [&standard_vert_shader, &advect_frag_shader],
+            vec!["delta_x", "delta_y", "aspect", "vec_field_texture",  "color_field_texture", "obstacles", "delta_t", "dissipation", "integrator", "staggered", "staggered_src", "src_texel", "periodic"], "vertex_position",
+            &geometry::QUAD_VERTICES, &geometry::QUAD_INDICES,
+        )?;
+
+        let maccormack_pass = render::RenderPass::new(gl,
+            [&standard_vert_shader, &maccormack_frag_shader],
+            vec!["delta_x", "delta_y", "aspect", "delta_t", "dissipation", "integrator", "staggered", "staggered_src", "src_texel", "vec_field_texture", "color_field_texture", "forward_texture", "backward_texture", "obstacles", "periodic"], "vertex_position",
+            &geometry::QUAD_VERTICES, &geometry::QUAD_INDICES,
+        )?;
+
+        let quad_pass = render::RenderPass::new(gl,
+            [&standard_vert_shader, &quad_frag_shader],
+            vec!["qtexture"], "vertex_position",
+            &geometry::QUAD_VERTICES, &geometry::QUAD_INDICES,
+        )?;
+
+        let jacobi_pass = render::RenderPass::new(gl,
+            [&standard_vert_shader, &jacobi_frag_shader],
+            vec!["delta_x", "delta_y", "aspect", "alpha", "r_beta", "omega", "parity", "x", "b", "obstacles", "periodic"], "vertex_position",
+            &geometry::QUAD_VERTICES, &geometry::QUAD_INDICES,
+        )?;
+
+        let residual_pass = render::RenderPass::new(gl,
+            [&standard_vert_shader, &residual_frag_shader],
+            vec!["delta_x", "delta_y", "aspect", "x", "b", "obstacles", "periodic"], "vertex_position",
+            &geometry::QUAD_VERTICES, &geometry::QUAD_INDICES,
+        )?;
+
+        let restrict_pass = render::RenderPass::new(gl,
+            [&standard_vert_shader, &restrict_frag_shader],
+            vec!["x", "fine_texel", "coarse_texel"], "vertex_position",
+            &geometry::QUAD_VERTICES, &geometry::QUAD_INDICES,
+        )?;
+
+        let prolong_pass = render::RenderPass::new(gl,
+            [&standard_vert_shader, &prolong_frag_shader],
+            vec!["x", "e", "coarse_texel", "periodic"], "vertex_position",
+            &geometry::QUAD_VERTICES, &geometry::QUAD_INDICES,
+        )?;
+
+        let reduce_pass = render::RenderPass::new(gl,
+            [&standard_vert_shader, &reduce_frag_shader],
+            vec!["src_size", "first", "x"], "vertex_position",
+            &geometry::QUAD_VERTICES, &geometry::QUAD_INDICES,
+        )?;
+
+        let pack_pass = render::RenderPass::new(gl,
+            [&standard_vert_shader, &pack_frag_shader],
+            vec!["x", "channel"], "vertex_position",
+            &geometry::QUAD_VERTICES, &geometry::QUAD_INDICES,
+        )?;
+
+        let divergence_pass = render::RenderPass::new(gl,
+            [&standard_vert_shader, &divergence_frag_shader],
+            vec!["delta_x", "delta_y", "aspect", "staggered", "w", "obstacles", "periodic",
+                "left", "right", "bottom", "top", "left_inflow", "right_inflow", "bottom_inflow", "top_inflow"], "vertex_position",
+            &geometry::QUAD_VERTICES, &geometry::QUAD_INDICES,
+        )?;
+
+        let subtract_pass = render::RenderPass::new(gl,
+            [&standard_vert_shader, &subtract_frag_shader],
+            vec!["delta_x", "delta_y", "aspect", "staggered", "p", "w", "obstacles", "periodic"], "vertex_position",
+            &geometry::QUAD_VERTICES, &geometry::QUAD_INDICES,
+        )?;
+
+        let boundary_pass = render::RenderPass::new(gl,
+            [&standard_vert_shader, &bound_frag_shader],
+            vec!["delta_x", "delta_y", "field", "staggered", "x", "periodic",
+                "left", "right", "bottom", "top", "left_inflow", "right_inflow", "bottom_inflow", "top_inflow"], "vertex_position",
+            &geometry::QUAD_VERTICES, &geometry::QUAD_INDICES,
+        )?;
+
+        let obstacle_pass = render::RenderPass::new(gl,
+            [&standard_vert_shader, &obstacle_frag_shader],
+            vec!["delta_x", "delta_y", "slip", "staggered", "obstacles", "x", "periodic"], "vertex_position",
+            &geometry::QUAD_VERTICES, &geometry::QUAD_INDICES,
+        )?;
+
+        let body_pass = render::RenderPass::new(gl,
+            [&standard_vert_shader, &body_frag_shader],
+            vec!["aspect", "shape", "position", "angle", "radius", "half_size", "vertices", "vertex_count", "velocity", "angular_velocity", "obstacles"], "vertex_position",
+            &geometry::QUAD_VERTICES, &geometry::QUAD_INDICES,
+        )?;
+
+        let force_pass = render::RenderPass::new(gl,
+            [&standard_vert_shader, &force_frag_shader],
+            vec!["delta_t", "aspect", "force", "impulse_pos", "velocity_field_texture", "radius", "falloff", "strength", "blend", "periodic"], "vertex_position",
+            &geometry::QUAD_VERTICES, &geometry::QUAD_INDICES,
+        )?;
+
+        let color_pass = render::RenderPass::new(gl,
+            [&standard_vert_shader, &color_frag_shader],
+            vec!["delta_t", "aspect", "color", "impulse_pos", "color_field_texture", "radius", "falloff", "strength", "blend", "periodic"], "vertex_position",
+            &geometry::QUAD_VERTICES, &geometry::QUAD_INDICES,
+        )?;
+
+        let vorticity_pass = render::RenderPass::new(gl,
+            [&standard_vert_shader, &vorticity_frag_shader],
+            vec!["delta_t", "delta_x", "delta_y", "aspect", "vorticity", "staggered", "v", "periodic"], "vertex_position",
+            &geometry::QUAD_VERTICES, &geometry::QUAD_INDICES,
+        )?;
+
+        let buoyancy_pass = render::RenderPass::new(gl,
+            [&standard_vert_shader, &buoyancy_frag_shader],
+            vec!["delta_t", "delta_y", "ambient", "lift", "weight", "staggered", "velocity_field_texture", "smoke_field_texture", "smoke_texel", "periodic"], "vertex_position",
+            &geometry::QUAD_VERTICES, &geometry::QUAD_INDICES,
+        )?;
+
+        let heat_pass = render::RenderPass::new(gl,
+            [&standard_vert_shader, &heat_frag_shader],
+            vec!["delta_t", "aspect", "heat", "impulse_pos", "smoke_field_texture", "radius", "falloff", "strength", "blend", "periodic"], "vertex_position",
+            &geometry::QUAD_VERTICES, &geometry::QUAD_INDICES,
+        )?;
+
+        Ok(WebGlBackend {
+            gl: gl.clone(),
+
+            advect_pass,
+            maccormack_pass,
+            quad_pass,
+            jacobi_pass,
+            residual_pass,
+            restrict_pass,
+            prolong_pass,
+            reduce_pass,
+            pack_pass,
+            divergence_pass,
+            subtract_pass,
+            boundary_pass,
+            obstacle_pass,
+            body_pass,
+            force_pass,
+            color_pass,
+            vorticity_pass,
+            buoyancy_pass,
+            heat_pass,
+
+            reduce_levels: Vec::new(),
+            float_readback: gl.get_extension("WEBGL_color_buffer_float")?.is_some(),
+        })
+    }
+
+    // reads `field` back as floats, or where the device cannot, as the bytes
+    // of each channel packed by pack.frag
+    fn read_back(&mut self, field: &texture::Framebuffer) -> Result<Vec<f32>, JsValue> {
+        if self.float_readback {
+            match field.read_pixels(&self.gl) {
+                Ok(pixels) => return Ok(pixels),
+                Err(_) => self.float_readback = false,
+            }
+        }
+
+        let bytes = texture::Framebuffer::new_bytes(&self.gl, field.width(), field.height())?;
+        let mut pixels = vec![0.0; (field.width() * field.height() * 4) as usize];
+        let mut result = Ok(());
+        for channel in 0..4 {
+            render_fluid::pack(&self.gl, &self.pack_pass, field, channel, &bytes);
+            match bytes.read_bytes(&self.gl) {
+                Ok(data) => for (k, b) in data.chunks(4).enumerate() {
+                    pixels[k * 4 + channel] = f32::from_bits(u32::from_be_bytes([b[0], b[1], b[2], b[3]]));
+                },
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+        bytes.delete_buffers(&self.gl);
+
+        result.map(|_| pixels)
+    }
+
+    // runs the reduction passes down to a single texel and reads it back
+    fn reduce(&mut self, field: &texture::Framebuffer, reduction: Reduction) -> Result<Vec<f32>, JsValue> {
+        // rebuild the chain when the field size changes
+        let first_size = ((field.width() + 1) / 2, (field.height() + 1) / 2);
+        if self.reduce_levels.first().map(|l| (l.width(), l.height())) != Some(first_size) {
+            for level in self.reduce_levels.drain(..) {
+                level.delete_buffers(&self.gl);
+            }
+
+            let (mut w, mut h) = first_size;
+            loop {
+                self.reduce_levels.push(Rc::new(texture::Framebuffer::new(&self.gl, w, h)?));
+                if w == 1 && h == 1 {
+                    break;
+                }
+                w = (w + 1) / 2;
+                h = (h + 1) / 2;
+            }
+        }
+
+        let mut first = Some(reduction);
+        let mut src = field;
+        for level in self.reduce_levels.iter() {
+            render_fluid::reduce(&self.gl, &self.reduce_pass, first.take(), src, Rc::clone(level));
+            src = level.as_ref();
+        }
+
+        // the single texel at the end, which `read_back` needs apart from `self`
+        let src = Rc::clone(self.reduce_levels.last().unwrap());
+        self.read_back(&src)
+    }
+
+    /// Draws `field` into `target`, or to the canvas when `None`.
+    pub fn render_to(&self, field: &texture::Framebuffer, target: Option<&texture::Framebuffer>) {
+        let gl = &self.gl;
+
+        match target {
+            Some(fb) => fb.bind(gl),
+            // the canvas may have been resized since the last pass
+            None => gl.viewport(0, 0, gl.drawing_buffer_width(), gl.drawing_buffer_height()),
+        }
+        render::clear_framebuffer(gl);
+
+        self.quad_pass.use_program(gl);
+        gl.uniform1i(self.quad_pass.uniforms["qtexture"].as_ref(), 0);
+
+        gl.active_texture(GL::TEXTURE0);
+        gl.bind_texture(GL::TEXTURE_2D, Some(field.get_texture()));
+
+        gl.bind_buffer(GL::ARRAY_BUFFER, Some(&self.quad_pass.vertex_buffer));
+        gl.vertex_attrib_pointer_with_i32(0, 3, GL::FLOAT, false, 0, 0);
+        gl.enable_vertex_attrib_array(0);
+
+        gl.bind_buffer(GL::ELEMENT_ARRAY_BUFFER, Some(&self.quad_pass.index_buffer));
+
+        gl.draw_elements_with_i32(GL::TRIANGLES, 6, GL::UNSIGNED_SHORT, 0);
+
+        if let Some(fb) = target {
+            fb.unbind(gl);
+        }
+    }
+}
+
+type Field = Rc<texture::Framebuffer>;
+
+impl SolverBackend for WebGlBackend {
+    type Field = Field;
+    type Error = JsValue;
+
+    fn create_field(&mut self, width: i32, height: i32) -> Result<Field, JsValue> {
+        Ok(Rc::new(texture::Framebuffer::new(&self.gl, width, height)?))
+    }
+
+    fn create_field_with_data(&mut self, width: i32, height: i32, data: Vec<f32>) -> Result<Field, JsValue> {
+        Ok(Rc::new(texture::Framebuffer::create_with_data(&self.gl, width, height, data)?))
+    }
+
+    fn delete_field(&mut self, field: Field) {
+        field.delete_buffers(&self.gl);
+    }
+
+    fn read_field(&mut self, field: &Field) -> Result<Vec<f32>, JsValue> {
+        self.read_back(field)
+    }
+
+    fn clear_field(&mut self, field: &mut Field) {
+        field.bind(&self.gl);
+        render::clear_framebuffer(&self.gl);
+        field.unbind(&self.gl);
+    }
+
+    fn set_periodic(&mut self, field: &mut Field, periodic_x: bool, periodic_y: bool) {
+        field.set_periodic(periodic_x, periodic_y);
+    }
+
+    fn resample(&mut self, src: &Field, dst: &mut Field) {
+        self.render_to(src, Some(dst));
+    }
+
+    fn norms(&mut self, field: &Field) -> Result<Norms, JsValue> {
+        let texel = self.reduce(field, Reduction::Red)?;
+        Ok(Norms::from_reduction(texel[0], texel[1], (field.width() * field.height()) as usize))
+    }
+
+    fn max_speed(&mut self, field: &Field) -> Result<f32, JsValue> {
+        Ok(self.reduce(field, Reduction::Speed)?[1])
+    }
+
+    fn advection(&mut self, delta_x: f32, delta_y: f32, aspect: f32, delta_t: f32, dissipation: f32, integrator: Integrator, layout: GridLayout, src_layout: GridLayout, obstacles: &Field, src: &Field, vector_field: &Field, dst: &mut Field) {
+        render_fluid::advection(&self.gl, &self.advect_pass,
+            delta_x, delta_y, aspect, delta_t, dissipation, integrator, layout, src_layout, obstacles, Rc::clone(src), vector_field, Rc::clone(dst));
+    }
+
+    fn maccormack(&mut self, delta_x: f32, delta_y: f32, aspect: f32, delta_t: f32, dissipation: f32, integrator: Integrator, layout: GridLayout, src_layout: GridLayout, obstacles: &Field, src: &Field, vector_field: &Field, forward: &Field, backward: &Field, dst: &mut Field) {
+        render_fluid::maccormack(&self.gl, &self.maccormack_pass,
+            delta_x, delta_y, aspect, delta_t, dissipation, integrator, layout, src_layout, obstacles, Rc::clone(src), vector_field, forward, backward, Rc::clone(dst));
+    }
+
+    fn jacobi_method(&mut self, iter: usize, delta_x: f32, delta_y: f32, aspect: f32, alpha: f32, r_beta: f32, obstacles: &Field, x: &mut Field, b: &Field, dst: &mut Field) {
+        render_fluid::jacobi_method(&self.gl, &self.jacobi_pass, iter,
+            delta_x, delta_y, aspect, alpha, r_beta, obstacles, Rc::clone(x), b, Rc::clone(dst));
+    }
+
+    fn jacobi_iteration(&mut self, delta_x: f32, delta_y: f32, aspect: f32, alpha: f32, r_beta: f32, obstacles: &Field, x: &Field, b: &Field, dst: &mut Field) {
+        dst.bind(&self.gl);
+        render_fluid::jacobi_iteration(&self.gl, &self.jacobi_pass, delta_x, delta_y, aspect, alpha, r_beta, obstacles, x, b);
+        dst.unbind(&self.gl);
+    }
+
+    fn weighted_jacobi_iteration(&mut self, delta_x: f32, delta_y: f32, aspect: f32, alpha: f32, r_beta: f32, omega: f32, obstacles: &Field, x: &Field, b: &Field, dst: &mut Field) {
+        dst.bind(&self.gl);
+        render_fluid::weighted_jacobi_iteration(&self.gl, &self.jacobi_pass, delta_x, delta_y, aspect, alpha, r_beta, omega, obstacles, x, b);
+        dst.unbind(&self.gl);
+    }
+
+    fn red_black_iteration(&mut self, delta_x: f32, delta_y: f32, aspect: f32, alpha: f32, r_beta: f32, omega: f32, obstacles: &Field, x: &mut Field, b: &Field, scratch: &mut Field) {
+        render_fluid::red_black_iteration(&self.gl, &self.jacobi_pass,
+            delta_x, delta_y, aspect, alpha, r_beta, omega, obstacles, Rc::clone(x), b, Rc::clone(scratch));
+    }
+
+    fn residual(&mut self, delta_x: f32, delta_y: f32, aspect: f32, obstacles: &Field, x: &Field, b: &Field, dst: &mut Field) {
+        render_fluid::residual(&self.gl, &self.residual_pass, delta_x, delta_y, aspect, obstacles, x, b, Rc::clone(dst));
+    }
+
+    fn restrict(&mut self, src: &Field, dst: &mut Field) {
+        render_fluid::restrict(&self.gl, &self.restrict_pass, src, Rc::clone(dst));
+    }
+
+    fn prolongate(&mut self, x: &Field, e: &Field, dst: &mut Field) {
+        render_fluid::prolongate(&self.gl, &self.prolong_pass, Rc::clone(x), e, Rc::clone(dst));
+    }
+
+    fn divergence(&mut self, delta_x: f32, delta_y: f32, aspect: f32, layout: GridLayout, boundaries: &Boundaries, obstacles: &Field, w: &Field, dst: &mut Field) {
+        render_fluid::divergence(&self.gl, &self.divergence_pass, delta_x, delta_y, aspect, layout, boundaries, obstacles, w, Rc::clone(dst));
+    }
+
+    fn subtract(&mut self, delta_x: f32, delta_y: f32, aspect: f32, layout: GridLayout, obstacles: &Field, p: &Field, w: &Field, dst: &mut Field) {
+        render_fluid::subtract(&self.gl, &self.subtract_pass,
+            delta_x, delta_y, aspect, layout, obstacles, p, Rc::clone(w), Rc::clone(dst));
+    }
+
+    fn boundary(&mut self, delta_x: f32, delta_y: f32, boundaries: &Boundaries, field: BoundaryField, layout: GridLayout, x: &Field, dst: &mut Field) {
+        render_fluid::boundary(&self.gl, &self.boundary_pass,
+            delta_x, delta_y, boundaries, field, layout, Rc::clone(x), Rc::clone(dst));
+    }
+
+    fn obstacle_boundary(&mut self, delta_x: f32, delta_y: f32, slip: Slip, layout: GridLayout, obstacles: &Field, x: &Field, dst: &mut Field) {
+        render_fluid::obstacle_boundary(&self.gl, &self.obstacle_pass,
+            delta_x, delta_y, slip, layout, obstacles, Rc::clone(x), Rc::clone(dst));
+    }
+
+    fn body(&mut self, aspect: f32, body: &RigidBody, obstacles: &Field, dst: &mut Field) {
+        render_fluid::body(&self.gl, &self.body_pass, aspect, body, Rc::clone(obstacles), Rc::clone(dst));
+    }
+
+    fn force(&mut self, delta_t: f32, brush: &Brush, aspect: f32, force: &Vector2<f32>, impulse_pos: &Vector2<f32>, velocity_field: &Field, dst: &mut Field) {
+        render_fluid::force(&self.gl, &self.force_pass,
+            delta_t, brush, aspect, force, impulse_pos, Rc::clone(velocity_field), Rc::clone(dst));
+    }
+
+    fn color(&mut self, delta_t: f32, brush: &Brush, aspect: f32, color: &Vector3<f32>, impulse_pos: &Vector2<f32>, color_field: &Field, dst: &mut Field) {
+        render_fluid::color(&self.gl, &self.color_pass,
+            delta_t, brush, aspect, color, impulse_pos, Rc::clone(color_field), Rc::clone(dst));
+    }
+
+    fn vorticity_confinement(&mut self, delta_t: f32, delta_x: f32, delta_y: f32, aspect: f32, vorticity: f32, layout: GridLayout, v: &Field, dst: &mut Field) {
+        render_fluid::vorticity_confinement(&self.gl, &self.vorticity_pass,
+            delta_t, delta_x, delta_y, aspect, vorticity, layout, Rc::clone(v), Rc::clone(dst));
+    }
+
+    fn buoyancy(&mut self, delta_t: f32, delta_y: f32, ambient: f32, lift: f32, weight: f32, layout: GridLayout, smoke_field: &Field, velocity_field: &Field, dst: &mut Field) {
+        render_fluid::buoyancy(&self.gl, &self.buoyancy_pass,
+            delta_t, delta_y, ambient, lift, weight, layout, smoke_field, Rc::clone(velocity_field), Rc::clone(dst));
+    }
+
+    fn heat(&mut self, delta_t: f32, brush: &Brush, aspect: f32, heat: &Vector2<f32>, impulse_pos: &Vector2<f32>, smoke_field: &Field, dst: &mut Field) {
+        render_fluid::heat(&self.gl, &self.heat_pass,
+            delta_t, brush, aspect, heat, impulse_pos, Rc::clone(smoke_field), Rc::clone(dst));
+    }
+}
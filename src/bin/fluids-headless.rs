@@ -0,0 +1,564 @@
+// Runs the solver on the CPU backend without a browser and writes the dye
+// field out as a numbered PNG sequence.
+//
+//     fluids-headless --width 256 --height 256 --steps 300 \
+//         --vector-field Circle --color-field Black \
+//         --splat 0,0.5,0.5,500,0,1,0.2,0.2 --output frames
+
+use fluids::backend::{CpuBackend, Edge, GridLayout, Integrator, Slip};
+use fluids::simulation::{AdvectionScheme, FluidSimulation, PressureSolver, Relaxation, SimulationParams};
+use fluids::brush::{Blend, Brush, Falloff};
+use fluids::emitter::{Emitter, Schedule, Source};
+use fluids::obstacle::{self, Shape};
+use fluids::texture;
+
+use nalgebra::{Vector2, Vector3};
+
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::process;
+
+const USAGE: &str = "usage: fluids-headless [options]
+
+options:
+    --width N               grid width (default 256)
+    --height N              grid height (default 256)
+    --dye-width N           width of the dye grid and the frames written
+                            (default the grid width)
+    --dye-height N          height of the dye grid and the frames written
+                            (default the grid height)
+    --steps N               number of steps to simulate (default 120)
+    --delta-t F             seconds per step (default 1/60)
+    --vector-field NAME     initial velocity: Static, Constant, Divergent, Sine,
+                            Circle, Rotational or Waves (default Waves)
+    --color-field NAME      initial dye: White, Black, Checkerboard, White/Black,
+                            Red/Blue or Rainbow (default Rainbow)
+    --grid NAME             velocity layout: collocated or staggered (default collocated)
+    --jacobi N              jacobi iterations per solve (default 20)
+    --viscosity F           kinematic viscosity (default 1e-6)
+    --vorticity F           vorticity confinement strength (default 10)
+    --advection NAME        semi-lagrangian or maccormack (default semi-lagrangian)
+    --integrator NAME       backtrace integrator: euler, midpoint or rk3 (default euler)
+    --pressure NAME         pressure solver: jacobi, multigrid or pcg (default jacobi)
+    --multigrid-cycles N    V-cycles per multigrid solve (default 2)
+    --pressure-relaxation R smoother for the jacobi pressure solve: jacobi, or sor
+                            with an optional factor as sor=OMEGA (default jacobi)
+    --diffusion-relaxation R
+                            smoother for the viscous diffusion, as above
+    --pcg-tolerance F       residual the pcg solve stops at (default 1e-3)
+    --pcg-iterations N      iteration cap for the pcg solve (default 200)
+    --cfl F                 most cells the flow may cross per substep (default 2)
+    --max-substeps N        cap on the substeps per step, 1 disables them (default 4)
+    --velocity-dissipation F
+                            fraction of the velocity lost per second (default 0)
+    --dye-dissipation F     fraction of the dye lost per second (default 0)
+    --smoke-dissipation F   fraction of the temperature and smoke lost per
+                            second (default 0)
+    --splat S               add a splat, S = step,x,y,fx,fy,r,g,b with x and y in
+                            [0, 1]; may be repeated
+    --splats FILE           read splats from FILE, one per line, '#' comments
+    --force-brush B         brush for the force and heat splats and emitters,
+                            B = radius,falloff,strength,blend with the radius in
+                            domain widths, falloff one of hard, gaussian or
+                            smoothstep and blend one of replace, additive or
+                            alpha-over (default 0.0316,gaussian,1,additive)
+    --dye-brush B           brush for the dye, as above
+                            (default 0.025,hard,1,replace)
+    --emitter E             add a continuous source, E = SOURCE:DX,DY,RATE with
+                            SOURCE one of point:X,Y, line:X0,Y0,X1,Y1 or
+                            region:X0,Y0,X1,Y1 in [0, 1], followed by an
+                            optional :R,G,B dye and an optional schedule
+                            :between=START,END or :pulse=PERIOD,ON in seconds;
+                            may be repeated
+    --smoke                 carry temperature and smoke density with buoyancy
+    --ambient F             ambient temperature (default 0)
+    --lift F                buoyancy per degree above ambient (default 1)
+    --weight F              downward force per unit of smoke density (default 0.1)
+    --heat H                add a heat splat, H = step,x,y,temperature,density
+                            in units per second; may be repeated
+    --circle C              add a solid circle, C = x,y,r with the center in
+                            [0, 1] and the radius in domain widths; may be
+                            repeated
+    --rect R                add a solid rectangle, R = x0,y0,x1,y1 in [0, 1];
+                            may be repeated
+    --obstacles FILE        add the dark pixels of the PNG image FILE, stretched
+                            over the grid, as solids
+    --slip NAME             obstacle surfaces: no-slip or free-slip
+                            (default no-slip)
+    --left EDGE, --right EDGE, --bottom EDGE, --top EDGE
+                            condition on that edge of the domain: no-slip,
+                            free-slip, periodic, outflow or inflow:VX,VY
+                            (default no-slip)
+    --diagnostics           print divergence and residual norms after every step
+    --every N               write a frame every N steps (default 1)
+    --output DIR            directory for the frames (default frames)
+    --help                  print this message";
+
+struct Splat {
+    step:   usize,
+    pos:    Vector2<f32>,
+    force:  Vector2<f32>,
+    color:  Vector3<f32>,
+}
+
+struct Heat {
+    step:   usize,
+    pos:    Vector2<f32>,
+    heat:   Vector2<f32>,
+}
+
+struct Options {
+    width:          i32,
+    height:         i32,
+    dye_width:      Option<i32>,
+    dye_height:     Option<i32>,
+    steps:          usize,
+    delta_t:        f32,
+    vector_field:   i32,
+    color_field:    i32,
+    params:         SimulationParams,
+    splats:         Vec<Splat>,
+    heats:          Vec<Heat>,
+    emitters:       Vec<Emitter>,
+    shapes:         Vec<Shape>,
+    obstacle_image: Option<PathBuf>,
+    every:          usize,
+    diagnostics:    bool,
+    output:         PathBuf,
+}
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("{} expects a value", flag))?;
+    value.parse().map_err(|_| format!("invalid value for {}: {}", flag, value))
+}
+
+fn parse_splat(s: &str) -> Result<Splat, String> {
+    let values = s.split(',')
+        .map(|v| v.trim().parse::<f32>())
+        .collect::<Result<Vec<f32>, _>>()
+        .map_err(|_| format!("invalid splat: {}", s))?;
+
+    if values.len() != 8 || values[0] < 0.0 {
+        return Err(format!("splat should be step,x,y,fx,fy,r,g,b: {}", s));
+    }
+
+    Ok(Splat {
+        step:   values[0] as usize,
+        pos:    Vector2::new(values[1], values[2]),
+        force:  Vector2::new(values[3], values[4]),
+        color:  Vector3::new(values[5], values[6], values[7]),
+    })
+}
+
+fn parse_heat(s: &str) -> Result<Heat, String> {
+    let values = s.split(',')
+        .map(|v| v.trim().parse::<f32>())
+        .collect::<Result<Vec<f32>, _>>()
+        .map_err(|_| format!("invalid heat splat: {}", s))?;
+
+    if values.len() != 5 || values[0] < 0.0 {
+        return Err(format!("heat splat should be step,x,y,temperature,density: {}", s));
+    }
+
+    Ok(Heat {
+        step:   values[0] as usize,
+        pos:    Vector2::new(values[1], values[2]),
+        heat:   Vector2::new(values[3], values[4]),
+    })
+}
+
+// comma separated floats, exactly `count` of them
+fn parse_floats(s: &str, count: usize, what: &str) -> Result<Vec<f32>, String> {
+    let values = s.split(',')
+        .map(|v| v.trim().parse::<f32>())
+        .collect::<Result<Vec<f32>, _>>()
+        .map_err(|_| format!("invalid {}: {}", what, s))?;
+
+    if values.len() != count {
+        return Err(format!("{} should have {} values: {}", what, count, s));
+    }
+    Ok(values)
+}
+
+// radius,falloff,strength,blend
+fn parse_brush(s: &str) -> Result<Brush, String> {
+    let parts: Vec<String> = s.split(',').map(|p| p.trim().to_lowercase()).collect();
+    if parts.len() != 4 {
+        return Err(format!("brush should be radius,falloff,strength,blend: {}", s));
+    }
+
+    let radius = parts[0].parse().map_err(|_| format!("invalid brush radius: {}", parts[0]))?;
+    let falloff = match parts[1].as_str() {
+        "hard" => Falloff::Hard,
+        "gaussian" => Falloff::Gaussian,
+        "smoothstep" => Falloff::Smoothstep,
+        _ => return Err(format!("unknown brush falloff: {}", parts[1])),
+    };
+    let strength = parts[2].parse().map_err(|_| format!("invalid brush strength: {}", parts[2]))?;
+    let blend = match parts[3].as_str() {
+        "replace" => Blend::Replace,
+        "additive" => Blend::Additive,
+        "alpha-over" => Blend::AlphaOver,
+        _ => return Err(format!("unknown brush blend: {}", parts[3])),
+    };
+
+    Ok(Brush { radius, falloff, strength, blend })
+}
+
+// SOURCE:DX,DY,RATE[:R,G,B][:SCHEDULE], see the usage
+fn parse_emitter(s: &str) -> Result<Emitter, String> {
+    let lower = s.to_lowercase();
+    let mut parts = lower.split(':');
+    let kind = parts.next().unwrap();
+    let coords = parts.next().ok_or_else(|| format!("emitter should be SOURCE:DX,DY,RATE: {}", s))?;
+    let source = match kind {
+        "point" => {
+            let v = parse_floats(coords, 2, "emitter point")?;
+            Source::Point(Vector2::new(v[0], v[1]))
+        }
+        "line" => {
+            let v = parse_floats(coords, 4, "emitter line")?;
+            Source::Line { start: Vector2::new(v[0], v[1]), end: Vector2::new(v[2], v[3]) }
+        }
+        "region" => {
+            let v = parse_floats(coords, 4, "emitter region")?;
+            Source::Region { min: Vector2::new(v[0], v[1]), max: Vector2::new(v[2], v[3]) }
+        }
+        _ => return Err(format!("unknown emitter source: {}", kind)),
+    };
+
+    let push = parts.next().ok_or_else(|| format!("emitter should be SOURCE:DX,DY,RATE: {}", s))?;
+    let v = parse_floats(push, 3, "emitter direction and rate")?;
+    let mut emitter = Emitter::new(source, Vector2::new(v[0], v[1]), v[2]);
+
+    for part in parts {
+        if let Some(window) = part.strip_prefix("between=") {
+            let v = parse_floats(window, 2, "emitter schedule")?;
+            emitter.schedule = Schedule::Between { start: v[0], end: v[1] };
+        } else if let Some(pulse) = part.strip_prefix("pulse=") {
+            let v = parse_floats(pulse, 2, "emitter schedule")?;
+            emitter.schedule = Schedule::Pulse { period: v[0], on: v[1] };
+        } else {
+            let v = parse_floats(part, 3, "emitter dye")?;
+            emitter.color = Some(Vector3::new(v[0], v[1], v[2]));
+        }
+    }
+    Ok(emitter)
+}
+
+// an edge condition, `inflow:VX,VY` for an inflow
+fn parse_edge(s: &str) -> Result<Edge, String> {
+    let s = s.to_lowercase();
+    if let Some(velocity) = s.strip_prefix("inflow:") {
+        let values = parse_floats(velocity, 2, "inflow velocity")?;
+        return Ok(Edge::Inflow(Vector2::new(values[0], values[1])));
+    }
+
+    match s.as_str() {
+        "no-slip" => Ok(Edge::NoSlip),
+        "free-slip" => Ok(Edge::FreeSlip),
+        "periodic" => Ok(Edge::Periodic),
+        "outflow" => Ok(Edge::Outflow),
+        _ => Err(format!("unknown edge condition: {}", s)),
+    }
+}
+
+fn read_splats(path: &Path) -> Result<Vec<Splat>, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+
+    contents.lines()
+        .map(|line| line.split('#').next().unwrap().trim())
+        .filter(|line| !line.is_empty())
+        .map(parse_splat)
+        .collect()
+}
+
+// jacobi, sor or sor=OMEGA
+fn parse_relaxation(name: &str) -> Result<Relaxation, String> {
+    let mut parts = name.splitn(2, '=');
+    match (parts.next().unwrap().to_lowercase().as_str(), parts.next()) {
+        ("jacobi", None) => Ok(Relaxation::Jacobi),
+        ("sor", None) => Ok(Relaxation::RedBlackSor { omega: 1.5 }),
+        ("sor", Some(omega)) => {
+            let omega = omega.parse().map_err(|_| format!("invalid over-relaxation factor: {}", omega))?;
+            Ok(Relaxation::RedBlackSor { omega })
+        }
+        _ => Err(format!("unknown relaxation: {}", name)),
+    }
+}
+
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Option<Options>, String> {
+    let mut options = Options {
+        width:          256,
+        height:         256,
+        dye_width:      None,
+        dye_height:     None,
+        steps:          120,
+        delta_t:        1.0/60.0,
+        vector_field:   texture::vector_field_value("Waves").unwrap(),
+        color_field:    texture::color_field_value("Rainbow").unwrap(),
+        params:         SimulationParams::default(),
+        splats:         Vec::new(),
+        heats:          Vec::new(),
+        emitters:       Vec::new(),
+        shapes:         Vec::new(),
+        obstacle_image: None,
+        every:          1,
+        diagnostics:    false,
+        output:         PathBuf::from("frames"),
+    };
+
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--width" => options.width = parse_value(&flag, args.next())?,
+            "--height" => options.height = parse_value(&flag, args.next())?,
+            "--dye-width" => options.dye_width = Some(parse_value(&flag, args.next())?),
+            "--dye-height" => options.dye_height = Some(parse_value(&flag, args.next())?),
+            "--steps" => options.steps = parse_value(&flag, args.next())?,
+            "--delta-t" => options.delta_t = parse_value(&flag, args.next())?,
+            "--vector-field" => {
+                let name: String = parse_value(&flag, args.next())?;
+                options.vector_field = texture::vector_field_value(&name)
+                    .ok_or_else(|| format!("unknown vector field: {}", name))?;
+            }
+            "--color-field" => {
+                let name: String = parse_value(&flag, args.next())?;
+                options.color_field = texture::color_field_value(&name)
+                    .ok_or_else(|| format!("unknown color field: {}", name))?;
+            }
+            "--jacobi" => options.params.jacobi_iterations = parse_value(&flag, args.next())?,
+            "--viscosity" => options.params.viscosity = parse_value(&flag, args.next())?,
+            "--vorticity" => options.params.vorticity = parse_value(&flag, args.next())?,
+            "--advection" => {
+                let name: String = parse_value(&flag, args.next())?;
+                options.params.advection = match name.to_lowercase().as_str() {
+                    "semi-lagrangian" => AdvectionScheme::SemiLagrangian,
+                    "maccormack" => AdvectionScheme::MacCormack,
+                    _ => return Err(format!("unknown advection scheme: {}", name)),
+                };
+            }
+            "--integrator" => {
+                let name: String = parse_value(&flag, args.next())?;
+                options.params.integrator = match name.to_lowercase().as_str() {
+                    "euler" => Integrator::Euler,
+                    "midpoint" => Integrator::Midpoint,
+                    "rk3" => Integrator::Rk3,
+                    _ => return Err(format!("unknown integrator: {}", name)),
+                };
+            }
+            "--grid" => {
+                let name: String = parse_value(&flag, args.next())?;
+                options.params.grid = match name.to_lowercase().as_str() {
+                    "collocated" => GridLayout::Collocated,
+                    "staggered" => GridLayout::Staggered,
+                    _ => return Err(format!("unknown grid layout: {}", name)),
+                };
+            }
+            "--pressure" => {
+                let name: String = parse_value(&flag, args.next())?;
+                options.params.pressure_solver = match name.to_lowercase().as_str() {
+                    "jacobi" => PressureSolver::Jacobi,
+                    "multigrid" => PressureSolver::Multigrid,
+                    "pcg" => PressureSolver::ConjugateGradient,
+                    _ => return Err(format!("unknown pressure solver: {}", name)),
+                };
+            }
+            "--multigrid-cycles" => options.params.multigrid_cycles = parse_value(&flag, args.next())?,
+            "--pressure-relaxation" => {
+                let name: String = parse_value(&flag, args.next())?;
+                options.params.pressure_relaxation = parse_relaxation(&name)?;
+            }
+            "--diffusion-relaxation" => {
+                let name: String = parse_value(&flag, args.next())?;
+                options.params.diffusion_relaxation = parse_relaxation(&name)?;
+            }
+            "--pcg-tolerance" => options.params.pcg_tolerance = parse_value(&flag, args.next())?,
+            "--pcg-iterations" => options.params.pcg_max_iterations = parse_value(&flag, args.next())?,
+            "--cfl" => options.params.cfl = parse_value(&flag, args.next())?,
+            "--max-substeps" => options.params.max_substeps = parse_value(&flag, args.next())?,
+            "--velocity-dissipation" => options.params.velocity_dissipation = parse_value(&flag, args.next())?,
+            "--dye-dissipation" => options.params.dye_dissipation = parse_value(&flag, args.next())?,
+            "--smoke-dissipation" => options.params.smoke_dissipation = parse_value(&flag, args.next())?,
+            "--splat" => {
+                let s: String = parse_value(&flag, args.next())?;
+                options.splats.push(parse_splat(&s)?);
+            }
+            "--splats" => {
+                let path: PathBuf = parse_value(&flag, args.next())?;
+                options.splats.extend(read_splats(&path)?);
+            }
+            "--force-brush" => options.params.force_brush = parse_brush(&parse_value::<String>(&flag, args.next())?)?,
+            "--dye-brush" => options.params.dye_brush = parse_brush(&parse_value::<String>(&flag, args.next())?)?,
+            "--emitter" => {
+                let s: String = parse_value(&flag, args.next())?;
+                options.emitters.push(parse_emitter(&s)?);
+            }
+            "--smoke" => options.params.smoke = true,
+            "--ambient" => options.params.ambient_temperature = parse_value(&flag, args.next())?,
+            "--lift" => options.params.buoyancy_lift = parse_value(&flag, args.next())?,
+            "--weight" => options.params.smoke_weight = parse_value(&flag, args.next())?,
+            "--heat" => {
+                let s: String = parse_value(&flag, args.next())?;
+                options.heats.push(parse_heat(&s)?);
+            }
+            "--circle" => {
+                let s: String = parse_value(&flag, args.next())?;
+                let v = parse_floats(&s, 3, "circle")?;
+                options.shapes.push(Shape::Circle { center: Vector2::new(v[0], v[1]), radius: v[2] });
+            }
+            "--rect" => {
+                let s: String = parse_value(&flag, args.next())?;
+                let v = parse_floats(&s, 4, "rectangle")?;
+                options.shapes.push(Shape::Rect { min: Vector2::new(v[0], v[1]), max: Vector2::new(v[2], v[3]) });
+            }
+            "--obstacles" => options.obstacle_image = Some(parse_value(&flag, args.next())?),
+            "--slip" => {
+                let name: String = parse_value(&flag, args.next())?;
+                options.params.obstacle_slip = match name.to_lowercase().as_str() {
+                    "no-slip" => Slip::NoSlip,
+                    "free-slip" => Slip::FreeSlip,
+                    _ => return Err(format!("unknown slip condition: {}", name)),
+                };
+            }
+            "--left" => options.params.boundaries.left = parse_edge(&parse_value::<String>(&flag, args.next())?)?,
+            "--right" => options.params.boundaries.right = parse_edge(&parse_value::<String>(&flag, args.next())?)?,
+            "--bottom" => options.params.boundaries.bottom = parse_edge(&parse_value::<String>(&flag, args.next())?)?,
+            "--top" => options.params.boundaries.top = parse_edge(&parse_value::<String>(&flag, args.next())?)?,
+            "--diagnostics" => options.diagnostics = true,
+            "--every" => options.every = parse_value(&flag, args.next())?,
+            "--output" => options.output = parse_value(&flag, args.next())?,
+            "--help" | "-h" => return Ok(None),
+            _ => return Err(format!("unknown option: {}", flag)),
+        }
+    }
+
+    if options.width <= 0 || options.height <= 0 {
+        return Err(String::from("grid size must be positive"));
+    }
+    if options.dye_width.is_some_and(|w| w <= 0) || options.dye_height.is_some_and(|h| h <= 0) {
+        return Err(String::from("dye grid size must be positive"));
+    }
+    if options.every == 0 {
+        return Err(String::from("--every must be at least 1"));
+    }
+    if options.params.cfl <= 0.0 {
+        return Err(String::from("--cfl must be positive"));
+    }
+
+    Ok(Some(options))
+}
+
+fn write_frame(path: &Path, width: i32, height: i32, data: &[f32]) -> Result<(), String> {
+    // fields are stored bottom row first, images top row first
+    let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+    for row in data.chunks((width * 4) as usize).rev() {
+        for texel in row.chunks(4) {
+            pixels.extend(texel[..3].iter().map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8));
+            pixels.push(255);
+        }
+    }
+
+    let file = File::create(path).map_err(|e| format!("could not create {}: {}", path.display(), e))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer.write_image_data(&pixels).map_err(|e| e.to_string())
+}
+
+// an RGBA copy of the PNG image at `path`, with its width and height
+fn read_image(path: &Path) -> Result<(usize, usize, Vec<u8>), String> {
+    let file = File::open(path).map_err(|e| format!("could not open {}: {}", path.display(), e))?;
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+
+    let mut reader = decoder.read_info().map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+    let buf = &buf[..info.buffer_size()];
+
+    let pixels = match info.color_type {
+        png::ColorType::Rgba => buf.to_vec(),
+        png::ColorType::Rgb => buf.chunks(3).flat_map(|p| [p[0], p[1], p[2], 255]).collect(),
+        png::ColorType::GrayscaleAlpha => buf.chunks(2).flat_map(|p| [p[0], p[0], p[0], p[1]]).collect(),
+        png::ColorType::Grayscale => buf.iter().flat_map(|&g| [g, g, g, 255]).collect(),
+        png::ColorType::Indexed => return Err(format!("could not expand the palette of {}", path.display())),
+    };
+
+    Ok((info.width as usize, info.height as usize, pixels))
+}
+
+fn run(options: Options) -> Result<(), String> {
+    let (width, height) = (options.width, options.height);
+    let dye_width = options.dye_width.unwrap_or(width);
+    let dye_height = options.dye_height.unwrap_or(height);
+
+    fs::create_dir_all(&options.output)
+        .map_err(|e| format!("could not create {}: {}", options.output.display(), e))?;
+
+    let mut sim = FluidSimulation::with_backend(CpuBackend::new(), width, height, options.params)?;
+    sim.emitters = options.emitters;
+    sim.resize_dye(dye_width, dye_height)?;
+    sim.set_velocity_field(texture::get_vector_field_with_value(options.vector_field, width, height))?;
+    sim.set_color_field(texture::get_color_field_with_value(options.color_field, dye_width, dye_height))?;
+
+    if !options.shapes.is_empty() || options.obstacle_image.is_some() {
+        let mut mask = obstacle::make_shapes_mask(width, height, &options.shapes);
+        if let Some(path) = &options.obstacle_image {
+            let (image_width, image_height, pixels) = read_image(path)?;
+            let image = obstacle::make_image_mask(width, height, image_width, image_height, &pixels);
+            for (m, i) in mask.chunks_mut(4).zip(image.chunks(4)) {
+                m[0] = m[0].max(i[0]);
+            }
+        }
+        sim.set_obstacles(mask)?;
+    }
+
+    for step in 0..options.steps {
+        for splat in options.splats.iter().filter(|s| s.step == step) {
+            sim.apply_force(splat.force, splat.pos);
+            sim.add_dye(splat.color, splat.pos);
+        }
+        for heat in options.heats.iter().filter(|h| h.step == step) {
+            sim.add_heat(heat.heat.x, heat.heat.y, heat.pos);
+        }
+
+        sim.step(options.delta_t);
+
+        if let Some(report) = sim.pcg_report() {
+            eprintln!("step {}: pcg {} iterations, residual {:e}{}", step, report.iterations, report.residual,
+                if report.converged { "" } else { " (not converged)" });
+        }
+
+        if options.diagnostics {
+            let d = sim.diagnostics()?;
+            eprintln!("step {}: {} substeps, divergence l2 {:e} linf {:e}, residual l2 {:e} linf {:e}", step,
+                sim.substeps(), d.divergence.l2, d.divergence.linf, d.residual.l2, d.residual.linf);
+        }
+
+        if step % options.every == 0 {
+            let path = options.output.join(format!("frame_{:05}.png", step / options.every));
+            write_frame(&path, dye_width, dye_height, &sim.read_color_field()?)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let result = parse_args(std::env::args().skip(1)).and_then(|options| match options {
+        Some(options) => run(options),
+        None => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+    });
+
+    if let Err(e) = result {
+        eprintln!("error: {}\n\n{}", e, USAGE);
+        process::exit(1);
+    }
+}
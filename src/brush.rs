@@ -0,0 +1,112 @@
+// The brush a splat is drawn with by the `force`, `color` and `heat` passes:
+// how far it reaches, how it fades towards its edge, and how its value is
+// combined with the field under it.
+
+use nalgebra::Vector2;
+
+/// How a brush fades from its center to its radius.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Falloff {
+    /// Full weight inside the radius and none outside.
+    Hard,
+    /// `exp(-d² / radius²)`, which never quite reaches zero.
+    Gaussian,
+    /// Eases from full weight at the center to none at the radius.
+    Smoothstep,
+}
+
+impl Falloff {
+    /// The value the shaders take for the falloff.
+    pub fn mode(&self) -> i32 {
+        match self {
+            Falloff::Hard => 0,
+            Falloff::Gaussian => 1,
+            Falloff::Smoothstep => 2,
+        }
+    }
+}
+
+/// How a brush combines its value with the field under it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Blend {
+    /// Moves the field to the value by `strength` times the weight at once,
+    /// whatever the time step.
+    Replace,
+    /// Adds `strength` times the value per second.
+    Additive,
+    /// Composites the value over the field with an opacity of `strength`
+    /// per second.
+    AlphaOver,
+}
+
+impl Blend {
+    /// The value the shaders take for the blend mode.
+    pub fn mode(&self) -> i32 {
+        match self {
+            Blend::Replace => 0,
+            Blend::Additive => 1,
+            Blend::AlphaOver => 2,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Brush {
+    /// In units of the domain width, like the splat positions' distances.
+    pub radius:     f32,
+    pub falloff:    Falloff,
+    pub strength:   f32,
+    pub blend:      Blend,
+}
+
+impl Brush {
+    /// The gaussian the force and heat splats have always been drawn with.
+    pub fn force() -> Brush {
+        Brush {
+            radius:     1e-3_f32.sqrt(),
+            falloff:    Falloff::Gaussian,
+            strength:   1.0,
+            blend:      Blend::Additive,
+        }
+    }
+
+    /// The hard disk of color the dye splats have always overwritten.
+    pub fn dye() -> Brush {
+        Brush {
+            radius:     0.025,
+            falloff:    Falloff::Hard,
+            strength:   1.0,
+            blend:      Blend::Replace,
+        }
+    }
+
+    /// Weight of the brush at `delta` domain widths from its center, 1 at the
+    /// center.
+    pub fn weight(&self, delta: Vector2<f32>) -> f32 {
+        let d = delta.norm();
+        match self.falloff {
+            Falloff::Hard => if d < self.radius { 1.0 } else { 0.0 },
+            Falloff::Gaussian => (-delta.norm_squared() / self.radius.powi(2)).exp(),
+            Falloff::Smoothstep => {
+                let t = (d / self.radius).min(1.0);
+                1.0 - t * t * (3.0 - 2.0 * t)
+            }
+        }
+    }
+
+    /// One channel of the field after a stroke of `delta_t` seconds, where
+    /// the brush has `weight`.
+    pub fn apply(&self, delta_t: f32, weight: f32, dst: f32, value: f32) -> f32 {
+        match self.blend {
+            Blend::Replace => {
+                let alpha = (self.strength * weight).clamp(0.0, 1.0);
+                dst + (value - dst) * alpha
+            }
+            Blend::Additive => dst + delta_t * self.strength * weight * value,
+            Blend::AlphaOver => {
+                let alpha = (delta_t * self.strength * weight).clamp(0.0, 1.0);
+                dst + (value - dst) * alpha
+            }
+        }
+    }
+}
@@ -0,0 +1,955 @@
+#![allow(clippy::too_many_arguments)]
+
+// CPU versions of the passes in `render_fluid`. Every function here follows the
+// math of its fragment shader line for line so the results can be compared with
+// the GPU solver on machines without WebGL.
+
+use nalgebra::{Vector2, Vector3, Vector4};
+
+use crate::backend::{vertical_weight, Boundaries, BoundaryField, Edge, GridLayout, Integrator, Slip};
+use crate::brush::Brush;
+use crate::diagnostics::Norms;
+use crate::obstacle::RigidBody;
+
+/// Fraction of `delta_t * velocity` an advection backtrace covers, kept from
+/// the original passes: the fluid moves at half the velocity the field holds.
+pub const BACKTRACE_SCALE: f32 = 0.5;
+
+/// An RGBA float grid laid out like the data given to
+/// `texture::Framebuffer::create_with_data`: row major, bottom row first.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Field {
+    width:  i32,
+    height: i32,
+    data:   Vec<f32>,
+    // whether samples wrap around along x and y instead of clamping
+    periodic: (bool, bool),
+}
+
+impl Field {
+    pub fn new(width: i32, height: i32) -> Field {
+        Field {
+            width,
+            height,
+            data: vec![0.0; (width * height * 4) as usize],
+            periodic: (false, false),
+        }
+    }
+
+    pub fn create_with_data(width: i32, height: i32, data: Vec<f32>) -> Result<Field, String> {
+        if data.len() != (width * height * 4) as usize {
+            return Err(String::from("invalid texture data"));
+        }
+
+        Ok(Field { width, height, data, periodic: (false, false) })
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    pub fn data(&self) -> &[f32] {
+        &self.data
+    }
+
+    pub fn into_data(self) -> Vec<f32> {
+        self.data
+    }
+
+    /// Makes `sample` wrap around past the left and right, or bottom and
+    /// top, edges like a texture with REPEAT wrapping.
+    pub fn set_periodic(&mut self, periodic_x: bool, periodic_y: bool) {
+        self.periodic = (periodic_x, periodic_y);
+    }
+
+    pub fn periodic(&self) -> (bool, bool) {
+        self.periodic
+    }
+
+    /// Same as `render::clear_framebuffer`: opaque black.
+    pub fn clear(&mut self) {
+        for texel in self.data.chunks_mut(4) {
+            texel.copy_from_slice(&[0.0, 0.0, 0.0, 1.0]);
+        }
+    }
+
+    pub fn texel(&self, x: i32, y: i32) -> Vector4<f32> {
+        let i = ((y * self.width + x) * 4) as usize;
+        Vector4::new(self.data[i], self.data[i + 1], self.data[i + 2], self.data[i + 3])
+    }
+
+    pub fn set_texel(&mut self, x: i32, y: i32, value: Vector4<f32>) {
+        let i = ((y * self.width + x) * 4) as usize;
+        self.data[i..i + 4].copy_from_slice(value.as_slice());
+    }
+
+    /// Equivalent of `texture2D` on a texture created with LINEAR filtering
+    /// and CLAMP_TO_EDGE wrapping, or REPEAT along the periodic axes.
+    pub fn sample(&self, uv: Vector2<f32>) -> Vector4<f32> {
+        let x = uv.x * self.width as f32 - 0.5;
+        let y = uv.y * self.height as f32 - 0.5;
+
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let fx = x - x0;
+        let fy = y - y0;
+
+        let wrap = |i: f32, n: i32, periodic: bool| if periodic { (i as i32).rem_euclid(n) } else { (i as i32).max(0).min(n - 1) };
+        let clamp_x = |i: f32| wrap(i, self.width, self.periodic.0);
+        let clamp_y = |j: f32| wrap(j, self.height, self.periodic.1);
+
+        let (i0, i1) = (clamp_x(x0), clamp_x(x0 + 1.0));
+        let (j0, j1) = (clamp_y(y0), clamp_y(y0 + 1.0));
+
+        let bottom = self.texel(i0, j0) * (1.0 - fx) + self.texel(i1, j0) * fx;
+        let top    = self.texel(i0, j1) * (1.0 - fx) + self.texel(i1, j1) * fx;
+
+        bottom * (1.0 - fy) + top * fy
+    }
+
+    /// Runs `shader` once per texel with the texel's center uv, the same way a
+    /// full screen quad pass fills a framebuffer.
+    pub fn shade<F: FnMut(Vector2<f32>) -> Vector4<f32>>(&mut self, mut shader: F) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let uv = Vector2::new(
+                    (x as f32 + 0.5) / self.width as f32,
+                    (y as f32 + 0.5) / self.height as f32,
+                );
+                self.set_texel(x, y, shader(uv));
+            }
+        }
+    }
+}
+
+/// Whether the cell at `uv` is solid in the `obstacles` mask.
+pub fn solid(obstacles: &Field, uv: Vector2<f32>) -> bool {
+    obstacles.sample(uv).x > 0.5
+}
+
+/// Velocity of the fluid moving with the solid at `uv`, zero where the mask
+/// is at rest. The mask holds the surface's own velocity, which advection
+/// only moves the fluid by once divided by `BACKTRACE_SCALE`.
+pub fn solid_velocity(obstacles: &Field, uv: Vector2<f32>) -> Vector2<f32> {
+    let texel = obstacles.sample(uv);
+    Vector2::new(texel.y, texel.z) / BACKTRACE_SCALE
+}
+
+// `uv - point` the short way around the axes `field` wraps along, the same as
+// wrap.glsl's `wrapDelta`
+fn wrap_delta(field: &Field, uv: Vector2<f32>, point: &Vector2<f32>) -> Vector2<f32> {
+    let mut delta = uv - point;
+    let (periodic_x, periodic_y) = field.periodic();
+    if periodic_x {
+        delta.x -= (delta.x + 0.5).floor();
+    }
+    if periodic_y {
+        delta.y -= (delta.y + 0.5).floor();
+    }
+    delta
+}
+
+// the staggered texel `face` at `uv` with the faces of solid cells moving
+// with the solid
+fn solid_faces(delta_x: f32, delta_y: f32, obstacles: &Field, uv: Vector2<f32>, mut face: Vector4<f32>) -> Vector4<f32> {
+    let (left, below) = (uv - Vector2::new(delta_x, 0.0), uv - Vector2::new(0.0, delta_y));
+
+    if solid(obstacles, uv) {
+        let v = solid_velocity(obstacles, uv);
+        face.x = v.x;
+        face.y = v.y;
+    } else {
+        if solid(obstacles, left) {
+            face.x = solid_velocity(obstacles, left).x;
+        }
+        if solid(obstacles, below) {
+            face.y = solid_velocity(obstacles, below).y;
+        }
+    }
+    face
+}
+
+// offsets from a point to where its u and v components are stored on a
+// staggered grid
+fn face_offsets(delta_x: f32, delta_y: f32) -> (Vector2<f32>, Vector2<f32>) {
+    (Vector2::new(0.5 * delta_x, 0.0), Vector2::new(0.0, 0.5 * delta_y))
+}
+
+fn velocity(delta_x: f32, delta_y: f32, layout: GridLayout, vector_field: &Field, coord: Vector2<f32>) -> Vector2<f32> {
+    match layout {
+        GridLayout::Collocated => vector_field.sample(coord).xy(),
+        GridLayout::Staggered => {
+            let (u_offset, v_offset) = face_offsets(delta_x, delta_y);
+            Vector2::new(vector_field.sample(coord + u_offset).x, vector_field.sample(coord + v_offset).y)
+        }
+    }
+}
+
+// velocity in uv per unit time, the domain being 1 wide and 1 / aspect tall
+fn uv_velocity(delta_x: f32, delta_y: f32, aspect: f32, layout: GridLayout, vector_field: &Field, coord: Vector2<f32>) -> Vector2<f32> {
+    velocity(delta_x, delta_y, layout, vector_field, coord).component_mul(&Vector2::new(1.0, aspect))
+}
+
+fn backtrace(delta_x: f32, delta_y: f32, aspect: f32, delta_t: f32, integrator: Integrator, layout: GridLayout, vector_field: &Field, coord: Vector2<f32>) -> Vector2<f32> {
+    let velocity = |coord| uv_velocity(delta_x, delta_y, aspect, layout, vector_field, coord);
+
+    let h = BACKTRACE_SCALE * delta_t;
+    let k1 = velocity(coord);
+    if integrator == Integrator::Euler {
+        return coord - h * k1;
+    }
+
+    let k2 = velocity(coord - 0.5 * h * k1);
+    if integrator == Integrator::Midpoint {
+        return coord - h * k2;
+    }
+
+    let k3 = velocity(coord - 0.75 * h * k2);
+    coord - h * ((2.0 * k1 + 3.0 * k2 + 4.0 * k3) / 9.0)
+}
+
+pub fn advection(
+    delta_x:            f32,
+    delta_y:            f32,
+    aspect:             f32,
+    delta_t:            f32,
+    dissipation:        f32,
+    integrator:         Integrator,
+    layout:             GridLayout,
+    src_layout:         GridLayout,
+    obstacles:          &Field,
+    src_color_field:    &Field,
+    vector_field:       &Field,
+    dst_color_field:    &mut Field,
+) {
+    let backtrace = |coord| backtrace(delta_x, delta_y, aspect, delta_t, integrator, layout, vector_field, coord);
+    let decay = (-dissipation * delta_t).exp();
+
+    dst_color_field.shade(|uv| {
+        // nothing is carried into a solid cell
+        if solid(obstacles, uv) {
+            return Vector4::new(0.0, 0.0, 0.0, 1.0);
+        }
+
+        if src_layout == GridLayout::Collocated {
+            let past_coord = backtrace(uv);
+            let color = src_color_field.sample(past_coord);
+
+            return Vector4::new(decay * color.x, decay * color.y, decay * color.z, color.w);
+        }
+
+        // trace each component back from its own face
+        let (u_offset, v_offset) = face_offsets(delta_x, delta_y);
+        let u = src_color_field.sample(backtrace(uv - u_offset) + u_offset).x;
+        let v = src_color_field.sample(backtrace(uv - v_offset) + v_offset).y;
+
+        Vector4::new(decay * u, decay * v, 0.0, 1.0)
+    });
+}
+
+// range of the texels bilinear sampling at `coord` interpolates between
+fn texel_range(delta_x: f32, delta_y: f32, field: &Field, coord: Vector2<f32>) -> (Vector4<f32>, Vector4<f32>) {
+    let delta = Vector2::new(delta_x, delta_y);
+    let texel = coord.component_div(&delta).add_scalar(-0.5).map(f32::floor).add_scalar(0.5).component_mul(&delta);
+    let a = field.sample(texel);
+    let b = field.sample(texel + Vector2::new(delta_x, 0.0));
+    let c = field.sample(texel + Vector2::new(0.0, delta_y));
+    let d = field.sample(texel + delta);
+
+    (a.inf(&b).inf(&c.inf(&d)), a.sup(&b).sup(&c.sup(&d)))
+}
+
+pub fn maccormack(
+    delta_x:            f32,
+    delta_y:            f32,
+    aspect:             f32,
+    delta_t:            f32,
+    dissipation:        f32,
+    integrator:         Integrator,
+    layout:             GridLayout,
+    src_layout:         GridLayout,
+    obstacles:          &Field,
+    src_color_field:    &Field,
+    vector_field:       &Field,
+    forward_field:      &Field,
+    backward_field:     &Field,
+    dst_color_field:    &mut Field,
+) {
+    let backtrace = |coord| backtrace(delta_x, delta_y, aspect, delta_t, integrator, layout, vector_field, coord);
+    // the advected field's own texels, which need not match the vector field's
+    let src_delta_x = 1.0 / src_color_field.width() as f32;
+    let src_delta_y = 1.0 / src_color_field.height() as f32;
+    let texel_range = |coord| texel_range(src_delta_x, src_delta_y, src_color_field, coord);
+    let decay = (-dissipation * delta_t).exp();
+
+    dst_color_field.shade(|uv| {
+        if solid(obstacles, uv) {
+            return Vector4::new(0.0, 0.0, 0.0, 1.0);
+        }
+
+        let forward = forward_field.sample(uv);
+        let backward = backward_field.sample(uv);
+        let corrected = forward + 0.5 * (src_color_field.sample(uv) - backward);
+
+        let (lo, hi) = match src_layout {
+            GridLayout::Collocated => texel_range(backtrace(uv)),
+            GridLayout::Staggered => {
+                let (u_offset, v_offset) = face_offsets(delta_x, delta_y);
+                let (u_lo, u_hi) = texel_range(backtrace(uv - u_offset) + u_offset);
+                let (v_lo, v_hi) = texel_range(backtrace(uv - v_offset) + v_offset);
+
+                (Vector4::new(u_lo.x, v_lo.y, u_lo.z, u_lo.w), Vector4::new(u_hi.x, v_hi.y, u_hi.z, u_hi.w))
+            }
+        };
+
+        let color = corrected.zip_zip_map(&lo, &hi, |x, lo, hi| x.max(lo).min(hi));
+        Vector4::new(decay * color.x, decay * color.y, decay * color.z, color.w)
+    });
+}
+
+/// Runs `iter` jacobi iterations ping-ponging between `x` and `dst`. As on the
+/// GPU, the result the caller should keep reading from is left in `x`.
+pub fn jacobi_method(
+    iter:       usize,
+    delta_x:    f32,
+    delta_y:    f32,
+    aspect:     f32,
+    alpha:      f32,
+    r_beta:     f32,
+    obstacles:  &Field,
+    x:          &mut Field,
+    b:          &Field,
+    dst:        &mut Field,
+) {
+    for k in 0..iter {
+        if k % 2 == 0 {
+            jacobi_iteration(delta_x, delta_y, aspect, alpha, r_beta, obstacles, x, b, dst);
+        } else {
+            jacobi_iteration(delta_x, delta_y, aspect, alpha, r_beta, obstacles, dst, b, x);
+        }
+    }
+}
+
+pub fn jacobi_iteration(
+    delta_x:    f32,
+    delta_y:    f32,
+    aspect:     f32,
+    alpha:      f32,
+    r_beta:     f32,
+    obstacles:  &Field,
+    x:          &Field,
+    b:          &Field,
+    dst:        &mut Field,
+) {
+    weighted_jacobi_iteration(delta_x, delta_y, aspect, alpha, r_beta, 1.0, obstacles, x, b, dst);
+}
+
+pub fn weighted_jacobi_iteration(
+    delta_x:    f32,
+    delta_y:    f32,
+    aspect:     f32,
+    alpha:      f32,
+    r_beta:     f32,
+    omega:      f32,
+    obstacles:  &Field,
+    x:          &Field,
+    b:          &Field,
+    dst:        &mut Field,
+) {
+    relax(delta_x, delta_y, aspect, alpha, r_beta, omega, -1, obstacles, x, b, dst);
+}
+
+/// One red-black SOR sweep, the result is left in `x`.
+pub fn red_black_iteration(
+    delta_x:    f32,
+    delta_y:    f32,
+    aspect:     f32,
+    alpha:      f32,
+    r_beta:     f32,
+    omega:      f32,
+    obstacles:  &Field,
+    x:          &mut Field,
+    b:          &Field,
+    scratch:    &mut Field,
+) {
+    relax(delta_x, delta_y, aspect, alpha, r_beta, omega, 0, obstacles, x, b, scratch);
+    relax(delta_x, delta_y, aspect, alpha, r_beta, omega, 1, obstacles, scratch, b, x);
+}
+
+fn relax(
+    delta_x:    f32,
+    delta_y:    f32,
+    aspect:     f32,
+    alpha:      f32,
+    r_beta:     f32,
+    omega:      f32,
+    parity:     i32,
+    obstacles:  &Field,
+    x:          &Field,
+    b:          &Field,
+    dst:        &mut Field,
+) {
+    let wy = vertical_weight(delta_x, delta_y, aspect);
+    let (width, height) = (x.width() as f32, x.height() as f32);
+    dst.shade(|uv| {
+        let x_center = x.sample(uv).xy();
+        let texel = ((uv.x * width) as i32, (uv.y * height) as i32);
+        let skip = parity >= 0 && (texel.0 + texel.1) % 2 != parity;
+        if skip || solid(obstacles, uv) {
+            return Vector4::new(x_center.x, x_center.y, 0.0, 1.0);
+        }
+
+        // solid neighbours mirror the center, so no gradient crosses their faces
+        let neighbour = |offset| if solid(obstacles, uv + offset) { x_center } else { x.sample(uv + offset).xy() };
+        let x_left  = neighbour(Vector2::new(-delta_x, 0.0));
+        let x_right = neighbour(Vector2::new(delta_x, 0.0));
+        let x_down  = neighbour(Vector2::new(0.0, -delta_y));
+        let x_up    = neighbour(Vector2::new(0.0, delta_y));
+
+        let b_center = b.sample(uv).xy();
+
+        let x_new = r_beta * (x_left + x_right + wy * (x_up + x_down) + alpha * b_center);
+        let r = x_center.lerp(&x_new, omega);
+        Vector4::new(r.x, r.y, 0.0, 1.0)
+    });
+}
+
+pub fn residual(
+    delta_x:    f32,
+    delta_y:    f32,
+    aspect:     f32,
+    obstacles:  &Field,
+    x:          &Field,
+    b:          &Field,
+    dst:        &mut Field,
+) {
+    let hy = delta_y / aspect;
+    dst.shade(|uv| {
+        if solid(obstacles, uv) {
+            return Vector4::new(0.0, 0.0, 0.0, 1.0);
+        }
+
+        // the same neumann condition as `relax`
+        let x_center = x.sample(uv).xy();
+        let neighbour = |offset| if solid(obstacles, uv + offset) { x_center } else { x.sample(uv + offset).xy() };
+        let x_left   = neighbour(Vector2::new(-delta_x, 0.0));
+        let x_right  = neighbour(Vector2::new(delta_x, 0.0));
+        let x_down   = neighbour(Vector2::new(0.0, -delta_y));
+        let x_up     = neighbour(Vector2::new(0.0, delta_y));
+
+        let b_center = b.sample(uv).xy();
+
+        let laplacian = (x_left + x_right - 2.0 * x_center) / (delta_x * delta_x)
+                      + (x_up + x_down - 2.0 * x_center) / (hy * hy);
+        let r = b_center - laplacian;
+        Vector4::new(r.x, r.y, 0.0, 1.0)
+    });
+}
+
+/// L2 and max norms of the red channel, what the reduction passes compute
+/// on the GPU.
+pub fn norms(x: &Field) -> Norms {
+    let (sum, max) = x.data().chunks(4)
+        .fold((0.0, 0.0_f32), |(sum, max), t| (sum + t[0] * t[0], max.max(t[0].abs())));
+    Norms::from_reduction(sum, max, (x.width() * x.height()) as usize)
+}
+
+/// Largest length of the xy vector, the speed reduction on the GPU.
+pub fn max_speed(x: &Field) -> f32 {
+    x.data().chunks(4).fold(0.0_f32, |max, t| max.max(t[0].hypot(t[1])))
+}
+
+/// Bilinear resampling of `src` onto the grid of `dst`, what drawing `src`
+/// with the quad pass into a framebuffer of another size does.
+pub fn resample(src: &Field, dst: &mut Field) {
+    dst.shade(|uv| src.sample(uv));
+}
+
+// fine texels `[lo, hi)` overlaps, in units of fine texels, as the first one
+// and how much of each of the three from it
+fn overlaps(lo: f32, hi: f32) -> (i32, [f32; 3]) {
+    let first = lo.floor();
+    let mut weights = [0.0; 3];
+    for (k, weight) in weights.iter_mut().enumerate() {
+        let start = first + k as f32;
+        *weight = (hi.min(start + 1.0) - lo.max(start)).max(0.0);
+    }
+    (first as i32, weights)
+}
+
+/// Same as restrict.frag: full weighting, each coarse texel the average of the
+/// fine texels its cell covers, weighted by how much of each it covers. A
+/// coarse cell is at most two fine ones wide, so it overlaps at most three
+/// along each axis.
+pub fn restrict(src: &Field, dst: &mut Field) {
+    let scale_x = src.width() as f32 / dst.width() as f32;
+    let scale_y = src.height() as f32 / dst.height() as f32;
+    for y in 0..dst.height() {
+        for x in 0..dst.width() {
+            let (i0, weights_x) = overlaps(x as f32 * scale_x, (x + 1) as f32 * scale_x);
+            let (j0, weights_y) = overlaps(y as f32 * scale_y, (y + 1) as f32 * scale_y);
+
+            let mut sum = Vector2::zeros();
+            for (j, weight_y) in weights_y.iter().enumerate() {
+                for (i, weight_x) in weights_x.iter().enumerate() {
+                    if weight_x * weight_y > 0.0 {
+                        sum += weight_x * weight_y * src.texel(i0 + i as i32, j0 + j as i32).xy();
+                    }
+                }
+            }
+            let r = sum / (scale_x * scale_y);
+            dst.set_texel(x, y, Vector4::new(r.x, r.y, 0.0, 1.0));
+        }
+    }
+}
+
+pub fn prolongate(
+    x:      &Field,
+    e:      &Field,
+    dst:    &mut Field,
+) {
+    dst.shade(|uv| {
+        let r = x.sample(uv).xy() + e.sample(uv).xy();
+        Vector4::new(r.x, r.y, 0.0, 1.0)
+    });
+}
+
+// flow through a face on an edge of the domain that is not periodic, given
+// the face across the cell from it
+fn edge_flow(edge: Edge, across: f32, component: usize) -> f32 {
+    match edge {
+        Edge::Inflow(velocity) => velocity[component],
+        Edge::Outflow => across,
+        // no flow through a wall
+        _ => 0.0,
+    }
+}
+
+pub fn divergence(
+    delta_x:    f32,
+    delta_y:    f32,
+    aspect:     f32,
+    layout:     GridLayout,
+    boundaries: &Boundaries,
+    obstacles:  &Field,
+    w:          &Field,
+    dst:        &mut Field,
+) {
+    let hy = delta_y / aspect;
+    dst.shade(|uv| {
+        if solid(obstacles, uv) {
+            return Vector4::new(0.0, 0.0, 0.0, 1.0);
+        }
+
+        let (left, right) = (Vector2::new(-delta_x, 0.0), Vector2::new(delta_x, 0.0));
+        let (down, up) = (Vector2::new(0.0, -delta_y), Vector2::new(0.0, delta_y));
+
+        if layout == GridLayout::Staggered {
+            // faces against a solid cell move with the solid, and faces on
+            // the edges of the domain follow the boundaries
+            let face = |offset, stored: Vector2<f32>| {
+                if solid(obstacles, uv + offset) {
+                    solid_velocity(obstacles, uv + offset)
+                } else {
+                    w.sample(stored).xy()
+                }
+            };
+            let mut u_left  = face(left, uv).x;
+            let mut u_right = face(right, uv + right).x;
+            let mut v_down  = face(down, uv).y;
+            let mut v_up    = face(up, uv + up).y;
+
+            let (periodic_x, periodic_y) = (boundaries.periodic_x(), boundaries.periodic_y());
+            let (u0, v0) = (u_left, v_down);
+            if uv.x < delta_x && !periodic_x {
+                u_left = edge_flow(boundaries.left, u_right, 0);
+            }
+            if uv.x + delta_x > 1.0 && !periodic_x {
+                u_right = edge_flow(boundaries.right, u0, 0);
+            }
+            if uv.y < delta_y && !periodic_y {
+                v_down = edge_flow(boundaries.bottom, v_up, 1);
+            }
+            if uv.y + delta_y > 1.0 && !periodic_y {
+                v_up = edge_flow(boundaries.top, v0, 1);
+            }
+
+            return Vector4::new((u_right - u_left) / delta_x + (v_up - v_down) / hy, 0.0, 0.0, 1.0);
+        }
+
+        // solid neighbours move with the solid
+        let neighbour = |offset| if solid(obstacles, uv + offset) { solid_velocity(obstacles, uv + offset) } else { w.sample(uv + offset).xy() };
+        let w_left  = neighbour(left);
+        let w_right = neighbour(right);
+        let w_down  = neighbour(down);
+        let w_up    = neighbour(up);
+
+        let half_rdx = 1.0 / (2.0 * delta_x);
+        let half_rdy = 1.0 / (2.0 * hy);
+        Vector4::new(half_rdx * (w_right.x - w_left.x) + half_rdy * (w_up.y - w_down.y), 0.0, 0.0, 1.0)
+    });
+}
+
+pub fn subtract(
+    delta_x:    f32,
+    delta_y:    f32,
+    aspect:     f32,
+    layout:     GridLayout,
+    obstacles:  &Field,
+    p:          &Field,
+    w:          &Field,
+    dst:        &mut Field,
+) {
+    let hy = delta_y / aspect;
+    dst.shade(|uv| {
+        let solid_center = solid(obstacles, uv);
+
+        if layout == GridLayout::Staggered {
+            // the pressure difference across each face
+            let p_center = p.sample(uv).x;
+            let p_left   = p.sample(uv - Vector2::new(delta_x, 0.0)).x;
+            let p_below  = p.sample(uv - Vector2::new(0.0, delta_y)).x;
+
+            let mut face = w.sample(uv);
+            face.x -= (p_center - p_left) / delta_x;
+            face.y -= (p_center - p_below) / hy;
+
+            // the faces of solid cells move with the solid
+            return solid_faces(delta_x, delta_y, obstacles, uv, face);
+        }
+
+        let mut color = w.sample(uv);
+        if solid_center {
+            let v = solid_velocity(obstacles, uv);
+            return Vector4::new(v.x, v.y, color.z, color.w);
+        }
+
+        // the pressure solve left no gradient across the faces of solid cells
+        let p_center = p.sample(uv).x;
+        let neighbour = |offset| if solid(obstacles, uv + offset) { p_center } else { p.sample(uv + offset).x };
+        let p_left  = neighbour(Vector2::new(-delta_x, 0.0));
+        let p_right = neighbour(Vector2::new(delta_x, 0.0));
+        let p_down  = neighbour(Vector2::new(0.0, -delta_y));
+        let p_up    = neighbour(Vector2::new(0.0, delta_y));
+
+        // the velocities of the solid neighbours on each axis, summed
+        let (mut walls, mut solids) = (Vector2::<f32>::zeros(), Vector2::<f32>::zeros());
+        for offset in [Vector2::new(-delta_x, 0.0), Vector2::new(delta_x, 0.0)].iter() {
+            if solid(obstacles, uv + offset) {
+                walls.x += solid_velocity(obstacles, uv + offset).x;
+                solids.x += 1.0;
+            }
+        }
+        for offset in [Vector2::new(0.0, -delta_y), Vector2::new(0.0, delta_y)].iter() {
+            if solid(obstacles, uv + offset) {
+                walls.y += solid_velocity(obstacles, uv + offset).y;
+                solids.y += 1.0;
+            }
+        }
+
+        let half_rdx = 1.0 / (2.0 * delta_x);
+        let half_rdy = 1.0 / (2.0 * hy);
+        color.x -= half_rdx * (p_right - p_left);
+        color.y -= half_rdy * (p_up - p_down);
+
+        // the central differences cannot see a flow into a neighbouring
+        // solid, so match the solid's outright
+        if solids.x > 0.0 {
+            color.x = walls.x / solids.x;
+        }
+        if solids.y > 0.0 {
+            color.y = walls.y / solids.y;
+        }
+
+        color
+    });
+}
+
+// a staggered face on the left or bottom edge, given the face next to it
+// inside
+fn edge_face(edge: Edge, face: f32, inside: f32, component: usize) -> f32 {
+    match edge {
+        Edge::Periodic => face,
+        Edge::Inflow(velocity) => velocity[component],
+        Edge::Outflow => inside,
+        // no flow through a wall
+        Edge::NoSlip | Edge::FreeSlip => 0.0,
+    }
+}
+
+pub fn boundary(
+    delta_x:    f32,
+    delta_y:    f32,
+    boundaries: &Boundaries,
+    field:      BoundaryField,
+    layout:     GridLayout,
+    x:          &Field,
+    dst:        &mut Field,
+) {
+    let (periodic_x, periodic_y) = (boundaries.periodic_x(), boundaries.periodic_y());
+
+    dst.shade(|uv| {
+        if layout == GridLayout::Staggered {
+            // the right and top edge faces are not stored
+            let mut face = x.sample(uv);
+            if uv.x < delta_x && !periodic_x {
+                face.x = edge_face(boundaries.left, face.x, x.sample(uv + Vector2::new(delta_x, 0.0)).x, 0);
+            }
+            if uv.y < delta_y && !periodic_y {
+                face.y = edge_face(boundaries.bottom, face.y, x.sample(uv + Vector2::new(0.0, delta_y)).y, 1);
+            }
+            return face;
+        }
+
+        // the edge this texel is on, the offset to its neighbour inside, and
+        // the component of the velocity through the edge
+        let (edge, offset, normal) = if uv.x - 0.0 < delta_x && !periodic_x {
+            (boundaries.left, Vector2::new(delta_x, 0.0), Vector2::new(-1.0, 1.0))
+        } else if 1.0 - uv.x < delta_x && !periodic_x {
+            (boundaries.right, Vector2::new(-delta_x, 0.0), Vector2::new(-1.0, 1.0))
+        } else if uv.y - 0.0 < delta_y && !periodic_y {
+            (boundaries.bottom, Vector2::new(0.0, delta_y), Vector2::new(1.0, -1.0))
+        } else if 1.0 - uv.y < delta_y && !periodic_y {
+            (boundaries.top, Vector2::new(0.0, -delta_y), Vector2::new(1.0, -1.0))
+        } else {
+            return x.sample(uv);
+        };
+
+        let inside = x.sample(uv + offset).xy();
+        let col = match (field, edge) {
+            // no pressure gradient through the edge, except where the fluid
+            // is free to leave
+            (BoundaryField::Pressure, Edge::Outflow) => Vector2::zeros(),
+            (BoundaryField::Pressure, _) => inside,
+            (_, Edge::NoSlip) => -inside,
+            (_, Edge::FreeSlip) => inside.component_mul(&normal),
+            (_, Edge::Inflow(velocity)) => velocity,
+            (_, _) => inside,
+        };
+        Vector4::new(col.x, col.y, 0.0, 1.0)
+    });
+}
+
+pub fn obstacle_boundary(
+    delta_x:    f32,
+    delta_y:    f32,
+    slip:       Slip,
+    layout:     GridLayout,
+    obstacles:  &Field,
+    x:          &Field,
+    dst:        &mut Field,
+) {
+    dst.shade(|uv| {
+        let center = x.sample(uv);
+
+        if layout == GridLayout::Staggered {
+            // the faces of solid cells move with the solid
+            return solid_faces(delta_x, delta_y, obstacles, uv, center);
+        }
+
+        if !solid(obstacles, uv) {
+            return center;
+        }
+
+        // the mirror image of the fluid neighbours about the surface's
+        // velocity, flipping the component through the shared face for
+        // free-slip and both for no-slip
+        let surface = solid_velocity(obstacles, uv);
+        let neighbours = [
+            (Vector2::new(-delta_x, 0.0), Vector2::new(-1.0, 1.0)),
+            (Vector2::new(delta_x, 0.0), Vector2::new(-1.0, 1.0)),
+            (Vector2::new(0.0, -delta_y), Vector2::new(1.0, -1.0)),
+            (Vector2::new(0.0, delta_y), Vector2::new(1.0, -1.0)),
+        ];
+
+        let (mut mirrored, mut fluid) = (Vector2::zeros(), 0.0);
+        for (offset, normal) in neighbours.iter() {
+            if solid(obstacles, uv + offset) {
+                continue;
+            }
+
+            let v = x.sample(uv + offset).xy() - surface;
+            mirrored += surface + match slip {
+                Slip::NoSlip => -v,
+                Slip::FreeSlip => v.component_mul(normal),
+            };
+            fluid += 1.0;
+        }
+
+        let v = if fluid > 0.0 { mirrored / fluid } else { surface };
+        Vector4::new(v.x, v.y, 0.0, 1.0)
+    });
+}
+
+/// Copies `obstacles` into `dst` with the footprint of `body` solid and moving
+/// with it, like `body.frag`.
+pub fn body(
+    aspect:     f32,
+    body:       &RigidBody,
+    obstacles:  &Field,
+    dst:        &mut Field,
+) {
+    dst.shade(|uv| {
+        if !body.contains(aspect, uv) {
+            return obstacles.sample(uv);
+        }
+
+        let surface = body.surface_velocity(aspect, uv);
+        Vector4::new(1.0, surface.x, surface.y, 1.0)
+    });
+}
+
+pub fn force(
+    delta_t:                f32,
+    brush:                  &Brush,
+    aspect:                 f32,
+    force:                  &Vector2<f32>,
+    impulse_pos:            &Vector2<f32>,
+    velocity_field_texture: &Field,
+    dst:                    &mut Field,
+) {
+    dst.shade(|uv| {
+        let mut color = velocity_field_texture.sample(uv);
+
+        // distance in units of the domain width, which is 1 / aspect tall, the
+        // short way around the periodic edges
+        let delta = wrap_delta(velocity_field_texture, uv, impulse_pos).component_mul(&Vector2::new(1.0, 1.0 / aspect));
+        let weight = brush.weight(delta);
+
+        color.x = brush.apply(delta_t, weight, color.x, force.x);
+        color.y = brush.apply(delta_t, weight, color.y, force.y);
+
+        color
+    });
+}
+
+pub fn color(
+    delta_t:                f32,
+    brush:                  &Brush,
+    aspect:                 f32,
+    color:                  &Vector3<f32>,
+    impulse_pos:            &Vector2<f32>,
+    color_field_texture:    &Field,
+    dst:                    &mut Field,
+) {
+    dst.shade(|uv| {
+        let mut orig_color = color_field_texture.sample(uv);
+
+        let delta = wrap_delta(color_field_texture, uv, impulse_pos).component_mul(&Vector2::new(1.0, 1.0 / aspect));
+        let weight = brush.weight(delta);
+
+        orig_color.x = brush.apply(delta_t, weight, orig_color.x, color.x);
+        orig_color.y = brush.apply(delta_t, weight, orig_color.y, color.y);
+        orig_color.z = brush.apply(delta_t, weight, orig_color.z, color.z);
+
+        orig_color
+    });
+}
+
+pub fn buoyancy(
+    delta_t:                f32,
+    delta_y:                f32,
+    ambient:                f32,
+    lift:                   f32,
+    weight:                 f32,
+    layout:                 GridLayout,
+    smoke_field_texture:    &Field,
+    velocity_field_texture: &Field,
+    dst:                    &mut Field,
+) {
+    dst.shade(|uv| {
+        let mut color = velocity_field_texture.sample(uv);
+
+        // on a staggered grid v is stored on the bottom face of the cell
+        let coord = match layout {
+            GridLayout::Collocated => uv,
+            GridLayout::Staggered => uv - Vector2::new(0.0, 0.5 * delta_y),
+        };
+        let smoke = smoke_field_texture.sample(coord);
+
+        // hot air rises, smoke weighs it down
+        color.y += delta_t * (lift * (smoke.x - ambient) - weight * smoke.y);
+
+        color
+    });
+}
+
+pub fn heat(
+    delta_t:                f32,
+    brush:                  &Brush,
+    aspect:                 f32,
+    heat:                   &Vector2<f32>,
+    impulse_pos:            &Vector2<f32>,
+    smoke_field_texture:    &Field,
+    dst:                    &mut Field,
+) {
+    dst.shade(|uv| {
+        let mut smoke = smoke_field_texture.sample(uv);
+
+        // same distance as `force`
+        let delta = wrap_delta(smoke_field_texture, uv, impulse_pos).component_mul(&Vector2::new(1.0, 1.0 / aspect));
+        let weight = brush.weight(delta);
+
+        smoke.x = brush.apply(delta_t, weight, smoke.x, heat.x);
+        smoke.y = brush.apply(delta_t, weight, smoke.y, heat.y);
+
+        smoke
+    });
+}
+
+fn curl(x: f32, y: f32, delta_x: f32, delta_y: f32, aspect: f32, layout: GridLayout, v: &Field) -> f32 {
+    let upx    = velocity(delta_x, delta_y, layout, v, Vector2::new(x, y + delta_y)).x;
+    let downx  = velocity(delta_x, delta_y, layout, v, Vector2::new(x, y - delta_y)).x;
+    let lefty  = velocity(delta_x, delta_y, layout, v, Vector2::new(x - delta_x, y)).y;
+    let righty = velocity(delta_x, delta_y, layout, v, Vector2::new(x + delta_x, y)).y;
+
+    // ratio of the cell width to its height
+    let cell_ratio = delta_x * aspect / delta_y;
+    0.5 * ((upx - downx) * cell_ratio + lefty - righty)
+}
+
+// confinement force at `p`, scaled by the time step
+fn confinement(delta_t: f32, delta_x: f32, delta_y: f32, aspect: f32, vorticity: f32, layout: GridLayout, v: &Field, p: Vector2<f32>) -> Vector2<f32> {
+    let (x, y) = (p.x, p.y);
+    let curl = |x, y| curl(x, y, delta_x, delta_y, aspect, layout, v);
+
+    let dx = curl(x, y - delta_y).abs() - curl(x, y + delta_y).abs();
+    let dy = curl(x + delta_x, y).abs() - curl(x - delta_x, y).abs();
+
+    let cell_ratio = delta_x * aspect / delta_y;
+    let mut d = Vector2::new(0.5 * dx * cell_ratio, 0.5 * dy);
+    let len = d.norm() + 1e-9;
+    d *= vorticity/len;
+
+    delta_t * curl(x, y) * d
+}
+
+pub fn vorticity_confinement(
+    delta_t:    f32,
+    delta_x:    f32,
+    delta_y:    f32,
+    aspect:     f32,
+    vorticity:  f32,
+    layout:     GridLayout,
+    v:          &Field,
+    dst:        &mut Field,
+) {
+    let confinement = |p| confinement(delta_t, delta_x, delta_y, aspect, vorticity, layout, v, p);
+
+    dst.shade(|uv| {
+        let force = match layout {
+            GridLayout::Collocated => confinement(uv),
+            GridLayout::Staggered => {
+                // each component is pushed by the force at its own face
+                let (u_offset, v_offset) = face_offsets(delta_x, delta_y);
+                Vector2::new(confinement(uv - u_offset).x, confinement(uv - v_offset).y)
+            }
+        };
+
+        v.sample(uv) + Vector4::new(force.x, force.y, 0.0, 0.0)
+    });
+}
@@ -0,0 +1,29 @@
+// How well the last projection enforced incompressibility: norms of the
+// divergence left in the velocity field and of the pressure solve residual.
+
+/// Norms over the red channel of a field.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Norms {
+    /// Root mean square, so grids of different sizes compare directly.
+    pub l2:     f32,
+    pub linf:   f32,
+}
+
+impl Norms {
+    /// Norms from the sum of squares and largest magnitude over `count`
+    /// texels, the two values the reduction passes produce.
+    pub fn from_reduction(sum_squares: f32, max: f32, count: usize) -> Norms {
+        Norms {
+            l2:     (sum_squares / count as f32).sqrt(),
+            linf:   max,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Diagnostics {
+    /// Divergence of the velocity right after the gradient subtraction.
+    pub divergence: Norms,
+    /// `b - laplacian(p)` for the pressure the solve produced.
+    pub residual:   Norms,
+}
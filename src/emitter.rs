@@ -0,0 +1,112 @@
+// Continuous sources of momentum and dye. An emitter is evaluated every
+// substep by `FluidSimulation` through the same `force` and `color` passes as
+// a mouse splat, one splat per point of its source.
+
+use nalgebra::{Vector2, Vector3};
+
+/// Distance between the splats covering a line or region, in units of the
+/// domain width. About the radius of a dye splat, so the disks overlap.
+pub const EMITTER_SPACING: f32 = 0.025;
+
+/// Where an emitter injects. Positions are in uv coordinates, like the splats.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Source {
+    Point(Vector2<f32>),
+    /// A segment between two points, covered by splats along its length.
+    Line { start: Vector2<f32>, end: Vector2<f32> },
+    /// An axis aligned rectangle between two corners, covered by a grid of
+    /// splats. Every splat is a pass over the whole field, so keep regions
+    /// small.
+    Region { min: Vector2<f32>, max: Vector2<f32> },
+}
+
+impl Source {
+    /// The splat positions covering the source on a domain `aspect` times as
+    /// wide as it is tall, no further than `spacing` domain widths apart.
+    pub fn points(&self, aspect: f32, spacing: f32) -> Vec<Vector2<f32>> {
+        // splats needed to span `length` domain widths, forgiving the rounding
+        // of lengths that are a whole number of spacings
+        let count = |length: f32| (length.abs() / spacing - 1e-3).ceil().max(0.0) as usize + 1;
+        let lerp = |a: f32, b: f32, i: usize, n: usize| {
+            if n == 1 { (a + b) / 2.0 } else { a + (b - a) * i as f32 / (n - 1) as f32 }
+        };
+
+        match *self {
+            Source::Point(pos) => vec![pos],
+            Source::Line { start, end } => {
+                let length = (end - start).component_mul(&Vector2::new(1.0, 1.0 / aspect)).norm();
+                let n = count(length);
+                (0..n)
+                    .map(|i| Vector2::new(lerp(start.x, end.x, i, n), lerp(start.y, end.y, i, n)))
+                    .collect()
+            }
+            Source::Region { min, max } => {
+                let nx = count(max.x - min.x);
+                let ny = count((max.y - min.y) / aspect);
+                (0..ny)
+                    .flat_map(|j| (0..nx).map(move |i| (i, j)))
+                    .map(|(i, j)| Vector2::new(lerp(min.x, max.x, i, nx), lerp(min.y, max.y, j, ny)))
+                    .collect()
+            }
+        }
+    }
+}
+
+/// When an emitter is on, in seconds of simulated time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Schedule {
+    Always,
+    /// On from `start` until `end`.
+    Between { start: f32, end: f32 },
+    /// On for the first `on` seconds of every `period`, from the start.
+    Pulse { period: f32, on: f32 },
+}
+
+impl Schedule {
+    pub fn is_on(&self, time: f32) -> bool {
+        match *self {
+            Schedule::Always => true,
+            Schedule::Between { start, end } => start <= time && time < end,
+            Schedule::Pulse { period, on } => period > 0.0 && time.rem_euclid(period) < on,
+        }
+    }
+}
+
+/// A source pushing the fluid along `direction` and stamping `color`, for as
+/// long as its schedule is on.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Emitter {
+    pub source:     Source,
+    /// Direction of the push. Only its direction matters; zero for a source
+    /// that only adds dye.
+    pub direction:  Vector2<f32>,
+    /// Force per second given at each splat of the source, in the units of
+    /// `FluidSimulation::apply_force`.
+    pub rate:       f32,
+    /// Dye stamped over the source every substep, or none.
+    pub color:      Option<Vector3<f32>>,
+    pub schedule:   Schedule,
+}
+
+impl Emitter {
+    /// An emitter that is always on and adds no dye.
+    pub fn new(source: Source, direction: Vector2<f32>, rate: f32) -> Emitter {
+        Emitter {
+            source,
+            direction,
+            rate,
+            color: None,
+            schedule: Schedule::Always,
+        }
+    }
+
+    /// The force the emitter gives at each of its splats.
+    pub fn force(&self) -> Vector2<f32> {
+        let length = self.direction.norm();
+        if length > 0.0 {
+            self.direction * (self.rate / length)
+        } else {
+            Vector2::zeros()
+        }
+    }
+}
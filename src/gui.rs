@@ -2,103 +2,135 @@ use nalgebra::Vector2;
 use wasm_bindgen::JsCast;
 use wasm_bindgen::prelude::*;
 use std::rc::Rc;
-use std::cell::RefCell; 
+use std::cell::RefCell;
+
+/// A mouse button, pen or finger held down on the canvas.
+pub struct Pointer {
+    pub id: i32,
+
+    // in uv coordinates
+    pub pos: Vector2<f32>,
+    // movement since the last `clear_deltas`
+    pub delta: Vector2<f32>,
+    // 0 to 1, 0.5 for devices that cannot sense it
+    pub pressure: f32,
+}
 
 pub struct Gui {
-    pub mouse_pressed: bool,
-
-    pub mouse_pos: Vector2<f32>, 
-    pub mouse_vec: Vector2<f32>,
-
-    pub width: f32, 
-    pub height: f32, 
-
-    pub j_iter: i32,
+    // the pointers held down, in the order they went down
+    pub pointers: Vec<Pointer>,
 }
 
 
 impl Gui {
-    pub fn new(width: f32, height: f32) -> Gui {
+    pub fn new() -> Gui {
         Gui {
-            mouse_pressed: false, 
-            mouse_pos: Vector2::new(0.0, 0.0),
-            mouse_vec: Vector2::new(0.0, 0.0), 
-            width: width,
-            height: height, 
-            j_iter: 20,
+            pointers: Vec::new(),
         }
     }
 
-    pub fn set_mouse_down(&mut self, x: f32, y: f32) {
-        self.mouse_pos = Vector2::new(x / self.width, 1.0 - y / self.height);
-        self.mouse_vec = Vector2::new(0.0, 0.0);
-
-        self.mouse_pressed = true;
+    /// The pointer that went down first, which drags the paddle and paints.
+    pub fn primary(&self) -> Option<&Pointer> {
+        self.pointers.first()
     }
 
-    pub fn set_mouse_move(&mut self, x: f32, y: f32) {
+    pub fn set_pointer_down(&mut self, id: i32, pos: Vector2<f32>, pressure: f32) {
+        self.pointers.retain(|p| p.id != id);
+        self.pointers.push(Pointer { id, pos, delta: Vector2::new(0.0, 0.0), pressure });
+    }
 
-        if !self.mouse_pressed {
-            return;
+    pub fn set_pointer_move(&mut self, id: i32, pos: Vector2<f32>, pressure: f32) {
+        // pointers that are not held down are ignored
+        if let Some(pointer) = self.pointers.iter_mut().find(|p| p.id == id) {
+            pointer.delta += pos - pointer.pos;
+            pointer.pos = pos;
+            pointer.pressure = pressure;
         }
-        let old_pos = self.mouse_pos;
-        self.mouse_pos = Vector2::new(x / self.width, 1.0 - y / self.height);
+    }
 
-        self.mouse_vec = self.mouse_pos - old_pos;
+    pub fn set_pointer_up(&mut self, id: i32) {
+        self.pointers.retain(|p| p.id != id);
     }
 
-    pub fn set_mouse_up(&mut self) {
-        self.mouse_pressed = false;
+    /// Starts the movement of every pointer over, once a frame has used it.
+    pub fn clear_deltas(&mut self) {
+        for pointer in self.pointers.iter_mut() {
+            pointer.delta = Vector2::new(0.0, 0.0);
+        }
     }
 }
 
-fn attach_mouse_down_handler(canvas: &web_sys::HtmlCanvasElement, gui: Rc<RefCell<Gui>>, offset_left: f32, offset_top: f32) -> Result<(), JsValue> {
-    let handler: Box<dyn FnMut(_)> = Box::new(move |event: web_sys::MouseEvent| {
-        let x = event.client_x() as f32 - offset_left; 
-        let y = event.client_y() as f32 - offset_top;
-        
-        gui.borrow_mut().set_mouse_down(x, y);
+// the position of `event` in uv coordinates, through where the canvas is on
+// the page at the time of the event. Scrolling and layout move the bounding
+// rect, and the canvas' CSS size may differ from its backing store, which the
+// division by the displayed size undoes
+fn event_uv(canvas: &web_sys::HtmlCanvasElement, event: &web_sys::MouseEvent) -> Vector2<f32> {
+    let rect = canvas.get_bounding_client_rect();
+
+    // the content box, inside the border
+    let left = rect.left() as f32 + canvas.client_left() as f32;
+    let top = rect.top() as f32 + canvas.client_top() as f32;
+    let width = canvas.client_width().max(1) as f32;
+    let height = canvas.client_height().max(1) as f32;
+
+    let x = (event.client_x() as f32 - left) / width;
+    let y = (event.client_y() as f32 - top) / height;
+    Vector2::new(x, 1.0 - y)
+}
+
+fn attach_pointer_down_handler(canvas: &web_sys::HtmlCanvasElement, gui: Rc<RefCell<Gui>>) -> Result<(), JsValue> {
+    let target = canvas.clone();
+    let handler: Box<dyn FnMut(_)> = Box::new(move |event: web_sys::PointerEvent| {
+        let pos = event_uv(&target, &event);
+
+        // keep the moves coming when the pointer leaves the canvas
+        let _ = target.set_pointer_capture(event.pointer_id());
+        event.prevent_default();
+
+        gui.borrow_mut().set_pointer_down(event.pointer_id(), pos, event.pressure());
     });
 
     let handler = Closure::wrap(handler);
-    canvas.add_event_listener_with_callback("mousedown", handler.as_ref().unchecked_ref())?;
+    canvas.add_event_listener_with_callback("pointerdown", handler.as_ref().unchecked_ref())?;
     handler.forget();
 
     Ok(())
 }
 
-fn attach_mouse_move_handler(canvas: &web_sys::HtmlCanvasElement, gui: Rc<RefCell<Gui>>, offset_left: f32, offset_top: f32) -> Result<(), JsValue> {
-    let handler: Box<dyn FnMut(_)> = Box::new(move |event: web_sys::MouseEvent| {
-        let x = event.client_x() as f32 - offset_left; 
-        let y = event.client_y() as f32 - offset_top;
-    
-        gui.borrow_mut().set_mouse_move(x, y); 
+fn attach_pointer_move_handler(canvas: &web_sys::HtmlCanvasElement, gui: Rc<RefCell<Gui>>) -> Result<(), JsValue> {
+    let target = canvas.clone();
+    let handler: Box<dyn FnMut(_)> = Box::new(move |event: web_sys::PointerEvent| {
+        let pos = event_uv(&target, &event);
+
+        gui.borrow_mut().set_pointer_move(event.pointer_id(), pos, event.pressure());
     });
 
 
     let handler = Closure::wrap(handler);
-    canvas.add_event_listener_with_callback("mousemove", handler.as_ref().unchecked_ref())?;
-    handler.forget(); 
+    canvas.add_event_listener_with_callback("pointermove", handler.as_ref().unchecked_ref())?;
+    handler.forget();
 
     Ok(())
 }
 
-fn attach_mouse_up_handler(canvas: &web_sys::HtmlCanvasElement, gui: Rc<RefCell<Gui>>) -> Result<(), JsValue> {
-    let handler: Box<dyn FnMut()> = Box::new(move || {
-        gui.borrow_mut().set_mouse_up();
+fn attach_pointer_up_handler(canvas: &web_sys::HtmlCanvasElement, gui: Rc<RefCell<Gui>>) -> Result<(), JsValue> {
+    let handler: Box<dyn FnMut(_)> = Box::new(move |event: web_sys::PointerEvent| {
+        gui.borrow_mut().set_pointer_up(event.pointer_id());
     });
 
+    // a touch the browser takes over for scrolling is cancelled, not lifted
     let handler = Closure::wrap(handler);
-    canvas.add_event_listener_with_callback("mouseup", handler.as_ref().unchecked_ref())?;
+    canvas.add_event_listener_with_callback("pointerup", handler.as_ref().unchecked_ref())?;
+    canvas.add_event_listener_with_callback("pointercancel", handler.as_ref().unchecked_ref())?;
     handler.forget();
 
     Ok(())
 }
 
-pub fn attach_mouse_handlers(canvas: &web_sys::HtmlCanvasElement, gui: Rc<RefCell<Gui>>, offset_left: f32, offset_top: f32) -> Result<(), JsValue>{
-    attach_mouse_down_handler(&canvas, Rc::clone(&gui), offset_left, offset_top)?;
-    attach_mouse_move_handler(&canvas, Rc::clone(&gui), offset_left, offset_top)?;
-    attach_mouse_up_handler(&canvas, Rc::clone(&gui))?;
+pub fn attach_pointer_handlers(canvas: &web_sys::HtmlCanvasElement, gui: Rc<RefCell<Gui>>) -> Result<(), JsValue>{
+    attach_pointer_down_handler(canvas, Rc::clone(&gui))?;
+    attach_pointer_move_handler(canvas, Rc::clone(&gui))?;
+    attach_pointer_up_handler(canvas, Rc::clone(&gui))?;
 
     Ok(())
-} 
\ No newline at end of file
+}
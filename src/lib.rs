@@ -1,16 +1,26 @@
 mod utils;
 mod shader;
 mod geometry;
-mod texture;
+pub mod texture;
 mod render;
 mod render_fluid;
 mod gui;
+pub mod backend;
+pub mod simulation;
+pub mod multigrid;
+pub mod pcg;
+pub mod cpu_fluid;
+pub mod diagnostics;
+pub mod timestep;
+pub mod obstacle;
+pub mod emitter;
+pub mod brush;
 
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use web_sys::WebGlRenderingContext as GL;
 
-use nalgebra::Vector3;
+use nalgebra::{Vector2, Vector3};
 
 use std::cell::RefCell; 
 use std::rc::Rc;
@@ -21,26 +31,82 @@ use std::rc::Rc;
 #[global_allocator]
 static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
+// seconds of simulated time per step, and the most steps one frame may take
+const SIM_STEP: f32 = 1.0 / 60.0;
+const MAX_STEPS_PER_FRAME: usize = 4;
+
+// inflow velocity of the wind tunnel edges
+const WIND_SPEED: f32 = 1.0;
+
+// the emitters of each option of the emitter select
+fn emitter_preset(index: i32) -> Vec<emitter::Emitter> {
+    use emitter::{Emitter, Schedule, Source};
+
+    match index {
+        // a jet blowing in from the left edge
+        1 | 2 => {
+            let mut jet = Emitter::new(Source::Point(Vector2::new(0.1, 0.5)), Vector2::new(1.0, 0.0), 2.0);
+            jet.color = Some(Vector3::new(1.0, 0.5, 0.0));
+            if index == 2 {
+                jet.schedule = Schedule::Pulse { period: 1.0, on: 0.25 };
+            }
+            vec![jet]
+        }
+        // dye sources across the left edge, to show the streamlines of a wind
+        // tunnel without pushing on the flow
+        3 => (1..8)
+            .map(|i| {
+                let y = i as f32 / 8.0;
+                let mut streak = Emitter::new(Source::Point(Vector2::new(0.02, y)), Vector2::zeros(), 0.0);
+                streak.color = Some(if i % 2 == 0 { Vector3::new(1.0, 1.0, 1.0) } else { Vector3::new(0.2, 0.4, 1.0) });
+                streak
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
 fn window() -> web_sys::Window {
     web_sys::window().expect("no global `window` exists")
 }
 
-fn request_animation_frame(f: &Closure<dyn FnMut(i32)>) {
+fn request_animation_frame(f: &Closure<dyn FnMut(f64)>) {
     window()
         .request_animation_frame(f.as_ref().unchecked_ref())
         .expect("should register `requestAnimationFrame` OK");
 }
 
+// sets the canvas' backing store to its displayed size in device pixels, and
+// returns that size
+fn fit_to_display(canvas: &web_sys::HtmlCanvasElement) -> (i32, i32) {
+    let ratio = window().device_pixel_ratio();
+    let width = (canvas.client_width() as f64 * ratio).round().max(1.0) as u32;
+    let height = (canvas.client_height() as f64 * ratio).round().max(1.0) as u32;
+
+    if canvas.width() != width || canvas.height() != height {
+        canvas.set_width(width);
+        canvas.set_height(height);
+    }
+    (width as i32, height as i32)
+}
+
+// a grid `resolution` texels across the shorter side of the display and with
+// its aspect ratio, or the display's own size without a resolution
+fn grid_size(display: (i32, i32), resolution: Option<i32>) -> (i32, i32) {
+    let (width, height) = display;
+    match resolution {
+        Some(resolution) if width < height => (resolution, (resolution * height + width / 2) / width),
+        Some(resolution) => ((resolution * width + height / 2) / height, resolution),
+        None => display,
+    }
+}
+
 fn document() -> web_sys::Document {
     window()
         .document()
         .expect("should have a document on the window")
 }
 
-fn body() -> web_sys::HtmlElement {
-    document().body().expect("document should have a body")
-}
-
 #[wasm_bindgen(start)]
 pub fn start() -> Result<(), JsValue> {
     utils::set_panic_hook(); // this allows us to get more detailed information from rust runtime errors
@@ -73,281 +139,361 @@ pub fn start() -> Result<(), JsValue> {
     let color_field_select = document().get_element_by_id("color_field_select").unwrap();
     let color_field_select: web_sys::HtmlSelectElement = color_field_select.dyn_into::<web_sys::HtmlSelectElement>()?;
 
-    let width: i32 = canvas.width() as i32;
-    let height: i32 = canvas.height() as i32;
-    let gui = Rc::new(RefCell::new(gui::Gui::new(width as f32, height as f32)));
+    let advection_select = document().get_element_by_id("advection_select").unwrap();
+    let advection_select: web_sys::HtmlSelectElement = advection_select.dyn_into::<web_sys::HtmlSelectElement>()?;
+
+    let integrator_select = document().get_element_by_id("integrator_select").unwrap();
+    let integrator_select: web_sys::HtmlSelectElement = integrator_select.dyn_into::<web_sys::HtmlSelectElement>()?;
+
+    let pressure_select = document().get_element_by_id("pressure_select").unwrap();
+    let pressure_select: web_sys::HtmlSelectElement = pressure_select.dyn_into::<web_sys::HtmlSelectElement>()?;
+
+    let grid_select = document().get_element_by_id("grid_select").unwrap();
+    let grid_select: web_sys::HtmlSelectElement = grid_select.dyn_into::<web_sys::HtmlSelectElement>()?;
+
+    let pressure_relaxation_select = document().get_element_by_id("pressure_relaxation_select").unwrap();
+    let pressure_relaxation_select: web_sys::HtmlSelectElement = pressure_relaxation_select.dyn_into::<web_sys::HtmlSelectElement>()?;
+
+    let diffusion_relaxation_select = document().get_element_by_id("diffusion_relaxation_select").unwrap();
+    let diffusion_relaxation_select: web_sys::HtmlSelectElement = diffusion_relaxation_select.dyn_into::<web_sys::HtmlSelectElement>()?;
+
+    let obstacle_select = document().get_element_by_id("obstacle_select").unwrap();
+    let obstacle_select: web_sys::HtmlSelectElement = obstacle_select.dyn_into::<web_sys::HtmlSelectElement>()?;
+
+    let obstacle_slip_select = document().get_element_by_id("obstacle_slip_select").unwrap();
+    let obstacle_slip_select: web_sys::HtmlSelectElement = obstacle_slip_select.dyn_into::<web_sys::HtmlSelectElement>()?;
+
+    let horizontal_edges_select = document().get_element_by_id("horizontal_edges_select").unwrap();
+    let horizontal_edges_select: web_sys::HtmlSelectElement = horizontal_edges_select.dyn_into::<web_sys::HtmlSelectElement>()?;
+
+    let vertical_edges_select = document().get_element_by_id("vertical_edges_select").unwrap();
+    let vertical_edges_select: web_sys::HtmlSelectElement = vertical_edges_select.dyn_into::<web_sys::HtmlSelectElement>()?;
+
+    let emitter_select = document().get_element_by_id("emitter_select").unwrap();
+    let emitter_select: web_sys::HtmlSelectElement = emitter_select.dyn_into::<web_sys::HtmlSelectElement>()?;
+
+    let brush_falloff_select = document().get_element_by_id("brush_falloff_select").unwrap();
+    let brush_falloff_select: web_sys::HtmlSelectElement = brush_falloff_select.dyn_into::<web_sys::HtmlSelectElement>()?;
+
+    let dye_blend_select = document().get_element_by_id("dye_blend_select").unwrap();
+    let dye_blend_select: web_sys::HtmlSelectElement = dye_blend_select.dyn_into::<web_sys::HtmlSelectElement>()?;
+
+    let brush_size_slider = document().get_element_by_id("brush_size_slider").unwrap();
+    let brush_size_slider: web_sys::HtmlInputElement = brush_size_slider.dyn_into::<web_sys::HtmlInputElement>()?;
+
+    let dye_strength_slider = document().get_element_by_id("dye_strength_slider").unwrap();
+    let dye_strength_slider: web_sys::HtmlInputElement = dye_strength_slider.dyn_into::<web_sys::HtmlInputElement>()?;
+
+    let sim_resolution_select = document().get_element_by_id("sim_resolution_select").unwrap();
+    let sim_resolution_select: web_sys::HtmlSelectElement = sim_resolution_select.dyn_into::<web_sys::HtmlSelectElement>()?;
+
+    let dye_resolution_select = document().get_element_by_id("dye_resolution_select").unwrap();
+    let dye_resolution_select: web_sys::HtmlSelectElement = dye_resolution_select.dyn_into::<web_sys::HtmlSelectElement>()?;
+
+    let omega_slider = document().get_element_by_id("omega_slider").unwrap();
+    let omega_slider: web_sys::HtmlInputElement = omega_slider.dyn_into::<web_sys::HtmlInputElement>()?;
+
+    let cfl_slider = document().get_element_by_id("cfl_slider").unwrap();
+    let cfl_slider: web_sys::HtmlInputElement = cfl_slider.dyn_into::<web_sys::HtmlInputElement>()?;
+
+    let smoke_checkbox = document().get_element_by_id("smoke_checkbox").unwrap();
+    let smoke_checkbox: web_sys::HtmlInputElement = smoke_checkbox.dyn_into::<web_sys::HtmlInputElement>()?;
+
+    let temperature_slider = document().get_element_by_id("temperature_slider").unwrap();
+    let temperature_slider: web_sys::HtmlInputElement = temperature_slider.dyn_into::<web_sys::HtmlInputElement>()?;
+
+    let lift_slider = document().get_element_by_id("lift_slider").unwrap();
+    let lift_slider: web_sys::HtmlInputElement = lift_slider.dyn_into::<web_sys::HtmlInputElement>()?;
+
+    let velocity_dissipation_slider = document().get_element_by_id("velocity_dissipation_slider").unwrap();
+    let velocity_dissipation_slider: web_sys::HtmlInputElement = velocity_dissipation_slider.dyn_into::<web_sys::HtmlInputElement>()?;
+
+    let dye_dissipation_slider = document().get_element_by_id("dye_dissipation_slider").unwrap();
+    let dye_dissipation_slider: web_sys::HtmlInputElement = dye_dissipation_slider.dyn_into::<web_sys::HtmlInputElement>()?;
+
+    let diagnostics_checkbox = document().get_element_by_id("diagnostics_checkbox").unwrap();
+    let diagnostics_checkbox: web_sys::HtmlInputElement = diagnostics_checkbox.dyn_into::<web_sys::HtmlInputElement>()?;
 
-    gui::attach_mouse_handlers(&canvas, Rc::clone(&gui), canvas.offset_left() as f32, canvas.offset_top() as f32)?;
+    let diagnostics_text = document().get_element_by_id("diagnostics_text").unwrap();
+
+    // the velocity and dye grids each have a resolution of their own, with
+    // the aspect ratio of the canvas' backing store, sized to its displayed
+    // size
+    let sim_resolution = |select: &web_sys::HtmlSelectElement| match select.selected_index() {
+        0 => Some(64),
+        2 => Some(256),
+        3 => None,
+        _ => Some(128),
+    };
+    let dye_resolution = |select: &web_sys::HtmlSelectElement| match select.selected_index() {
+        0 => Some(256),
+        1 => Some(512),
+        3 => None,
+        _ => Some(1024),
+    };
+    let display = fit_to_display(&canvas);
+    let (mut width, mut height) = grid_size(display, sim_resolution(&sim_resolution_select));
+    let (mut dye_width, mut dye_height) = grid_size(display, dye_resolution(&dye_resolution_select));
+    let gui = Rc::new(RefCell::new(gui::Gui::new()));
+
+    gui::attach_pointer_handlers(&canvas, Rc::clone(&gui))?;
 
     let gl = canvas.get_context("webgl")?.unwrap().dyn_into::<GL>()?;
     gl.get_extension("OES_texture_float")?;
     gl.get_extension("OES_texture_float_linear")?;
+    gl.get_extension("WEBGL_color_buffer_float")?;
 
-    let standard_vert_shader = shader::compile_shader(&gl, GL::VERTEX_SHADER, shader::STANDARD_VERTEX_SHADER)?;
-    let quad_frag_shader = shader::compile_shader(&gl, GL::FRAGMENT_SHADER, shader::QUAD_FRAGMENT_SHADER)?;
-    let advect_frag_shader = shader::compile_shader(&gl, GL::FRAGMENT_SHADER, shader::ADVECT_FRAGMENT_SHADER)?;
-    let jacobi_frag_shader = shader::compile_shader(&gl, GL::FRAGMENT_SHADER, shader::JACOBI_FRAGMENT_SHADER)?;
-    let divergence_frag_shader = shader::compile_shader(&gl, GL::FRAGMENT_SHADER, shader::DIVERGE_FRAGMENT_SHADER)?;
-    let subtract_frag_shader = shader::compile_shader(&gl, GL::FRAGMENT_SHADER, shader::SUB_FRAGMENT_SHADER)?;
-    let bound_frag_shader = shader::compile_shader(&gl, GL::FRAGMENT_SHADER, shader::BOUND_FRAGMENT_SHADER)?;
-    let force_frag_shader = shader::compile_shader(&gl, GL::FRAGMENT_SHADER, shader::FORCE_FRAGMENT_SHADER)?;
-    let color_frag_shader = shader::compile_shader(&gl, GL::FRAGMENT_SHADER, shader::COLOR_FRAGMENT_SHADER)?;
-    let vorticity_frag_shader = shader::compile_shader(&gl, GL::FRAGMENT_SHADER, shader::VORT_FRAGMENT_SHADER)?;
-
-    let advect_pass = render::RenderPass::new(&gl, 
-        [&standard_vert_shader, &advect_frag_shader],
-        vec!["delta_x", "vec_field_texture",  "color_field_texture", "delta_t"], "vertex_position",
-        &geometry::QUAD_VERTICES, &geometry::QUAD_INDICES,
-    )?;
-
-    let quad_pass = render::RenderPass::new(&gl,
-        [&standard_vert_shader, &quad_frag_shader],
-        vec!["qtexture"], "vertex_position",
-        &geometry::QUAD_VERTICES, &geometry::QUAD_INDICES,
-    )?;
-
-    let jacobi_pass = render::RenderPass::new(&gl, 
-        [&standard_vert_shader, &jacobi_frag_shader],
-        vec!["delta_x", "alpha", "r_beta", "x", "b"], "vertex_position",
-        &geometry::QUAD_VERTICES, &geometry::QUAD_INDICES,
-    )?;
-
-    let divergence_pass = render::RenderPass::new(&gl, 
-        [&standard_vert_shader, &divergence_frag_shader],
-        vec!["delta_x", "w"], "vertex_position",
-        &geometry::QUAD_VERTICES, &geometry::QUAD_INDICES,
-    )?;
-
-    let subtract_pass = render::RenderPass::new(&gl,
-        [&standard_vert_shader, &subtract_frag_shader],
-        vec!["delta_x", "p", "w"], "vertex_position",
-        &geometry::QUAD_VERTICES, &geometry::QUAD_INDICES,
-    )?;
-
-    let boundary_pass = render::RenderPass::new(&gl,
-        [&standard_vert_shader, &bound_frag_shader],
-        vec!["delta_x", "scale", "x"], "vertex_position",
-        &geometry::QUAD_VERTICES, &geometry::QUAD_INDICES,
-    )?;
-
-    let force_pass = render::RenderPass::new(&gl,
-        [&standard_vert_shader, &force_frag_shader],
-        vec!["delta_t", "rho", "force", "impulse_pos", "velocity_field_texture"], "vertex_position",
-        &geometry::QUAD_VERTICES, &geometry::QUAD_INDICES,
-    )?;
-
-    let color_pass = render::RenderPass::new(&gl,
-        [&standard_vert_shader, &color_frag_shader],
-        vec!["delta_t", "rho", "color", "impulse_pos", "color_field_texture"], "vertex_position",
-        &geometry::QUAD_VERTICES, &geometry::QUAD_INDICES,
-    )?;
-
-    let vorticity_pass = render::RenderPass::new(&gl, 
-        [&standard_vert_shader, &vorticity_frag_shader],
-        vec!["delta_t", "delta_x", "vorticity", "v"], "vertex_position", 
-        &geometry::QUAD_VERTICES, &geometry::QUAD_INDICES,
-    )?;
+    let mut sim = simulation::FluidSimulation::new(&gl, width, height, simulation::SimulationParams::default())?;
+    sim.set_velocity_field(texture::make_waves_vector_field(width as f32, height as f32))?;
+    sim.resize_dye(dye_width, dye_height)?;
+    sim.set_color_field(texture::make_rainbow_array(dye_width, dye_height))?;
 
     // RenderLoop 
     let f = Rc::new(RefCell::new(None));
     let g = f.clone(); 
 
-    let delta_x = 1.0/width as f32;
-
-    let vf_data = texture::make_waves_vector_field(width as f32, height as f32);
-    let cb_data = texture::make_rainbow_array(width, height);
-
     let mut cur_vector = 0;
     let mut cur_color = 0;
-
-    let mut src_velocity_field = Rc::new(texture::Framebuffer::create_with_data(&gl, width, height, vf_data)?);
-    let mut dst_velocity_field = Rc::new(texture::Framebuffer::new(&gl, width, height)?);
-
-    let mut src_pressure_field = Rc::new(texture::Framebuffer::new(&gl, width, height)?);
-    let mut dst_pressure_field = Rc::new(texture::Framebuffer::new(&gl, width, height)?);
-
-    let mut divergence_fb = Rc::new(texture::Framebuffer::new(&gl, width, height)?);
-
-    let mut src_color_field = Rc::new(texture::Framebuffer::create_with_data(&gl, width, height, cb_data)?);
-    let mut dst_color_field = Rc::new(texture::Framebuffer::new(&gl, width, height)?);
+    let mut cur_obstacle = 0;
+    let mut cur_emitters = 0;
 
     let rainbow_colors = texture::get_rainbow_array();
 
-    let mainloop: Box<dyn FnMut(i32)> = Box::new(move |now| { 
-        let gui = gui.borrow();
+    let mut timestep = timestep::FixedTimestep::new(SIM_STEP, MAX_STEPS_PER_FRAME);
+
+    // grid sizes the device could not allocate, not tried again until the
+    // canvas or the resolution asks for another
+    let mut refused_size = None;
+    let mut refused_dye_size = None;
+
+    let mainloop: Box<dyn FnMut(f64)> = Box::new(move |now| { 
+        let mut gui = gui.borrow_mut();
+
+        // the canvas was resized or moved to a screen of another pixel ratio,
+        // or a resolution was picked
+        let display = fit_to_display(&canvas);
+        let size = grid_size(display, sim_resolution(&sim_resolution_select));
+        if size != (width, height) && refused_size != Some(size) {
+            match sim.resize(size.0, size.1) {
+                Ok(()) => {
+                    width = size.0;
+                    height = size.1;
+                }
+                // carry on at the size it had
+                Err(e) => {
+                    crate::log!("could not resize the simulation to {}x{}: {:?}", size.0, size.1, e);
+                    refused_size = Some(size);
+                }
+            }
+        }
+        let dye_size = grid_size(display, dye_resolution(&dye_resolution_select));
+        if dye_size != (dye_width, dye_height) && refused_dye_size != Some(dye_size) {
+            match sim.resize_dye(dye_size.0, dye_size.1) {
+                Ok(()) => {
+                    dye_width = dye_size.0;
+                    dye_height = dye_size.1;
+                }
+                Err(e) => {
+                    crate::log!("could not resize the dye to {}x{}: {:?}", dye_size.0, dye_size.1, e);
+                    refused_dye_size = Some(dye_size);
+                }
+            }
+        }
 
-        let iter = jacobi_slider.value_as_number() as usize;
-        let delta_t = 1.0/60.0;
+        sim.params.jacobi_iterations = jacobi_slider.value_as_number() as usize;
+        sim.params.viscosity = (10.0_f32).powf(viscocity_slider.value_as_number() as f32);
+        sim.params.vorticity = vorticity_slider.value_as_number() as f32;
+        sim.params.cfl = cfl_slider.value_as_number() as f32 / 10.0;
+        sim.params.smoke = smoke_checkbox.checked();
+        sim.params.buoyancy_lift = lift_slider.value_as_number() as f32 / 10.0;
+        sim.params.velocity_dissipation = velocity_dissipation_slider.value_as_number() as f32 / 100.0;
+        sim.params.dye_dissipation = dye_dissipation_slider.value_as_number() as f32 / 100.0;
+        sim.params.smoke_dissipation = sim.params.dye_dissipation;
+        sim.params.grid = match grid_select.selected_index() {
+            1 => backend::GridLayout::Staggered,
+            _ => backend::GridLayout::Collocated,
+        };
+        sim.params.advection = match advection_select.selected_index() {
+            1 => simulation::AdvectionScheme::MacCormack,
+            _ => simulation::AdvectionScheme::SemiLagrangian,
+        };
+        sim.params.integrator = match integrator_select.selected_index() {
+            1 => backend::Integrator::Midpoint,
+            2 => backend::Integrator::Rk3,
+            _ => backend::Integrator::Euler,
+        };
+        sim.params.pressure_solver = match pressure_select.selected_index() {
+            1 => simulation::PressureSolver::Multigrid,
+            _ => simulation::PressureSolver::Jacobi,
+        };
+
+        let omega = omega_slider.value_as_number() as f32 / 100.0;
+        let relaxation = |select: &web_sys::HtmlSelectElement| match select.selected_index() {
+            1 => simulation::Relaxation::RedBlackSor { omega },
+            _ => simulation::Relaxation::Jacobi,
+        };
+        sim.params.pressure_relaxation = relaxation(&pressure_relaxation_select);
+        sim.params.diffusion_relaxation = relaxation(&diffusion_relaxation_select);
+        sim.params.obstacle_slip = match obstacle_slip_select.selected_index() {
+            1 => backend::Slip::FreeSlip,
+            _ => backend::Slip::NoSlip,
+        };
+
+        // both brushes scaled from their defaults, the default falloff keeping
+        // the gaussian force and the hard edged dye
+        let size = brush_size_slider.value_as_number() as f32 / 100.0;
+        let falloff = match brush_falloff_select.selected_index() {
+            1 => Some(brush::Falloff::Hard),
+            2 => Some(brush::Falloff::Gaussian),
+            3 => Some(brush::Falloff::Smoothstep),
+            _ => None,
+        };
+        let mut force_brush = brush::Brush::force();
+        let mut dye_brush = brush::Brush::dye();
+        force_brush.radius *= size;
+        dye_brush.radius *= size;
+        if let Some(falloff) = falloff {
+            force_brush.falloff = falloff;
+            dye_brush.falloff = falloff;
+        }
+        dye_brush.strength = dye_strength_slider.value_as_number() as f32 / 10.0;
+        dye_brush.blend = match dye_blend_select.selected_index() {
+            1 => brush::Blend::Additive,
+            2 => brush::Blend::AlphaOver,
+            _ => brush::Blend::Replace,
+        };
+        sim.params.force_brush = force_brush;
+        sim.params.dye_brush = dye_brush;
+
+        // a pair of edges, the last option opening the far edge (and for the
+        // wind tunnel blowing in through the near one)
+        let edges = |select: &web_sys::HtmlSelectElement, inflow: Option<Vector2<f32>>| match select.selected_index() {
+            1 => (backend::Edge::FreeSlip, backend::Edge::FreeSlip),
+            2 => (backend::Edge::Periodic, backend::Edge::Periodic),
+            3 => (inflow.map_or(backend::Edge::Outflow, backend::Edge::Inflow), backend::Edge::Outflow),
+            _ => (backend::Edge::NoSlip, backend::Edge::NoSlip),
+        };
+        let (left, right) = edges(&horizontal_edges_select, Some(Vector2::new(WIND_SPEED, 0.0)));
+        let (bottom, top) = edges(&vertical_edges_select, None);
+        sim.params.boundaries = backend::Boundaries { left, right, bottom, top };
 
         let vector_field_select_value = vector_field_select.selected_index();
         let color_field_select_value = color_field_select.selected_index();
 
         if vector_field_select_value != cur_vector {
-            src_velocity_field.delete_buffers(&gl);
-            src_pressure_field.delete_buffers(&gl);
             let data = texture::get_vector_field_with_value(vector_field_select_value, width, height);
-            src_velocity_field = Rc::new(texture::Framebuffer::create_with_data(&gl, width, height, data).unwrap());
-            src_pressure_field = Rc::new(texture::Framebuffer::new(&gl, width, height).unwrap());
+            sim.set_velocity_field(data).unwrap();
 
             cur_vector = vector_field_select_value;
         }
         
         if color_field_select_value != cur_color {
-            src_color_field.delete_buffers(&gl);
-            let data = texture::get_color_field_with_value(color_field_select_value, width, height);
-            src_color_field = Rc::new(texture::Framebuffer::create_with_data(&gl, width, height, data).unwrap());
+            let data = texture::get_color_field_with_value(color_field_select_value, dye_width, dye_height);
+            sim.set_color_field(data).unwrap();
 
             cur_color = color_field_select_value;
         }
 
-        {
-            // advect vector field
-            let result = render_fluid::advection(&gl, &advect_pass,
-                delta_x, delta_t,  
-                Rc::clone(&src_velocity_field), &src_velocity_field, Rc::clone(&dst_velocity_field));
-            
-            src_velocity_field = result.0;
-            dst_velocity_field = result.1; // rust does not have destructuring assignment yet https://github.com/rust-lang/rfcs/issues/372
-        }
+        let steps = timestep.advance(now);
 
-        {
-            // viscuous diffusion
-            let viscocity = (10.0_f32).powf(viscocity_slider.value_as_number() as f32);
-            let alpha   = delta_x.powf(2.0) / (viscocity * delta_t);
-            let r_beta  = 1.0/(4.0 + alpha);
+        let obstacle_select_value = obstacle_select.selected_index();
+        if obstacle_select_value != cur_obstacle {
+            // painting and the paddle start from an empty domain
+            sim.bodies.clear();
+            match obstacle_select_value {
+                1 => {
+                    let circle = obstacle::Shape::Circle { center: Vector2::new(0.5, 0.5), radius: 0.1 };
+                    sim.set_obstacles(obstacle::make_shapes_mask(width, height, &[circle])).unwrap();
+                }
+                3 => {
+                    sim.clear_obstacles();
+                    let paddle = obstacle::BodyShape::Rect { half_size: Vector2::new(0.01, 0.08) };
+                    sim.bodies.push(obstacle::RigidBody::new(paddle, Vector2::new(0.5, 0.5)));
+                }
+                _ => sim.clear_obstacles(),
+            }
 
-            let bufs = [&src_velocity_field, &dst_velocity_field];
-            for k in 0..iter {
-                let j_source = bufs[k % 2];
-                let j_dst = bufs[(k + 1) % 2];
+            cur_obstacle = obstacle_select_value;
+        }
 
-                j_dst.bind(&gl);
-                render_fluid::jacobi_iteration(&gl, &jacobi_pass, delta_x, alpha, r_beta, &j_source, &j_source);            
-                j_dst.unbind(&gl);
-            }
+        let emitter_select_value = emitter_select.selected_index();
+        if emitter_select_value != cur_emitters {
+            sim.emitters = emitter_preset(emitter_select_value);
+            cur_emitters = emitter_select_value;
         }
 
-        {
-            if gui.mouse_pressed {
-                // add forces
-                let rho = 1e-3;
-                let speed = speed_slider.value_as_number() as f32;
-                let force = speed * gui.mouse_vec;
-                let impulse_pos = gui.mouse_pos;
-                let result = render_fluid::force(&gl, &force_pass,
-                    delta_t, rho, &force, &impulse_pos,  
-                    Rc::clone(&src_velocity_field), Rc::clone(&dst_velocity_field));
-                
-                src_velocity_field = result.0;
-                dst_velocity_field = result.1;
+        // the paddle is dragged instead, below
+        if obstacle_select_value == 2 {
+            for pointer in &gui.pointers {
+                sim.paint_obstacle(pointer.pos, true);
+            }
+        } else if obstacle_select_value != 3 && steps > 0 {
+            // splats only on frames that step, which take them all at once;
+            // frames without a step leave the movement to the next that has one
+            let speed = speed_slider.value_as_number() as f32;
+            let rand_checked = random_color.checked();
+            let selected_color = &splat_color.value()[1..];
+            let color_hex = hex::decode(selected_color).unwrap();
+            let temperature = temperature_slider.value_as_number() as f32;
+
+            // one splat per pointer held down
+            for (i, pointer) in gui.pointers.iter().enumerate() {
+                // add forces, a mouse's pressure of 0.5 leaving them as they are
+                sim.apply_force(2.0 * pointer.pressure * speed * pointer.delta, pointer.pos);
 
                 // add dye
-                let rand_checked = random_color.checked();
-                let selected_color = &splat_color.value()[1..];
-                let color_hex = hex::decode(selected_color).unwrap();
                 let mut r = color_hex[0] as f32 / 255.0;
                 let mut g = color_hex[1] as f32 / 255.0;
                 let mut b = color_hex[2] as f32 / 255.0;
-                
+
                 if rand_checked {
-                    let now_sec = now as f32 * 0.25;
+                    // each touch a different color
+                    let now_sec = now as f32 * 0.25 + i as f32 * 97.0;
                     let rand_color = rainbow_colors[(now_sec % rainbow_colors.len() as f32) as usize];
                     r = rand_color.red;
                     g = rand_color.green;
                     b = rand_color.blue;
                 }
 
-                let col = Vector3::new(r, g, b);
+                sim.add_dye(Vector3::new(r, g, b), pointer.pos);
 
-                let result = render_fluid::color(&gl, &color_pass,
-                    delta_t, rho, &col, &impulse_pos,  
-                    Rc::clone(&src_color_field), Rc::clone(&dst_color_field));
-                
-                src_color_field = result.0;
-                dst_color_field = result.1;
+                // heat, with a tenth as much smoke to weigh it down
+                sim.add_heat(temperature, 0.1 * temperature, pointer.pos);
             }
         }
 
-        {
-            // compute pressure 
-            divergence_fb = render_fluid::divergence(&gl, &divergence_pass, 
-                delta_x, &src_velocity_field, Rc::clone(&divergence_fb));
-
-            let alpha   = -(delta_x.powf(2.0));    
-            let r_beta  = 0.25;
-
-            let result = render_fluid::jacobi_method(&gl, &jacobi_pass, iter, 
-                delta_x, alpha, r_beta, 
-                Rc::clone(&src_pressure_field), &divergence_fb, Rc::clone(&dst_pressure_field));
-
-            src_pressure_field = result.0;
-            dst_pressure_field = result.1;
-
-        }
-
-        {
-            // gradient subtraction
-            let result = render_fluid::subtract(&gl, &subtract_pass, 
-                delta_x, &src_pressure_field, 
-                Rc::clone(&src_velocity_field), Rc::clone(&dst_velocity_field));
-                
-            src_velocity_field = result.0;
-            dst_velocity_field = result.1;
+        if steps > 0 {
+            gui.clear_deltas();
         }
 
-        {
-            // boundary conditions
-            let v_result = render_fluid::boundary(&gl, &boundary_pass, 
-                delta_x, -1.0, Rc::clone(&src_velocity_field), Rc::clone(&dst_velocity_field));
-            src_velocity_field = v_result.0;
-            dst_velocity_field = v_result.1;
-
-            let p_result = render_fluid::boundary(&gl, &boundary_pass, 
-                delta_x, 1.0, Rc::clone(&src_pressure_field), Rc::clone(&dst_pressure_field));
-                src_pressure_field = p_result.0;
-                dst_pressure_field = p_result.1;
+        // the paddle moves to the first pointer over the steps of this frame
+        if let Some(paddle) = sim.bodies.first_mut() {
+            paddle.velocity = Vector2::zeros();
+            if let (Some(pointer), true) = (gui.primary(), steps > 0) {
+                let aspect = width as f32 / height as f32;
+                let offset = (pointer.pos - paddle.position).component_mul(&Vector2::new(1.0, 1.0 / aspect));
+                paddle.velocity = offset / (steps as f32 * timestep.step);
+            }
         }
 
-        {   
-            let vorticity = vorticity_slider.value_as_number() as f32;
-            let result = render_fluid::vorticity_confinement(&gl, &vorticity_pass, 
-                delta_t, delta_x, vorticity, 
-                Rc::clone(&src_velocity_field), Rc::clone(&dst_velocity_field));
-            
-            src_velocity_field = result.0;
-            dst_velocity_field = result.1;
-            
+        for _ in 0..steps {
+            sim.step(timestep.step);
         }
 
-        {
-            // advect color field
-            let result = render_fluid::advection(&gl, &advect_pass,
-                 delta_x, delta_t, 
-                 Rc::clone(&src_color_field), &src_velocity_field, Rc::clone(&dst_color_field));
-            
-            src_color_field = result.0;
-            dst_color_field = result.1;
+        if diagnostics_checkbox.checked() {
+            // a failed readback leaves the last frame's figures up
+            match sim.diagnostics() {
+                Ok(d) => diagnostics_text.set_text_content(Some(&format!(
+                    "{} substeps, divergence L2 {:.3e} Linf {:.3e}, residual L2 {:.3e} Linf {:.3e}",
+                    sim.substeps(), d.divergence.l2, d.divergence.linf, d.residual.l2, d.residual.linf))),
+                Err(e) => {
+                    crate::log!("could not read the diagnostics back: {:?}", e);
+                }
+            }
         }
 
-        
-        {   
-            // render texture to screen 
-            render::clear_framebuffer(&gl);
-
-            quad_pass.use_program(&gl);
-            gl.uniform1i(quad_pass.uniforms["qtexture"].as_ref(), 0);
-            
-            gl.active_texture(GL::TEXTURE0);
-            gl.bind_texture(GL::TEXTURE_2D, Some(src_color_field.get_texture()));
-
-            gl.bind_buffer(GL::ARRAY_BUFFER, Some(&quad_pass.vertex_buffer));
-            gl.vertex_attrib_pointer_with_i32(0, 3, GL::FLOAT, false, 0, 0);
-            gl.enable_vertex_attrib_array(0); 
-            
-            gl.bind_buffer(GL::ELEMENT_ARRAY_BUFFER, Some(&quad_pass.index_buffer));
-
-            gl.draw_elements_with_i32(GL::TRIANGLES, 6, GL::UNSIGNED_SHORT, 0);
-        }
+        // render texture to screen 
+        sim.render_to(None);
         
         request_animation_frame(f.borrow().as_ref().unwrap());
     });
@@ -0,0 +1,150 @@
+// Geometric multigrid for the pressure poisson equation. Each level halves the
+// grid of the one above it, rounding up; the residual is restricted by full
+// weighting, the correction prolongated bilinearly, and weighted jacobi is the
+// smoother. The obstacle mask is resampled onto each level, so a level sees
+// the solids that cover most of each of its cells.
+
+use crate::backend::{vertical_weight, DoubleField, SolverBackend};
+
+// stop coarsening once a side would drop below this many texels
+const COARSEST_SIZE: i32 = 8;
+// iterations used to solve the coarsest level outright
+const COARSEST_ITERATIONS: usize = 20;
+// damping for the jacobi smoother, 4/5 is optimal for the 5 point laplacian
+const SMOOTHING_WEIGHT: f32 = 0.8;
+
+struct Level<F> {
+    width:      i32,
+    height:     i32,
+    x:          DoubleField<F>,
+    b:          F,
+    residual:   F,
+    obstacles:  F,
+}
+
+pub struct Multigrid<F> {
+    // residual of the finest grid, the one the caller owns
+    residual:   F,
+    levels:     Vec<Level<F>>,
+}
+
+impl<F> Multigrid<F> {
+    pub fn new<B: SolverBackend<Field = F>>(backend: &mut B, width: i32, height: i32) -> Result<Multigrid<F>, B::Error> {
+        let residual = backend.create_field(width, height)?;
+
+        let mut levels = Vec::new();
+        let (mut w, mut h) = (width, height);
+        while w / 2 >= COARSEST_SIZE && h / 2 >= COARSEST_SIZE {
+            w = (w + 1) / 2;
+            h = (h + 1) / 2;
+
+            levels.push(Level {
+                width:      w,
+                height:     h,
+                x:          DoubleField::new(backend.create_field(w, h)?, backend.create_field(w, h)?),
+                b:          backend.create_field(w, h)?,
+                residual:   backend.create_field(w, h)?,
+                obstacles:  backend.create_field(w, h)?,
+            });
+        }
+
+        Ok(Multigrid { residual, levels })
+    }
+
+    pub fn delete<B: SolverBackend<Field = F>>(self, backend: &mut B) {
+        backend.delete_field(self.residual);
+        for level in self.levels {
+            backend.delete_field(level.x.read);
+            backend.delete_field(level.x.write);
+            backend.delete_field(level.b);
+            backend.delete_field(level.residual);
+            backend.delete_field(level.obstacles);
+        }
+    }
+
+    /// Makes every level wrap around along the periodic axes, like the fine
+    /// grid's fields.
+    pub fn set_periodic<B: SolverBackend<Field = F>>(&mut self, backend: &mut B, periodic_x: bool, periodic_y: bool) {
+        backend.set_periodic(&mut self.residual, periodic_x, periodic_y);
+        for level in self.levels.iter_mut() {
+            for field in [&mut level.x.read, &mut level.x.write, &mut level.b, &mut level.residual, &mut level.obstacles] {
+                backend.set_periodic(field, periodic_x, periodic_y);
+            }
+        }
+    }
+
+    /// Runs `cycles` V-cycles on `laplacian(x) = b`, smoothing `smoothing`
+    /// times before and after each coarse grid correction. The result is
+    /// left in `x.read`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn solve<B: SolverBackend<Field = F>>(&mut self, backend: &mut B,
+        cycles:     usize,
+        smoothing:  usize,
+        delta_x:    f32,
+        delta_y:    f32,
+        aspect:     f32,
+        obstacles:  &F,
+        x:          &mut DoubleField<F>,
+        b:          &F,
+    ) {
+        // the mask only changes between solves
+        let mut fine = obstacles;
+        for level in self.levels.iter_mut() {
+            backend.resample(fine, &mut level.obstacles);
+            fine = &level.obstacles;
+        }
+
+        for _ in 0..cycles {
+            v_cycle(backend, &mut self.levels, &mut self.residual, smoothing, delta_x, delta_y, aspect, obstacles, x, b);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn smooth<B: SolverBackend>(backend: &mut B, iter: usize, delta_x: f32, delta_y: f32, aspect: f32, obstacles: &B::Field, x: &mut DoubleField<B::Field>, b: &B::Field) {
+    let alpha = -(delta_x * delta_x);
+    let r_beta = 1.0 / (2.0 + 2.0 * vertical_weight(delta_x, delta_y, aspect));
+    for _ in 0..iter {
+        backend.weighted_jacobi_iteration(delta_x, delta_y, aspect, alpha, r_beta, SMOOTHING_WEIGHT, obstacles, &x.read, b, &mut x.write);
+        x.swap();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn v_cycle<B: SolverBackend>(backend: &mut B,
+    levels:     &mut [Level<B::Field>],
+    residual:   &mut B::Field,
+    smoothing:  usize,
+    delta_x:    f32,
+    delta_y:    f32,
+    aspect:     f32,
+    obstacles:  &B::Field,
+    x:          &mut DoubleField<B::Field>,
+    b:          &B::Field,
+) {
+    let (coarse, coarser) = match levels.split_first_mut() {
+        Some(split) => split,
+        None => {
+            smooth(backend, COARSEST_ITERATIONS, delta_x, delta_y, aspect, obstacles, x, b);
+            return;
+        }
+    };
+
+    smooth(backend, smoothing, delta_x, delta_y, aspect, obstacles, x, b);
+
+    // restrict the residual and solve for the error on the coarser grid
+    backend.residual(delta_x, delta_y, aspect, obstacles, &x.read, b, residual);
+    backend.restrict(residual, &mut coarse.b);
+    backend.clear_field(&mut coarse.x.read);
+
+    // the domain keeps its aspect ratio, only the texels grow
+    let coarse_delta_x = 1.0 / coarse.width as f32;
+    let coarse_delta_y = 1.0 / coarse.height as f32;
+    v_cycle(backend, coarser, &mut coarse.residual, smoothing,
+        coarse_delta_x, coarse_delta_y, aspect, &coarse.obstacles, &mut coarse.x, &coarse.b);
+
+    backend.prolongate(&x.read, &coarse.x.read, &mut x.write);
+    x.swap();
+
+    smooth(backend, smoothing, delta_x, delta_y, aspect, obstacles, x, b);
+}
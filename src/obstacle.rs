@@ -0,0 +1,157 @@
+// Building obstacle masks for `FluidSimulation::set_obstacles`, from shapes or
+// from an image, and the rigid bodies the simulation moves through the fluid.
+// A mask is RGBA texture data with the red channel set to 1 in solid cells and
+// 0 elsewhere; green and blue hold the velocity of the solid, zero for these
+// static masks.
+
+use nalgebra::Vector2;
+
+/// A solid region. Positions are in uv coordinates and lengths in units of
+/// the domain width, like the splats, so a circle stays round on a domain
+/// that is not square.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shape {
+    Circle { center: Vector2<f32>, radius: f32 },
+    /// An axis aligned rectangle between two corners, in uv.
+    Rect { min: Vector2<f32>, max: Vector2<f32> },
+}
+
+impl Shape {
+    /// Whether the point at `uv` is inside the shape on a domain `aspect`
+    /// times as wide as it is tall.
+    pub fn contains(&self, aspect: f32, uv: Vector2<f32>) -> bool {
+        match *self {
+            Shape::Circle { center, radius } => {
+                let delta = (uv - center).component_mul(&Vector2::new(1.0, 1.0 / aspect));
+                delta.norm() <= radius
+            }
+            Shape::Rect { min, max } => {
+                min.x <= uv.x && uv.x <= max.x && min.y <= uv.y && uv.y <= max.y
+            }
+        }
+    }
+}
+
+fn make_mask<F: Fn(Vector2<f32>) -> bool>(width: i32, height: i32, solid: F) -> Vec<f32> {
+    let mut data = Vec::with_capacity((width * height * 4) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let uv = Vector2::new((x as f32 + 0.5) / width as f32, (y as f32 + 0.5) / height as f32);
+            let value = if solid(uv) { 1.0 } else { 0.0 };
+            data.extend_from_slice(&[value, 0.0, 0.0, 1.0]);
+        }
+    }
+    data
+}
+
+/// A mask with the cells whose centers lie in any of `shapes` solid.
+pub fn make_shapes_mask(width: i32, height: i32, shapes: &[Shape]) -> Vec<f32> {
+    let aspect = width as f32 / height as f32;
+    make_mask(width, height, |uv| shapes.iter().any(|shape| shape.contains(aspect, uv)))
+}
+
+/// A mask from an 8 bit RGBA image, `image_width` by `image_height` pixels
+/// stored top row first, stretched over the grid. Dark opaque pixels are
+/// solid.
+pub fn make_image_mask(width: i32, height: i32, image_width: usize, image_height: usize, pixels: &[u8]) -> Vec<f32> {
+    make_mask(width, height, |uv| {
+        let px = ((uv.x * image_width as f32) as usize).min(image_width - 1);
+        let py = (((1.0 - uv.y) * image_height as f32) as usize).min(image_height - 1);
+        let p = &pixels[(py * image_width + px) * 4..][..4];
+
+        let luminance = (0.299 * p[0] as f32 + 0.587 * p[1] as f32 + 0.114 * p[2] as f32) / 255.0;
+        p[3] >= 128 && luminance < 0.5
+    })
+}
+
+/// Most corners a `BodyShape::Polygon` may have.
+pub const MAX_POLYGON_VERTICES: usize = 16;
+
+/// The outline of a rigid body around its position, before rotation, in
+/// units of the domain width.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BodyShape {
+    Circle { radius: f32 },
+    /// A rectangle `2 * half_size` across.
+    Rect { half_size: Vector2<f32> },
+    /// A simple polygon with up to `MAX_POLYGON_VERTICES` corners, in either
+    /// winding order.
+    Polygon { vertices: Vec<Vector2<f32>> },
+}
+
+/// A solid moving through the fluid. Its footprint is solid, and the fluid at
+/// its surface moves with it. The body itself is kinematic: the fluid does not
+/// push back, it goes wherever its velocities take it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RigidBody {
+    pub shape:      BodyShape,
+    /// Center of rotation, in uv coordinates.
+    pub position:   Vector2<f32>,
+    /// Counterclockwise rotation in radians.
+    pub angle:      f32,
+    /// Domain widths per second.
+    pub velocity:   Vector2<f32>,
+    /// Counterclockwise radians per second.
+    pub angular_velocity: f32,
+}
+
+impl RigidBody {
+    /// A body at rest at `position`.
+    pub fn new(shape: BodyShape, position: Vector2<f32>) -> RigidBody {
+        RigidBody {
+            shape,
+            position,
+            angle: 0.0,
+            velocity: Vector2::zeros(),
+            angular_velocity: 0.0,
+        }
+    }
+
+    /// Moves and turns the body by its velocities over `delta_t`, on a domain
+    /// `aspect` times as wide as it is tall.
+    pub fn advance(&mut self, aspect: f32, delta_t: f32) {
+        self.position += delta_t * self.velocity.component_mul(&Vector2::new(1.0, aspect));
+        self.angle += delta_t * self.angular_velocity;
+    }
+
+    // offset of `uv` from the position in domain widths, in the world frame
+    // and in the body's own
+    fn offsets(&self, aspect: f32, uv: Vector2<f32>) -> (Vector2<f32>, Vector2<f32>) {
+        let world = (uv - self.position).component_mul(&Vector2::new(1.0, 1.0 / aspect));
+        let (sin, cos) = self.angle.sin_cos();
+        let local = Vector2::new(cos * world.x + sin * world.y, -sin * world.x + cos * world.y);
+        (world, local)
+    }
+
+    /// Whether the point at `uv` is inside the body.
+    pub fn contains(&self, aspect: f32, uv: Vector2<f32>) -> bool {
+        let (_, local) = self.offsets(aspect, uv);
+        match self.shape {
+            BodyShape::Circle { radius } => local.norm() <= radius,
+            BodyShape::Rect { half_size } => local.x.abs() <= half_size.x && local.y.abs() <= half_size.y,
+            BodyShape::Polygon { ref vertices } => {
+                // even-odd rule, counting the edges a ray to the right crosses
+                let vertices = &vertices[..vertices.len().min(MAX_POLYGON_VERTICES)];
+                let mut inside = false;
+                let mut last = match vertices.last() {
+                    Some(last) => *last,
+                    None => return false,
+                };
+                for &vertex in vertices {
+                    if (vertex.y > local.y) != (last.y > local.y)
+                        && local.x < (last.x - vertex.x) * (local.y - vertex.y) / (last.y - vertex.y) + vertex.x {
+                        inside = !inside;
+                    }
+                    last = vertex;
+                }
+                inside
+            }
+        }
+    }
+
+    /// Velocity of the body at the point `uv`, in domain widths per second.
+    pub fn surface_velocity(&self, aspect: f32, uv: Vector2<f32>) -> Vector2<f32> {
+        let (world, _) = self.offsets(aspect, uv);
+        self.velocity + self.angular_velocity * Vector2::new(-world.y, world.x)
+    }
+}
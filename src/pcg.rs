@@ -0,0 +1,297 @@
+// Preconditioned conjugate gradient for the pressure poisson equation on the
+// CPU, with a modified incomplete cholesky (MIC(0)) preconditioner.
+//
+// The system is the one the jacobi and residual passes discretize: the 5 point
+// laplacian where samples past the walls clamp to the edge texel, i.e. the
+// walls are neumann boundaries, and so are the faces of the solid cells in the
+// obstacle mask. Along the axes where `b` wraps around (`Field::periodic`) the
+// last column or row couples to the first instead, and past an outflow edge
+// the pressure is held at zero, a dirichlet boundary. Internally it is solved as
+// `A p = -dx^2 b` with
+// `A` the (positive semidefinite) negated laplacian, in f64. On cells taller or
+// wider than they are long the vertical couplings of `A` are `wy = (dx/dy)^2`
+// instead of 1.
+
+use nalgebra::Vector4;
+
+use crate::backend::{vertical_weight, Boundaries, Edge};
+use crate::cpu_fluid::Field;
+
+// blend between incomplete cholesky (0) and modified incomplete cholesky (1)
+const MIC_TAU: f64 = 0.97;
+// fall back to the diagonal when a pivot shrinks below this fraction of it
+const MIC_SIGMA: f64 = 0.25;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PcgReport {
+    pub iterations: usize,
+    /// Max norm of `b - laplacian(x)` when the solve stopped.
+    pub residual:   f32,
+    pub converged:  bool,
+}
+
+struct Grid {
+    width:  usize,
+    height: usize,
+    // weight of the vertical neighbours
+    wy:     f64,
+    // cells of the obstacle mask, which take no part in the solve
+    solid:  Vec<bool>,
+    // whether the last column and row couple to the first
+    periodic_x: bool,
+    periodic_y: bool,
+    // whether the pressure past the left, right, bottom and top edges is zero
+    outflow:    [bool; 4],
+}
+
+impl Grid {
+    fn fluid(&self, i: usize, j: usize) -> bool {
+        !self.solid[j * self.width + i]
+    }
+
+    // coefficient of A between (i, j) and (i + 1, j), zero unless both are fluid
+    fn plus_i(&self, i: usize, j: usize) -> f64 {
+        if i + 1 < self.width && self.fluid(i, j) && self.fluid(i + 1, j) { -1.0 } else { 0.0 }
+    }
+
+    // coefficient of A between (i, j) and (i, j + 1)
+    fn plus_j(&self, i: usize, j: usize) -> f64 {
+        if j + 1 < self.height && self.fluid(i, j) && self.fluid(i, j + 1) { -self.wy } else { 0.0 }
+    }
+
+    // coefficient of A between the last and first cells of row j, across a
+    // periodic edge
+    fn wrap_i(&self, j: usize) -> f64 {
+        let last = self.width - 1;
+        if self.periodic_x && last > 0 && self.fluid(last, j) && self.fluid(0, j) { -1.0 } else { 0.0 }
+    }
+
+    // coefficient of A between the last and first cells of column i
+    fn wrap_j(&self, i: usize) -> f64 {
+        let last = self.height - 1;
+        if self.periodic_y && last > 0 && self.fluid(i, last) && self.fluid(i, 0) { -self.wy } else { 0.0 }
+    }
+
+    // coefficient of A between (i, j) and the zero pressure past an outflow
+    // edge, which only adds to the diagonal
+    fn outflow(&self, i: usize, j: usize) -> f64 {
+        if !self.fluid(i, j) {
+            return 0.0;
+        }
+        let [left, right, bottom, top] = self.outflow;
+        let mut n = 0.0;
+        if i == 0 && left { n += 1.0; }
+        if i + 1 == self.width && right { n += 1.0; }
+        if j == 0 && bottom { n += self.wy; }
+        if j + 1 == self.height && top { n += self.wy; }
+        n
+    }
+
+    // solid cells, and fluid cells walled in on every side, are left out by
+    // making their rows the identity
+    fn diagonal(&self, i: usize, j: usize) -> f64 {
+        let mut n = -self.plus_i(i, j) - self.plus_j(i, j) + self.outflow(i, j);
+        if i > 0 { n -= self.plus_i(i - 1, j); }
+        if j > 0 { n -= self.plus_j(i, j - 1); }
+        if i == 0 || i + 1 == self.width { n -= self.wrap_i(j); }
+        if j == 0 || j + 1 == self.height { n -= self.wrap_j(i); }
+        if n == 0.0 { 1.0 } else { n }
+    }
+
+    fn apply(&self, x: &[f64], dst: &mut [f64]) {
+        let w = self.width;
+        for j in 0..self.height {
+            for i in 0..w {
+                let k = j * w + i;
+                let mut sum = self.diagonal(i, j) * x[k];
+                if i > 0 { sum += self.plus_i(i - 1, j) * x[k - 1]; }
+                if i + 1 < w { sum += self.plus_i(i, j) * x[k + 1]; }
+                if j > 0 { sum += self.plus_j(i, j - 1) * x[k - w]; }
+                if j + 1 < self.height { sum += self.plus_j(i, j) * x[k + w]; }
+
+                // across the periodic edges
+                let (last_i, last_j) = (w - 1, self.height - 1);
+                if i == 0 { sum += self.wrap_i(j) * x[k + last_i]; }
+                if i == last_i { sum += self.wrap_i(j) * x[k - last_i]; }
+                if j == 0 { sum += self.wrap_j(i) * x[k + last_j * w]; }
+                if j == last_j { sum += self.wrap_j(i) * x[k - last_j * w]; }
+                dst[k] = sum;
+            }
+        }
+    }
+
+    // the factorization leaves out the couplings across periodic edges, which
+    // still gives a symmetric positive definite preconditioner
+    fn mic_preconditioner(&self) -> Vec<f64> {
+        let w = self.width;
+        let mut precon = vec![0.0; w * self.height];
+
+        for j in 0..self.height {
+            for i in 0..w {
+                let k = j * w + i;
+                let diag = self.diagonal(i, j);
+
+                let mut e = diag;
+                if i > 0 {
+                    let p = precon[k - 1];
+                    let (a, b) = (self.plus_i(i - 1, j), self.plus_j(i - 1, j));
+                    e -= a * a * p * p + MIC_TAU * a * b * p * p;
+                }
+                if j > 0 {
+                    let p = precon[k - w];
+                    let (a, b) = (self.plus_j(i, j - 1), self.plus_i(i, j - 1));
+                    e -= a * a * p * p + MIC_TAU * a * b * p * p;
+                }
+
+                if e < MIC_SIGMA * diag {
+                    e = diag;
+                }
+                precon[k] = 1.0 / e.sqrt();
+            }
+        }
+
+        precon
+    }
+
+    fn apply_preconditioner(&self, precon: &[f64], r: &[f64], q: &mut [f64], z: &mut [f64]) {
+        let w = self.width;
+
+        // solve L q = r
+        for j in 0..self.height {
+            for i in 0..w {
+                let k = j * w + i;
+                let mut t = r[k];
+                if i > 0 { t -= self.plus_i(i - 1, j) * precon[k - 1] * q[k - 1]; }
+                if j > 0 { t -= self.plus_j(i, j - 1) * precon[k - w] * q[k - w]; }
+                q[k] = t * precon[k];
+            }
+        }
+
+        // solve L^T z = q
+        for j in (0..self.height).rev() {
+            for i in (0..w).rev() {
+                let k = j * w + i;
+                let mut t = q[k];
+                if i + 1 < w { t -= self.plus_i(i, j) * precon[k] * z[k + 1]; }
+                if j + 1 < self.height { t -= self.plus_j(i, j) * precon[k] * z[k + w]; }
+                z[k] = t * precon[k];
+            }
+        }
+    }
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+fn max_abs(a: &[f64]) -> f64 {
+    a.iter().fold(0.0, |m, v| m.max(v.abs()))
+}
+
+/// Solves `laplacian(x) = b` starting from the current contents of `x`, until
+/// the max norm of the residual drops below `tolerance` or `max_iterations`
+/// is reached. The pressure is read from and written to the red channel.
+///
+/// The outflow edges of `boundaries` hold the pressure past them at zero; the
+/// others are walls, or wrap around where `b` does. With walls all round the
+/// system only has a solution when `b` sums to zero, so the mean of `b` over
+/// the fluid cells is removed first, the same as ignoring the part of the
+/// divergence no pressure field can cancel. An outflow edge lets that part
+/// leave, so then `b` is solved for as it is. Solid cells of `obstacles` are
+/// left at zero pressure.
+#[allow(clippy::too_many_arguments)]
+pub fn solve_pressure(
+    delta_x:        f32,
+    delta_y:        f32,
+    aspect:         f32,
+    tolerance:      f32,
+    max_iterations: usize,
+    boundaries:     &Boundaries,
+    obstacles:      &Field,
+    x:              &mut Field,
+    b:              &Field,
+) -> PcgReport {
+    let grid = Grid {
+        width:  x.width() as usize,
+        height: x.height() as usize,
+        wy:     vertical_weight(delta_x, delta_y, aspect) as f64,
+        solid:  obstacles.data().chunks(4).map(|t| t[0] > 0.5).collect(),
+        periodic_x: b.periodic().0,
+        periodic_y: b.periodic().1,
+        outflow:    [boundaries.left, boundaries.right, boundaries.bottom, boundaries.top].map(|edge| edge == Edge::Outflow),
+    };
+    let n = grid.width * grid.height;
+
+    // scale between the residual of A and that of the laplacian
+    let scale = (delta_x as f64) * (delta_x as f64);
+
+    let mut rhs: Vec<f64> = b.data().chunks(4).map(|t| -(t[0] as f64) * scale).collect();
+    let fluid = grid.solid.iter().filter(|&&s| !s).count().max(1);
+    let mean = if grid.outflow.contains(&true) {
+        0.0
+    } else {
+        rhs.iter().zip(&grid.solid).filter(|(_, &s)| !s).map(|(v, _)| v).sum::<f64>() / fluid as f64
+    };
+    for (v, &solid) in rhs.iter_mut().zip(&grid.solid) {
+        *v = if solid { 0.0 } else { *v - mean };
+    }
+
+    let mut p: Vec<f64> = x.data().chunks(4).zip(&grid.solid)
+        .map(|(t, &solid)| if solid { 0.0 } else { t[0] as f64 })
+        .collect();
+    let mut r = vec![0.0; n];
+    grid.apply(&p, &mut r);
+    for (r, rhs) in r.iter_mut().zip(&rhs) {
+        *r = rhs - *r;
+    }
+
+    let tolerance = tolerance as f64 * scale;
+    let mut report = PcgReport { iterations: 0, residual: 0.0, converged: false };
+
+    let precon = grid.mic_preconditioner();
+    let mut q = vec![0.0; n];
+    let mut z = vec![0.0; n];
+    let mut s = vec![0.0; n];
+
+    if max_abs(&r) > tolerance {
+        grid.apply_preconditioner(&precon, &r, &mut q, &mut z);
+        s.copy_from_slice(&z);
+        let mut sigma = dot(&z, &r);
+
+        while report.iterations < max_iterations {
+            report.iterations += 1;
+
+            grid.apply(&s, &mut z);
+            let alpha = sigma / dot(&z, &s);
+            for k in 0..n {
+                p[k] += alpha * s[k];
+                r[k] -= alpha * z[k];
+            }
+
+            if max_abs(&r) <= tolerance {
+                break;
+            }
+
+            grid.apply_preconditioner(&precon, &r, &mut q, &mut z);
+            let sigma_new = dot(&z, &r);
+            let beta = sigma_new / sigma;
+            for k in 0..n {
+                s[k] = z[k] + beta * s[k];
+            }
+            sigma = sigma_new;
+        }
+    }
+
+    let residual = max_abs(&r);
+    report.residual = (residual / scale) as f32;
+    report.converged = residual <= tolerance;
+
+    let mut k = 0;
+    x.shade(|_| {
+        let value = Vector4::new(p[k] as f32, 0.0, 0.0, 1.0);
+        k += 1;
+        value
+    });
+
+    report
+}
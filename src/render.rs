@@ -34,15 +34,15 @@ impl RenderPass<'_> {
         indices:        &[u16],
     ) -> Result<RenderPass<'a>, JsValue>
     {
-        let program = shader::link_program(&gl, shaders[0], shaders[1])?;
+        let program = shader::link_program(gl, shaders[0], shaders[1])?;
         let mut uniform_map = HashMap::new();
 
         for uni in uniform_names {
             uniform_map.insert(uni, gl.get_uniform_location(&program, uni));
         }
 
-        let v_buffer = geometry::make_vertex_buffer(&gl, &vertices)?;
-        let i_buffer = geometry::make_index_buffer(&gl, &indices)?;
+        let v_buffer = geometry::make_vertex_buffer(gl, vertices)?;
+        let i_buffer = geometry::make_index_buffer(gl, indices)?;
 
         let a_loc = gl.get_attrib_location(&program, attrib_name);
 
@@ -1,6 +1,11 @@
+#![allow(clippy::too_many_arguments)]
+
 use web_sys::WebGlRenderingContext as GL;
 use nalgebra::{Vector2, Vector3};
 
+use crate::backend::{Boundaries, BoundaryField, GridLayout, Integrator, Slip};
+use crate::brush::Brush;
+use crate::obstacle::{BodyShape, RigidBody, MAX_POLYGON_VERTICES};
 use crate::render; 
 use crate::texture;
 
@@ -9,20 +14,36 @@ use std::rc::Rc;
 pub fn advection(gl: &GL,
     advect_pass:        &render::RenderPass,
     delta_x:            f32,
+    delta_y:            f32,
+    aspect:             f32,
     delta_t:            f32,
+    dissipation:        f32,
+    integrator:         Integrator,
+    layout:             GridLayout,
+    src_layout:         GridLayout,
+    obstacles:          &texture::Framebuffer,
     src_color_field:    Rc<texture::Framebuffer>,
     vector_field:       &texture::Framebuffer,
     dst_color_field:    Rc<texture::Framebuffer>,
 ) ->  (Rc<texture::Framebuffer>, Rc<texture::Framebuffer>) {
-    dst_color_field.bind(&gl);
-    render::clear_framebuffer(&gl);
+    dst_color_field.bind(gl);
+    render::clear_framebuffer(gl);
 
-    advect_pass.use_program(&gl);
+    advect_pass.use_program(gl);
+    set_periodic(gl, advect_pass, &src_color_field);
 
     gl.uniform1f(advect_pass.uniforms["delta_x"].as_ref(), delta_x); 
+    gl.uniform1f(advect_pass.uniforms["delta_y"].as_ref(), delta_y);
+    gl.uniform1f(advect_pass.uniforms["aspect"].as_ref(), aspect);
     gl.uniform1f(advect_pass.uniforms["delta_t"].as_ref(), delta_t); 
+    gl.uniform1f(advect_pass.uniforms["dissipation"].as_ref(), dissipation);
+    gl.uniform1i(advect_pass.uniforms["integrator"].as_ref(), integrator as i32);
+    gl.uniform1i(advect_pass.uniforms["staggered"].as_ref(), layout as i32);
+    gl.uniform1i(advect_pass.uniforms["staggered_src"].as_ref(), src_layout as i32);
+    gl.uniform2f(advect_pass.uniforms["src_texel"].as_ref(), 1.0 / src_color_field.width() as f32, 1.0 / src_color_field.height() as f32);
     gl.uniform1i(advect_pass.uniforms["color_field_texture"].as_ref(), 0);
     gl.uniform1i(advect_pass.uniforms["vec_field_texture"].as_ref(), 1);
+    gl.uniform1i(advect_pass.uniforms["obstacles"].as_ref(), 2);
 
     gl.active_texture(GL::TEXTURE0);
     gl.bind_texture(GL::TEXTURE_2D, Some(src_color_field.get_texture()));
@@ -30,6 +51,9 @@ pub fn advection(gl: &GL,
     gl.active_texture(GL::TEXTURE1);
     gl.bind_texture(GL::TEXTURE_2D, Some(vector_field.get_texture()));
 
+    gl.active_texture(GL::TEXTURE2);
+    gl.bind_texture(GL::TEXTURE_2D, Some(obstacles.get_texture()));
+
     gl.bind_buffer(GL::ARRAY_BUFFER, Some(&advect_pass.vertex_buffer));
     gl.vertex_attrib_pointer_with_i32(0, 3, GL::FLOAT, false, 0, 0);
     gl.enable_vertex_attrib_array(0); 
@@ -37,7 +61,72 @@ pub fn advection(gl: &GL,
     gl.bind_buffer(GL::ELEMENT_ARRAY_BUFFER, Some(&advect_pass.index_buffer));
 
     gl.draw_elements_with_i32(GL::TRIANGLES, 6, GL::UNSIGNED_SHORT, 0);
-    dst_color_field.unbind(&gl);
+    dst_color_field.unbind(gl);
+
+    (dst_color_field, src_color_field)
+}
+
+pub fn maccormack(gl: &GL,
+    maccormack_pass:    &render::RenderPass,
+    delta_x:            f32,
+    delta_y:            f32,
+    aspect:             f32,
+    delta_t:            f32,
+    dissipation:        f32,
+    integrator:         Integrator,
+    layout:             GridLayout,
+    src_layout:         GridLayout,
+    obstacles:          &texture::Framebuffer,
+    src_color_field:    Rc<texture::Framebuffer>,
+    vector_field:       &texture::Framebuffer,
+    forward_field:      &texture::Framebuffer,
+    backward_field:     &texture::Framebuffer,
+    dst_color_field:    Rc<texture::Framebuffer>,
+) ->  (Rc<texture::Framebuffer>, Rc<texture::Framebuffer>) {
+    dst_color_field.bind(gl);
+    render::clear_framebuffer(gl);
+
+    maccormack_pass.use_program(gl);
+    set_periodic(gl, maccormack_pass, &src_color_field);
+
+    gl.uniform1f(maccormack_pass.uniforms["delta_x"].as_ref(), delta_x);
+    gl.uniform1f(maccormack_pass.uniforms["delta_y"].as_ref(), delta_y);
+    gl.uniform1f(maccormack_pass.uniforms["aspect"].as_ref(), aspect);
+    gl.uniform1f(maccormack_pass.uniforms["delta_t"].as_ref(), delta_t);
+    gl.uniform1f(maccormack_pass.uniforms["dissipation"].as_ref(), dissipation);
+    gl.uniform1i(maccormack_pass.uniforms["integrator"].as_ref(), integrator as i32);
+    gl.uniform1i(maccormack_pass.uniforms["staggered"].as_ref(), layout as i32);
+    gl.uniform1i(maccormack_pass.uniforms["staggered_src"].as_ref(), src_layout as i32);
+    gl.uniform2f(maccormack_pass.uniforms["src_texel"].as_ref(), 1.0 / src_color_field.width() as f32, 1.0 / src_color_field.height() as f32);
+    gl.uniform1i(maccormack_pass.uniforms["color_field_texture"].as_ref(), 0);
+    gl.uniform1i(maccormack_pass.uniforms["vec_field_texture"].as_ref(), 1);
+    gl.uniform1i(maccormack_pass.uniforms["forward_texture"].as_ref(), 2);
+    gl.uniform1i(maccormack_pass.uniforms["backward_texture"].as_ref(), 3);
+    gl.uniform1i(maccormack_pass.uniforms["obstacles"].as_ref(), 4);
+
+    gl.active_texture(GL::TEXTURE0);
+    gl.bind_texture(GL::TEXTURE_2D, Some(src_color_field.get_texture()));
+
+    gl.active_texture(GL::TEXTURE1);
+    gl.bind_texture(GL::TEXTURE_2D, Some(vector_field.get_texture()));
+
+    gl.active_texture(GL::TEXTURE2);
+    gl.bind_texture(GL::TEXTURE_2D, Some(forward_field.get_texture()));
+
+    gl.active_texture(GL::TEXTURE3);
+    gl.bind_texture(GL::TEXTURE_2D, Some(backward_field.get_texture()));
+
+    gl.active_texture(GL::TEXTURE4);
+    gl.bind_texture(GL::TEXTURE_2D, Some(obstacles.get_texture()));
+
+    gl.bind_buffer(GL::ARRAY_BUFFER, Some(&maccormack_pass.vertex_buffer));
+    gl.vertex_attrib_pointer_with_i32(0, 3, GL::FLOAT, false, 0, 0);
+    gl.enable_vertex_attrib_array(0); 
+    
+    gl.bind_buffer(GL::ELEMENT_ARRAY_BUFFER, Some(&maccormack_pass.index_buffer));
+
+    gl.draw_elements_with_i32(GL::TRIANGLES, 6, GL::UNSIGNED_SHORT, 0);
+    dst_color_field.unbind(gl);
 
     (dst_color_field, src_color_field)
 }
@@ -46,8 +135,11 @@ pub fn jacobi_method(gl: &GL,
     jacobi_pass:    &render::RenderPass,
     iter:           usize,
     delta_x:        f32, 
+    delta_y:        f32,
+    aspect:         f32,
     alpha:          f32, 
     r_beta:         f32, 
+    obstacles:      &texture::Framebuffer,
     x:              Rc<texture::Framebuffer>, 
     b:              &texture::Framebuffer,
     dst:            Rc<texture::Framebuffer>,
@@ -58,9 +150,9 @@ pub fn jacobi_method(gl: &GL,
         let j_source = bufs[k % 2];
         let j_dst = bufs[(k + 1) % 2];
 
-        j_dst.bind(&gl);
-        jacobi_iteration(&gl, &jacobi_pass, delta_x, alpha, r_beta, &j_source, &b);            
-        j_dst.unbind(&gl);
+        j_dst.bind(gl);
+        jacobi_iteration(gl, jacobi_pass, delta_x, delta_y, aspect, alpha, r_beta, obstacles, j_source, b);            
+        j_dst.unbind(gl);
     }
     
     // lazy code: essentially we do jacobi `iter-1` or `iter` iterations
@@ -70,21 +162,92 @@ pub fn jacobi_method(gl: &GL,
 pub fn jacobi_iteration(gl: &GL, 
     jacobi_pass:    &render::RenderPass,
     delta_x:        f32, 
+    delta_y:        f32,
+    aspect:         f32,
     alpha:          f32, 
     r_beta:         f32, 
+    obstacles:      &texture::Framebuffer,
     x:              &texture::Framebuffer, 
     b:              &texture::Framebuffer,
 ) 
 {
-    render::clear_framebuffer(&gl);
-    jacobi_pass.use_program(&gl);
+    weighted_jacobi_iteration(gl, jacobi_pass, delta_x, delta_y, aspect, alpha, r_beta, 1.0, obstacles, x, b);
+}
+
+// jacobi iteration blended with the previous iterate by `omega`, as used by the
+// multigrid smoother
+pub fn weighted_jacobi_iteration(gl: &GL, 
+    jacobi_pass:    &render::RenderPass,
+    delta_x:        f32, 
+    delta_y:        f32,
+    aspect:         f32,
+    alpha:          f32, 
+    r_beta:         f32, 
+    omega:          f32,
+    obstacles:      &texture::Framebuffer,
+    x:              &texture::Framebuffer, 
+    b:              &texture::Framebuffer,
+) 
+{
+    relax(gl, jacobi_pass, delta_x, delta_y, aspect, alpha, r_beta, omega, -1, obstacles, x, b);
+}
+
+// one successive over-relaxation sweep in red-black order: the red texels are
+// updated into `scratch`, then the black ones back into `x` from the new reds
+pub fn red_black_iteration(gl: &GL,
+    jacobi_pass:    &render::RenderPass,
+    delta_x:        f32,
+    delta_y:        f32,
+    aspect:         f32,
+    alpha:          f32,
+    r_beta:         f32,
+    omega:          f32,
+    obstacles:      &texture::Framebuffer,
+    x:              Rc<texture::Framebuffer>,
+    b:              &texture::Framebuffer,
+    scratch:        Rc<texture::Framebuffer>,
+) -> (Rc<texture::Framebuffer>, Rc<texture::Framebuffer>)
+{
+    scratch.bind(gl);
+    relax(gl, jacobi_pass, delta_x, delta_y, aspect, alpha, r_beta, omega, 0, obstacles, &x, b);
+    scratch.unbind(gl);
+
+    x.bind(gl);
+    relax(gl, jacobi_pass, delta_x, delta_y, aspect, alpha, r_beta, omega, 1, obstacles, &scratch, b);
+    x.unbind(gl);
+
+    (x, scratch)
+}
+
+fn relax(gl: &GL,
+    jacobi_pass:    &render::RenderPass,
+    delta_x:        f32,
+    delta_y:        f32,
+    aspect:         f32,
+    alpha:          f32,
+    r_beta:         f32,
+    omega:          f32,
+    parity:         i32,
+    obstacles:      &texture::Framebuffer,
+    x:              &texture::Framebuffer,
+    b:              &texture::Framebuffer,
+)
+{
+    render::clear_framebuffer(gl);
+    jacobi_pass.use_program(gl);
+    set_periodic(gl, jacobi_pass, x);
 
     gl.uniform1f(jacobi_pass.uniforms["delta_x"].as_ref(), delta_x);
+    gl.uniform1f(jacobi_pass.uniforms["delta_y"].as_ref(), delta_y);
+    gl.uniform1f(jacobi_pass.uniforms["aspect"].as_ref(), aspect);
     gl.uniform1f(jacobi_pass.uniforms["alpha"].as_ref(), alpha);
     gl.uniform1f(jacobi_pass.uniforms["r_beta"].as_ref(), r_beta);
+    gl.uniform1f(jacobi_pass.uniforms["omega"].as_ref(), omega);
+    gl.uniform1i(jacobi_pass.uniforms["parity"].as_ref(), parity);
     
     gl.uniform1i(jacobi_pass.uniforms["x"].as_ref(), 0);
     gl.uniform1i(jacobi_pass.uniforms["b"].as_ref(), 1);
+    gl.uniform1i(jacobi_pass.uniforms["obstacles"].as_ref(), 2);
 
     gl.active_texture(GL::TEXTURE0);
     gl.bind_texture(GL::TEXTURE_2D, Some(x.get_texture()));
@@ -92,6 +255,9 @@ pub fn jacobi_iteration(gl: &GL,
     gl.active_texture(GL::TEXTURE1);
     gl.bind_texture(GL::TEXTURE_2D, Some(b.get_texture()));
 
+    gl.active_texture(GL::TEXTURE2);
+    gl.bind_texture(GL::TEXTURE_2D, Some(obstacles.get_texture()));
+
     gl.bind_buffer(GL::ARRAY_BUFFER, Some(&jacobi_pass.vertex_buffer));
     gl.vertex_attrib_pointer_with_i32(0, 3, GL::FLOAT, false, 0, 0);
     gl.enable_vertex_attrib_array(0); 
@@ -101,24 +267,235 @@ pub fn jacobi_iteration(gl: &GL,
     gl.draw_elements_with_i32(GL::TRIANGLES, 6, GL::UNSIGNED_SHORT, 0);
 }
 
+pub fn residual(gl: &GL,
+    residual_pass:  &render::RenderPass,
+    delta_x:        f32, 
+    delta_y:        f32,
+    aspect:         f32,
+    obstacles:      &texture::Framebuffer,
+    x:              &texture::Framebuffer, 
+    b:              &texture::Framebuffer,
+    dst:            Rc<texture::Framebuffer>,
+) -> Rc<texture::Framebuffer> {
+    dst.bind(gl);
+    render::clear_framebuffer(gl);
+    residual_pass.use_program(gl);
+    set_periodic(gl, residual_pass, x);
+
+    gl.uniform1f(residual_pass.uniforms["delta_x"].as_ref(), delta_x);
+    gl.uniform1f(residual_pass.uniforms["delta_y"].as_ref(), delta_y);
+    gl.uniform1f(residual_pass.uniforms["aspect"].as_ref(), aspect);
+
+    gl.uniform1i(residual_pass.uniforms["x"].as_ref(), 0);
+    gl.uniform1i(residual_pass.uniforms["b"].as_ref(), 1);
+    gl.uniform1i(residual_pass.uniforms["obstacles"].as_ref(), 2);
+
+    gl.active_texture(GL::TEXTURE0);
+    gl.bind_texture(GL::TEXTURE_2D, Some(x.get_texture()));
+
+    gl.active_texture(GL::TEXTURE1);
+    gl.bind_texture(GL::TEXTURE_2D, Some(b.get_texture()));
+
+    gl.active_texture(GL::TEXTURE2);
+    gl.bind_texture(GL::TEXTURE_2D, Some(obstacles.get_texture()));
+
+    gl.bind_buffer(GL::ARRAY_BUFFER, Some(&residual_pass.vertex_buffer));
+    gl.vertex_attrib_pointer_with_i32(0, 3, GL::FLOAT, false, 0, 0);
+    gl.enable_vertex_attrib_array(0); 
+    
+    gl.bind_buffer(GL::ELEMENT_ARRAY_BUFFER, Some(&residual_pass.index_buffer));
+
+    gl.draw_elements_with_i32(GL::TRIANGLES, 6, GL::UNSIGNED_SHORT, 0);
+    dst.unbind(gl);
+
+    dst
+}
+
+/// What the first pass of a reduction folds out of the field.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Reduction {
+    /// The red channel.
+    Red     = 1,
+    /// The length of the xy vector.
+    Speed   = 2,
+}
+
+// folds 2x2 blocks of `x` into `dst`, which is half its size rounded up;
+// `first` is `None` on the passes over partial sums
+pub fn reduce(gl: &GL,
+    reduce_pass:    &render::RenderPass,
+    first:          Option<Reduction>,
+    x:              &texture::Framebuffer,
+    dst:            Rc<texture::Framebuffer>,
+) -> Rc<texture::Framebuffer> {
+    dst.bind(gl);
+    render::clear_framebuffer(gl);
+    reduce_pass.use_program(gl);
+
+    gl.uniform2f(reduce_pass.uniforms["src_size"].as_ref(), x.width() as f32, x.height() as f32);
+    gl.uniform1i(reduce_pass.uniforms["first"].as_ref(), first.map_or(0, |r| r as i32));
+
+    gl.uniform1i(reduce_pass.uniforms["x"].as_ref(), 0);
+
+    gl.active_texture(GL::TEXTURE0);
+    gl.bind_texture(GL::TEXTURE_2D, Some(x.get_texture()));
+
+    gl.bind_buffer(GL::ARRAY_BUFFER, Some(&reduce_pass.vertex_buffer));
+    gl.vertex_attrib_pointer_with_i32(0, 3, GL::FLOAT, false, 0, 0);
+    gl.enable_vertex_attrib_array(0);
+
+    gl.bind_buffer(GL::ELEMENT_ARRAY_BUFFER, Some(&reduce_pass.index_buffer));
+
+    gl.draw_elements_with_i32(GL::TRIANGLES, 6, GL::UNSIGNED_SHORT, 0);
+    dst.unbind(gl);
+
+    dst
+}
+
+pub fn restrict(gl: &GL,
+    restrict_pass:  &render::RenderPass,
+    x:              &texture::Framebuffer,
+    dst:            Rc<texture::Framebuffer>,
+) -> Rc<texture::Framebuffer> {
+    dst.bind(gl);
+    render::clear_framebuffer(gl);
+    restrict_pass.use_program(gl);
+
+    gl.uniform1i(restrict_pass.uniforms["x"].as_ref(), 0);
+    gl.uniform2f(restrict_pass.uniforms["fine_texel"].as_ref(), 1.0 / x.width() as f32, 1.0 / x.height() as f32);
+    gl.uniform2f(restrict_pass.uniforms["coarse_texel"].as_ref(), 1.0 / dst.width() as f32, 1.0 / dst.height() as f32);
+
+    gl.active_texture(GL::TEXTURE0);
+    gl.bind_texture(GL::TEXTURE_2D, Some(x.get_texture()));
+
+    gl.bind_buffer(GL::ARRAY_BUFFER, Some(&restrict_pass.vertex_buffer));
+    gl.vertex_attrib_pointer_with_i32(0, 3, GL::FLOAT, false, 0, 0);
+    gl.enable_vertex_attrib_array(0);
+
+    gl.bind_buffer(GL::ELEMENT_ARRAY_BUFFER, Some(&restrict_pass.index_buffer));
+
+    gl.draw_elements_with_i32(GL::TRIANGLES, 6, GL::UNSIGNED_SHORT, 0);
+    dst.unbind(gl);
+
+    dst
+}
+
+pub fn pack(gl: &GL,
+    pack_pass:      &render::RenderPass,
+    x:              &texture::Framebuffer,
+    channel:        usize,
+    dst:            &texture::Framebuffer,
+) {
+    dst.bind(gl);
+    render::clear_framebuffer(gl);
+    pack_pass.use_program(gl);
+
+    let mut mask = [0.0; 4];
+    mask[channel] = 1.0;
+    gl.uniform1i(pack_pass.uniforms["x"].as_ref(), 0);
+    gl.uniform4f(pack_pass.uniforms["channel"].as_ref(), mask[0], mask[1], mask[2], mask[3]);
+
+    gl.active_texture(GL::TEXTURE0);
+    gl.bind_texture(GL::TEXTURE_2D, Some(x.get_texture()));
+
+    gl.bind_buffer(GL::ARRAY_BUFFER, Some(&pack_pass.vertex_buffer));
+    gl.vertex_attrib_pointer_with_i32(0, 3, GL::FLOAT, false, 0, 0);
+    gl.enable_vertex_attrib_array(0);
+
+    gl.bind_buffer(GL::ELEMENT_ARRAY_BUFFER, Some(&pack_pass.index_buffer));
+
+    gl.draw_elements_with_i32(GL::TRIANGLES, 6, GL::UNSIGNED_SHORT, 0);
+    dst.unbind(gl);
+}
+
+pub fn prolongate(gl: &GL,
+    prolong_pass:   &render::RenderPass,
+    x:              Rc<texture::Framebuffer>, 
+    e:              &texture::Framebuffer,
+    dst:            Rc<texture::Framebuffer>,
+) -> (Rc<texture::Framebuffer>, Rc<texture::Framebuffer>) {
+    dst.bind(gl);
+    render::clear_framebuffer(gl);
+    prolong_pass.use_program(gl);
+
+    gl.uniform1i(prolong_pass.uniforms["x"].as_ref(), 0);
+    gl.uniform1i(prolong_pass.uniforms["e"].as_ref(), 1);
+    gl.uniform2f(prolong_pass.uniforms["coarse_texel"].as_ref(), 1.0 / e.width() as f32, 1.0 / e.height() as f32);
+    set_periodic(gl, prolong_pass, e);
+
+    gl.active_texture(GL::TEXTURE0);
+    gl.bind_texture(GL::TEXTURE_2D, Some(x.get_texture()));
+
+    gl.active_texture(GL::TEXTURE1);
+    gl.bind_texture(GL::TEXTURE_2D, Some(e.get_texture()));
+
+    gl.bind_buffer(GL::ARRAY_BUFFER, Some(&prolong_pass.vertex_buffer));
+    gl.vertex_attrib_pointer_with_i32(0, 3, GL::FLOAT, false, 0, 0);
+    gl.enable_vertex_attrib_array(0); 
+    
+    gl.bind_buffer(GL::ELEMENT_ARRAY_BUFFER, Some(&prolong_pass.index_buffer));
+
+    gl.draw_elements_with_i32(GL::TRIANGLES, 6, GL::UNSIGNED_SHORT, 0);
+    dst.unbind(gl);
+
+    (dst, x)
+}
+
+// the `periodic` uniform, from the axes `field` wraps around along
+fn set_periodic(gl: &GL, pass: &render::RenderPass, field: &texture::Framebuffer) {
+    let (periodic_x, periodic_y) = field.periodic();
+    gl.uniform2f(pass.uniforms["periodic"].as_ref(), periodic_x as i32 as f32, periodic_y as i32 as f32);
+}
+
+// the `radius`, `falloff`, `strength` and `blend` uniforms of a splat
+fn set_brush(gl: &GL, pass: &render::RenderPass, brush: &Brush) {
+    gl.uniform1f(pass.uniforms["radius"].as_ref(), brush.radius);
+    gl.uniform1i(pass.uniforms["falloff"].as_ref(), brush.falloff.mode());
+    gl.uniform1f(pass.uniforms["strength"].as_ref(), brush.strength);
+    gl.uniform1i(pass.uniforms["blend"].as_ref(), brush.blend.mode());
+}
+
+// the `left`, `right`, `bottom` and `top` edge uniforms, and their inflows
+fn set_boundaries(gl: &GL, pass: &render::RenderPass, boundaries: &Boundaries) {
+    let edges = [("left", boundaries.left), ("right", boundaries.right), ("bottom", boundaries.bottom), ("top", boundaries.top)];
+    for (name, edge) in edges.iter() {
+        let inflow = edge.inflow();
+        gl.uniform1i(pass.uniforms[name].as_ref(), edge.mode());
+        gl.uniform2f(pass.uniforms[format!("{}_inflow", name).as_str()].as_ref(), inflow.x, inflow.y);
+    }
+}
 
 pub fn divergence(gl: &GL,
     divergence_pass:    &render::RenderPass,
     delta_x:            f32, 
+    delta_y:            f32,
+    aspect:             f32,
+    layout:             GridLayout,
+    boundaries:         &Boundaries,
+    obstacles:          &texture::Framebuffer,
     w:                  &texture::Framebuffer,
     dst:                Rc<texture::Framebuffer>,
 ) -> Rc<texture::Framebuffer> {
-    dst.bind(&gl);
-    render::clear_framebuffer(&gl);
-    divergence_pass.use_program(&gl);
+    dst.bind(gl);
+    render::clear_framebuffer(gl);
+    divergence_pass.use_program(gl);
+    set_periodic(gl, divergence_pass, w);
 
     gl.uniform1f(divergence_pass.uniforms["delta_x"].as_ref(), delta_x);
+    gl.uniform1f(divergence_pass.uniforms["delta_y"].as_ref(), delta_y);
+    gl.uniform1f(divergence_pass.uniforms["aspect"].as_ref(), aspect);
+    gl.uniform1i(divergence_pass.uniforms["staggered"].as_ref(), layout as i32);
+    set_boundaries(gl, divergence_pass, boundaries);
 
     gl.uniform1i(divergence_pass.uniforms["w"].as_ref(), 0);
+    gl.uniform1i(divergence_pass.uniforms["obstacles"].as_ref(), 1);
 
     gl.active_texture(GL::TEXTURE0);
     gl.bind_texture(GL::TEXTURE_2D, Some(w.get_texture()));
 
+    gl.active_texture(GL::TEXTURE1);
+    gl.bind_texture(GL::TEXTURE_2D, Some(obstacles.get_texture()));
+
     gl.bind_buffer(GL::ARRAY_BUFFER, Some(&divergence_pass.vertex_buffer));
     gl.vertex_attrib_pointer_with_i32(0, 3, GL::FLOAT, false, 0, 0);
     gl.enable_vertex_attrib_array(0); 
@@ -126,7 +503,7 @@ pub fn divergence(gl: &GL,
     gl.bind_buffer(GL::ELEMENT_ARRAY_BUFFER, Some(&divergence_pass.index_buffer));
 
     gl.draw_elements_with_i32(GL::TRIANGLES, 6, GL::UNSIGNED_SHORT, 0);
-    dst.unbind(&gl);
+    dst.unbind(gl);
 
     dst
 }
@@ -134,23 +511,34 @@ pub fn divergence(gl: &GL,
 pub fn subtract(gl: &GL,
     subtract_pass:  &render::RenderPass,
     delta_x:        f32, 
+    delta_y:        f32,
+    aspect:         f32,
+    layout:         GridLayout,
+    obstacles:      &texture::Framebuffer,
     p:              &texture::Framebuffer,
     w:              Rc<texture::Framebuffer>,
     dst:            Rc<texture::Framebuffer>, 
 ) -> (Rc<texture::Framebuffer>, Rc<texture::Framebuffer>) {
-    dst.bind(&gl);
-    render::clear_framebuffer(&gl);
-    subtract_pass.use_program(&gl);
+    dst.bind(gl);
+    render::clear_framebuffer(gl);
+    subtract_pass.use_program(gl);
+    set_periodic(gl, subtract_pass, &w);
 
     gl.uniform1f(subtract_pass.uniforms["delta_x"].as_ref(), delta_x);
+    gl.uniform1f(subtract_pass.uniforms["delta_y"].as_ref(), delta_y);
+    gl.uniform1f(subtract_pass.uniforms["aspect"].as_ref(), aspect);
+    gl.uniform1i(subtract_pass.uniforms["staggered"].as_ref(), layout as i32);
 
     gl.uniform1i(subtract_pass.uniforms["p"].as_ref(), 0);
     gl.uniform1i(subtract_pass.uniforms["w"].as_ref(), 1);
+    gl.uniform1i(subtract_pass.uniforms["obstacles"].as_ref(), 2);
 
     gl.active_texture(GL::TEXTURE0);
     gl.bind_texture(GL::TEXTURE_2D, Some(p.get_texture()));
     gl.active_texture(GL::TEXTURE1);
     gl.bind_texture(GL::TEXTURE_2D, Some(w.get_texture()));
+    gl.active_texture(GL::TEXTURE2);
+    gl.bind_texture(GL::TEXTURE_2D, Some(obstacles.get_texture()));
 
     gl.bind_buffer(GL::ARRAY_BUFFER, Some(&subtract_pass.vertex_buffer));
     gl.vertex_attrib_pointer_with_i32(0, 3, GL::FLOAT, false, 0, 0);
@@ -159,7 +547,7 @@ pub fn subtract(gl: &GL,
     gl.bind_buffer(GL::ELEMENT_ARRAY_BUFFER, Some(&subtract_pass.index_buffer));
 
     gl.draw_elements_with_i32(GL::TRIANGLES, 6, GL::UNSIGNED_SHORT, 0);
-    dst.unbind(&gl);
+    dst.unbind(gl);
 
     (dst, w)
 }
@@ -167,15 +555,22 @@ pub fn subtract(gl: &GL,
 pub fn boundary(gl: &GL,
     boundary_pass:  &render::RenderPass,
     delta_x:        f32, 
-    scale:          f32,
+    delta_y:        f32,
+    boundaries:     &Boundaries,
+    field:          BoundaryField,
+    layout:         GridLayout,
     x:              Rc<texture::Framebuffer>,
     dst:            Rc<texture::Framebuffer>,
 ) -> (Rc<texture::Framebuffer>, Rc<texture::Framebuffer>) {
-    dst.bind(&gl);
-    boundary_pass.use_program(&gl);
+    dst.bind(gl);
+    boundary_pass.use_program(gl);
+    set_periodic(gl, boundary_pass, &x);
 
     gl.uniform1f(boundary_pass.uniforms["delta_x"].as_ref(), delta_x);
-    gl.uniform1f(boundary_pass.uniforms["scale"].as_ref(), scale);
+    gl.uniform1f(boundary_pass.uniforms["delta_y"].as_ref(), delta_y);
+    gl.uniform1i(boundary_pass.uniforms["field"].as_ref(), field as i32);
+    set_boundaries(gl, boundary_pass, boundaries);
+    gl.uniform1i(boundary_pass.uniforms["staggered"].as_ref(), layout as i32);
 
     gl.uniform1i(boundary_pass.uniforms["x"].as_ref(), 0);
 
@@ -189,30 +584,126 @@ pub fn boundary(gl: &GL,
     gl.bind_buffer(GL::ELEMENT_ARRAY_BUFFER, Some(&boundary_pass.index_buffer));
 
     gl.draw_elements_with_i32(GL::TRIANGLES, 6, GL::UNSIGNED_SHORT, 0);
-    dst.unbind(&gl);
+    dst.unbind(gl);
 
     (dst, x)
 }
 
+pub fn obstacle_boundary(gl: &GL,
+    obstacle_pass:  &render::RenderPass,
+    delta_x:        f32,
+    delta_y:        f32,
+    slip:           Slip,
+    layout:         GridLayout,
+    obstacles:      &texture::Framebuffer,
+    x:              Rc<texture::Framebuffer>,
+    dst:            Rc<texture::Framebuffer>,
+) -> (Rc<texture::Framebuffer>, Rc<texture::Framebuffer>) {
+    dst.bind(gl);
+    obstacle_pass.use_program(gl);
+    set_periodic(gl, obstacle_pass, &x);
+
+    gl.uniform1f(obstacle_pass.uniforms["delta_x"].as_ref(), delta_x);
+    gl.uniform1f(obstacle_pass.uniforms["delta_y"].as_ref(), delta_y);
+    gl.uniform1i(obstacle_pass.uniforms["slip"].as_ref(), slip as i32);
+    gl.uniform1i(obstacle_pass.uniforms["staggered"].as_ref(), layout as i32);
+
+    gl.uniform1i(obstacle_pass.uniforms["x"].as_ref(), 0);
+    gl.uniform1i(obstacle_pass.uniforms["obstacles"].as_ref(), 1);
+
+    gl.active_texture(GL::TEXTURE0);
+    gl.bind_texture(GL::TEXTURE_2D, Some(x.get_texture()));
+
+    gl.active_texture(GL::TEXTURE1);
+    gl.bind_texture(GL::TEXTURE_2D, Some(obstacles.get_texture()));
+
+    gl.bind_buffer(GL::ARRAY_BUFFER, Some(&obstacle_pass.vertex_buffer));
+    gl.vertex_attrib_pointer_with_i32(0, 3, GL::FLOAT, false, 0, 0);
+    gl.enable_vertex_attrib_array(0);
+
+    gl.bind_buffer(GL::ELEMENT_ARRAY_BUFFER, Some(&obstacle_pass.index_buffer));
+
+    gl.draw_elements_with_i32(GL::TRIANGLES, 6, GL::UNSIGNED_SHORT, 0);
+    dst.unbind(gl);
+
+    (dst, x)
+}
+
+pub fn body(gl: &GL,
+    body_pass:  &render::RenderPass,
+    aspect:     f32,
+    body:       &RigidBody,
+    obstacles:  Rc<texture::Framebuffer>,
+    dst:        Rc<texture::Framebuffer>,
+) -> (Rc<texture::Framebuffer>, Rc<texture::Framebuffer>) {
+    dst.bind(gl);
+    body_pass.use_program(gl);
+
+    gl.uniform1f(body_pass.uniforms["aspect"].as_ref(), aspect);
+    gl.uniform2f(body_pass.uniforms["position"].as_ref(), body.position.x, body.position.y);
+    gl.uniform1f(body_pass.uniforms["angle"].as_ref(), body.angle);
+    gl.uniform2f(body_pass.uniforms["velocity"].as_ref(), body.velocity.x, body.velocity.y);
+    gl.uniform1f(body_pass.uniforms["angular_velocity"].as_ref(), body.angular_velocity);
+
+    match body.shape {
+        BodyShape::Circle { radius } => {
+            gl.uniform1i(body_pass.uniforms["shape"].as_ref(), 0);
+            gl.uniform1f(body_pass.uniforms["radius"].as_ref(), radius);
+        }
+        BodyShape::Rect { half_size } => {
+            gl.uniform1i(body_pass.uniforms["shape"].as_ref(), 1);
+            gl.uniform2f(body_pass.uniforms["half_size"].as_ref(), half_size.x, half_size.y);
+        }
+        BodyShape::Polygon { ref vertices } => {
+            let vertices = &vertices[..vertices.len().min(MAX_POLYGON_VERTICES)];
+            let data: Vec<f32> = vertices.iter().flat_map(|v| vec![v.x, v.y]).collect();
+            gl.uniform1i(body_pass.uniforms["shape"].as_ref(), 2);
+            gl.uniform1i(body_pass.uniforms["vertex_count"].as_ref(), vertices.len() as i32);
+            if !data.is_empty() {
+                gl.uniform2fv_with_f32_array(body_pass.uniforms["vertices"].as_ref(), &data);
+            }
+        }
+    }
+
+    gl.uniform1i(body_pass.uniforms["obstacles"].as_ref(), 0);
+
+    gl.active_texture(GL::TEXTURE0);
+    gl.bind_texture(GL::TEXTURE_2D, Some(obstacles.get_texture()));
+
+    gl.bind_buffer(GL::ARRAY_BUFFER, Some(&body_pass.vertex_buffer));
+    gl.vertex_attrib_pointer_with_i32(0, 3, GL::FLOAT, false, 0, 0);
+    gl.enable_vertex_attrib_array(0);
+
+    gl.bind_buffer(GL::ELEMENT_ARRAY_BUFFER, Some(&body_pass.index_buffer));
+
+    gl.draw_elements_with_i32(GL::TRIANGLES, 6, GL::UNSIGNED_SHORT, 0);
+    dst.unbind(gl);
+
+    (dst, obstacles)
+}
+
 pub fn force(gl: &GL,
     force_pass:  &render::RenderPass,
     delta_t:        f32, 
-    rho:            f32,
+    brush:          &Brush,
+    aspect:         f32,
     force:          &Vector2<f32>,
     impulse_pos:    &Vector2<f32>,
     velocity_field_texture:     Rc<texture::Framebuffer>,
     dst:                        Rc<texture::Framebuffer>,
 ) -> (Rc<texture::Framebuffer>, Rc<texture::Framebuffer>) 
 {
-    dst.bind(&gl);
-    force_pass.use_program(&gl);
+    dst.bind(gl);
+    force_pass.use_program(gl);
 
     gl.uniform1f(force_pass.uniforms["delta_t"].as_ref(), delta_t);
-    gl.uniform1f(force_pass.uniforms["rho"].as_ref(), rho);
+    set_brush(gl, force_pass, brush);
+    gl.uniform1f(force_pass.uniforms["aspect"].as_ref(), aspect);
     gl.uniform2f(force_pass.uniforms["force"].as_ref(), force.x, force.y);
     gl.uniform2f(force_pass.uniforms["impulse_pos"].as_ref(), impulse_pos.x, impulse_pos.y);
 
     gl.uniform1i(force_pass.uniforms["velocity_field_texture"].as_ref(), 0);
+    set_periodic(gl, force_pass, &velocity_field_texture);
 
     gl.active_texture(GL::TEXTURE0);
     gl.bind_texture(GL::TEXTURE_2D, Some(velocity_field_texture.get_texture()));
@@ -224,7 +715,7 @@ pub fn force(gl: &GL,
     gl.bind_buffer(GL::ELEMENT_ARRAY_BUFFER, Some(&force_pass.index_buffer));
 
     gl.draw_elements_with_i32(GL::TRIANGLES, 6, GL::UNSIGNED_SHORT, 0);
-    dst.unbind(&gl);
+    dst.unbind(gl);
 
     (dst, velocity_field_texture)
 }
@@ -232,22 +723,25 @@ pub fn force(gl: &GL,
 pub fn color(gl: &GL,
     force_pass:  &render::RenderPass,
     delta_t:        f32, 
-    rho:            f32,
+    brush:          &Brush,
+    aspect:         f32,
     color:          &Vector3<f32>,
     impulse_pos:    &Vector2<f32>,
     color_field_texture:     Rc<texture::Framebuffer>,
     dst:                        Rc<texture::Framebuffer>,
 ) -> (Rc<texture::Framebuffer>, Rc<texture::Framebuffer>) 
 {
-    dst.bind(&gl);
-    force_pass.use_program(&gl);
+    dst.bind(gl);
+    force_pass.use_program(gl);
 
     gl.uniform1f(force_pass.uniforms["delta_t"].as_ref(), delta_t);
-    gl.uniform1f(force_pass.uniforms["rho"].as_ref(), rho);
+    set_brush(gl, force_pass, brush);
+    gl.uniform1f(force_pass.uniforms["aspect"].as_ref(), aspect);
     gl.uniform3f(force_pass.uniforms["color"].as_ref(), color.x, color.y, color.z);
     gl.uniform2f(force_pass.uniforms["impulse_pos"].as_ref(), impulse_pos.x, impulse_pos.y);
 
     gl.uniform1i(force_pass.uniforms["color_field_texture"].as_ref(), 0);
+    set_periodic(gl, force_pass, &color_field_texture);
 
     gl.active_texture(GL::TEXTURE0);
     gl.bind_texture(GL::TEXTURE_2D, Some(color_field_texture.get_texture()));
@@ -259,26 +753,117 @@ pub fn color(gl: &GL,
     gl.bind_buffer(GL::ELEMENT_ARRAY_BUFFER, Some(&force_pass.index_buffer));
 
     gl.draw_elements_with_i32(GL::TRIANGLES, 6, GL::UNSIGNED_SHORT, 0);
-    dst.unbind(&gl);
+    dst.unbind(gl);
 
     (dst, color_field_texture)
 }
 
+pub fn buoyancy(gl: &GL,
+    buoyancy_pass:  &render::RenderPass,
+    delta_t:        f32,
+    delta_y:        f32,
+    ambient:        f32,
+    lift:           f32,
+    weight:         f32,
+    layout:         GridLayout,
+    smoke_field_texture:        &texture::Framebuffer,
+    velocity_field_texture:     Rc<texture::Framebuffer>,
+    dst:                        Rc<texture::Framebuffer>,
+) -> (Rc<texture::Framebuffer>, Rc<texture::Framebuffer>)
+{
+    dst.bind(gl);
+    buoyancy_pass.use_program(gl);
+
+    gl.uniform1f(buoyancy_pass.uniforms["delta_t"].as_ref(), delta_t);
+    gl.uniform1f(buoyancy_pass.uniforms["delta_y"].as_ref(), delta_y);
+    gl.uniform1f(buoyancy_pass.uniforms["ambient"].as_ref(), ambient);
+    gl.uniform1f(buoyancy_pass.uniforms["lift"].as_ref(), lift);
+    gl.uniform1f(buoyancy_pass.uniforms["weight"].as_ref(), weight);
+    gl.uniform1i(buoyancy_pass.uniforms["staggered"].as_ref(), layout as i32);
+
+    gl.uniform1i(buoyancy_pass.uniforms["velocity_field_texture"].as_ref(), 0);
+    gl.uniform1i(buoyancy_pass.uniforms["smoke_field_texture"].as_ref(), 1);
+    gl.uniform2f(buoyancy_pass.uniforms["smoke_texel"].as_ref(), 1.0 / smoke_field_texture.width() as f32, 1.0 / smoke_field_texture.height() as f32);
+    set_periodic(gl, buoyancy_pass, smoke_field_texture);
+
+    gl.active_texture(GL::TEXTURE0);
+    gl.bind_texture(GL::TEXTURE_2D, Some(velocity_field_texture.get_texture()));
+
+    gl.active_texture(GL::TEXTURE1);
+    gl.bind_texture(GL::TEXTURE_2D, Some(smoke_field_texture.get_texture()));
+
+    gl.bind_buffer(GL::ARRAY_BUFFER, Some(&buoyancy_pass.vertex_buffer));
+    gl.vertex_attrib_pointer_with_i32(0, 3, GL::FLOAT, false, 0, 0);
+    gl.enable_vertex_attrib_array(0);
+
+    gl.bind_buffer(GL::ELEMENT_ARRAY_BUFFER, Some(&buoyancy_pass.index_buffer));
+
+    gl.draw_elements_with_i32(GL::TRIANGLES, 6, GL::UNSIGNED_SHORT, 0);
+    dst.unbind(gl);
+
+    (dst, velocity_field_texture)
+}
+
+pub fn heat(gl: &GL,
+    heat_pass:      &render::RenderPass,
+    delta_t:        f32,
+    brush:          &Brush,
+    aspect:         f32,
+    heat:           &Vector2<f32>,
+    impulse_pos:    &Vector2<f32>,
+    smoke_field_texture:    Rc<texture::Framebuffer>,
+    dst:                    Rc<texture::Framebuffer>,
+) -> (Rc<texture::Framebuffer>, Rc<texture::Framebuffer>)
+{
+    dst.bind(gl);
+    heat_pass.use_program(gl);
+
+    gl.uniform1f(heat_pass.uniforms["delta_t"].as_ref(), delta_t);
+    set_brush(gl, heat_pass, brush);
+    gl.uniform1f(heat_pass.uniforms["aspect"].as_ref(), aspect);
+    gl.uniform2f(heat_pass.uniforms["heat"].as_ref(), heat.x, heat.y);
+    gl.uniform2f(heat_pass.uniforms["impulse_pos"].as_ref(), impulse_pos.x, impulse_pos.y);
+
+    gl.uniform1i(heat_pass.uniforms["smoke_field_texture"].as_ref(), 0);
+    set_periodic(gl, heat_pass, &smoke_field_texture);
+
+    gl.active_texture(GL::TEXTURE0);
+    gl.bind_texture(GL::TEXTURE_2D, Some(smoke_field_texture.get_texture()));
+
+    gl.bind_buffer(GL::ARRAY_BUFFER, Some(&heat_pass.vertex_buffer));
+    gl.vertex_attrib_pointer_with_i32(0, 3, GL::FLOAT, false, 0, 0);
+    gl.enable_vertex_attrib_array(0);
+
+    gl.bind_buffer(GL::ELEMENT_ARRAY_BUFFER, Some(&heat_pass.index_buffer));
+
+    gl.draw_elements_with_i32(GL::TRIANGLES, 6, GL::UNSIGNED_SHORT, 0);
+    dst.unbind(gl);
+
+    (dst, smoke_field_texture)
+}
+
 pub fn vorticity_confinement(gl: &GL,
     vorticity_pass: &render::RenderPass, 
     delta_t:    f32,
     delta_x:    f32, 
+    delta_y:    f32,
+    aspect:     f32,
     vorticity:  f32, 
+    layout:     GridLayout,
     v :         Rc<texture::Framebuffer>,
     dst:        Rc<texture::Framebuffer>,
 ) -> (Rc<texture::Framebuffer>, Rc<texture::Framebuffer>) 
 {
-    dst.bind(&gl);
-    vorticity_pass.use_program(&gl);
+    dst.bind(gl);
+    vorticity_pass.use_program(gl);
+    set_periodic(gl, vorticity_pass, &v);
 
     gl.uniform1f(vorticity_pass.uniforms["delta_t"].as_ref(), delta_t);
     gl.uniform1f(vorticity_pass.uniforms["delta_x"].as_ref(), delta_x);
+    gl.uniform1f(vorticity_pass.uniforms["delta_y"].as_ref(), delta_y);
+    gl.uniform1f(vorticity_pass.uniforms["aspect"].as_ref(), aspect);
     gl.uniform1f(vorticity_pass.uniforms["vorticity"].as_ref(), vorticity);
+    gl.uniform1i(vorticity_pass.uniforms["staggered"].as_ref(), layout as i32);
     gl.uniform1i(vorticity_pass.uniforms["v"].as_ref(), 0);
 
     gl.active_texture(GL::TEXTURE0);
@@ -293,7 +878,7 @@ pub fn vorticity_confinement(gl: &GL,
     gl.draw_elements_with_i32(GL::TRIANGLES, 6, GL::UNSIGNED_SHORT, 0);
 
 
-    dst.unbind(&gl);
+    dst.unbind(gl);
 
     (dst, v)
 }
\ No newline at end of file
@@ -1,16 +1,82 @@
-precision mediump float;
-
+// compiled after wrap.glsl, which has the precision, `wrap` and `sampleWrapped`
 uniform float delta_x;
+uniform float delta_y;
+uniform float aspect;
 uniform float delta_t;
+// fraction lost per second, as exponential decay
+uniform float dissipation;
+uniform int integrator;
 uniform sampler2D color_field_texture;
 uniform sampler2D vec_field_texture;
+uniform sampler2D obstacles;
+// 1 when u and v are stored on the left and bottom faces of each cell, for the
+// vector field and for the field being advected
+uniform int staggered;
+uniform int staggered_src;
+// texel size of the advected field, which may be finer or coarser than the
+// vector field's `delta_x` by `delta_y`
+uniform vec2 src_texel;
 varying vec2 UV;
 
+// cells whose obstacle mask is set are solid
+bool solid(vec2 coord) {
+    return sampleWrapped(obstacles, vec2(delta_x, delta_y), coord).x > 0.5;
+}
+
+// velocity at coord in uv per unit time, each component interpolated from
+// the faces it lives on
+vec2 velocity(vec2 coord) {
+    vec2 v = sampleWrapped(vec_field_texture, vec2(delta_x, delta_y), coord).xy;
+    if (staggered == 1) {
+        v = vec2(
+            sampleWrapped(vec_field_texture, vec2(delta_x, delta_y), coord + vec2(0.5 * delta_x, 0.0)).x,
+            sampleWrapped(vec_field_texture, vec2(delta_x, delta_y), coord + vec2(0.0, 0.5 * delta_y)).y);
+    }
+    // the domain is 1 wide and 1 / aspect tall
+    return v * vec2(1.0, aspect);
+}
+
+// 0: euler, 1: midpoint, 2: ralston's third order runge-kutta
+vec2 backtrace(vec2 coord) {
+    float h = 0.5 * delta_t;
+    vec2 k1 = velocity(coord);
+    if (integrator == 0) {
+        return coord - h * k1;
+    }
+
+    vec2 k2 = velocity(coord - 0.5 * h * k1);
+    if (integrator == 1) {
+        return coord - h * k2;
+    }
+
+    vec2 k3 = velocity(coord - 0.75 * h * k2);
+    return coord - h * ((2.0 * k1 + 3.0 * k2 + 4.0 * k3) / 9.0);
+}
+
 void main() {
-    vec2 u = texture2D(vec_field_texture, UV).xy;
-    vec2 pastCoord = UV - (0.5 * delta_t * u); 
+    // nothing is carried into a solid cell
+    if (solid(UV)) {
+        gl_FragColor = vec4(0.0, 0.0, 0.0, 1.0);
+        return;
+    }
+
+    float decay = exp(-dissipation * delta_t);
+
+    if (staggered_src == 0) {
+        vec2 pastCoord = backtrace(UV); 
+
+        vec4 color = sampleWrapped(color_field_texture, src_texel, pastCoord);
+        gl_FragColor = vec4(decay * color.xyz, color.w);
+        return;
+    }
+
+    // trace each component back from its own face
+    vec2 uOffset = vec2(0.5 * delta_x, 0.0);
+    vec2 vOffset = vec2(0.0, 0.5 * delta_y);
+    float u = sampleWrapped(color_field_texture, src_texel, backtrace(UV - uOffset) + uOffset).x;
+    float v = sampleWrapped(color_field_texture, src_texel, backtrace(UV - vOffset) + vOffset).y;
 
-    gl_FragColor = texture2D(color_field_texture, pastCoord); 
+    gl_FragColor = vec4(decay * vec2(u, v), 0.0, 1.0);
 }
 
 
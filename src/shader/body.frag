@@ -0,0 +1,63 @@
+precision mediump float;
+
+uniform float aspect;
+// 0: circle, 1: rectangle, 2: polygon
+uniform int shape;
+uniform vec2 position;
+uniform float angle;
+uniform float radius;
+uniform vec2 half_size;
+uniform vec2 vertices[16];
+uniform int vertex_count;
+uniform vec2 velocity;
+uniform float angular_velocity;
+uniform sampler2D obstacles;
+
+varying vec2 UV;
+
+// even-odd rule, counting the edges a ray to the right crosses
+bool insidePolygon(vec2 p) {
+    // uniform arrays can only be indexed by the loop counter
+    vec2 last = vec2(0.0);
+    for (int i = 0; i < 16; i++) {
+        if (i == vertex_count - 1) last = vertices[i];
+    }
+
+    bool inside = false;
+    for (int i = 0; i < 16; i++) {
+        if (i >= vertex_count) break;
+
+        vec2 vertex = vertices[i];
+        if ((vertex.y > p.y) != (last.y > p.y)
+            && p.x < (last.x - vertex.x) * (p.y - vertex.y) / (last.y - vertex.y) + vertex.x) {
+            inside = !inside;
+        }
+        last = vertex;
+    }
+    return inside;
+}
+
+void main() {
+    // offset from the body in domain widths, in the world frame and the body's
+    vec2 world = (UV - position) * vec2(1.0, 1.0 / aspect);
+    float s = sin(angle);
+    float c = cos(angle);
+    vec2 local = vec2(c * world.x + s * world.y, -s * world.x + c * world.y);
+
+    bool inside;
+    if (shape == 0) {
+        inside = length(local) <= radius;
+    } else if (shape == 1) {
+        inside = abs(local.x) <= half_size.x && abs(local.y) <= half_size.y;
+    } else {
+        inside = insidePolygon(local);
+    }
+
+    if (!inside) {
+        gl_FragColor = texture2D(obstacles, UV);
+        return;
+    }
+
+    vec2 surface = velocity + angular_velocity * vec2(-world.y, world.x);
+    gl_FragColor = vec4(1.0, surface, 1.0);
+}
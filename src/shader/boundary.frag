@@ -1,26 +1,98 @@
-precision mediump float;
-
+// compiled after wrap.glsl, which has the precision and `wrap`
 uniform float delta_x;
-uniform float scale;
+uniform float delta_y;
+// one of the `Edge` modes per edge: 0: no-slip, 1: free-slip, 2: periodic,
+// 3: inflow, 4: outflow
+uniform int left;
+uniform int right;
+uniform int bottom;
+uniform int top;
+uniform vec2 left_inflow;
+uniform vec2 right_inflow;
+uniform vec2 bottom_inflow;
+uniform vec2 top_inflow;
+// 0: velocity, 1: pressure
+uniform int field;
 uniform sampler2D x;
+uniform int staggered;
 
 varying vec2 UV;
 
+// a face on the left or bottom edge, given the face next to it inside
+float edgeFace(int mode, float inflow, float face, float inside) {
+    if (mode == 2) {
+        return face;
+    } else if (mode == 3) {
+        return inflow;
+    } else if (mode == 4) {
+        return inside;
+    }
+    // no flow through a wall
+    return 0.0;
+}
+
 void main() {
-    float eps = delta_x;
-    vec2 offset = vec2(0.0, 0.0);
-    if (UV.x - 0.0 < eps) {
+    bool periodicX = left == 2 || right == 2;
+    bool periodicY = bottom == 2 || top == 2;
+
+    if (staggered == 1) {
+        // the right and top edge faces are not stored
+        vec4 face = texture2D(x, UV);
+        if (UV.x < delta_x && !periodicX) {
+            face.x = edgeFace(left, left_inflow.x, face.x, texture2D(x, wrap(UV + vec2(delta_x, 0.0))).x);
+        }
+        if (UV.y < delta_y && !periodicY) {
+            face.y = edgeFace(bottom, bottom_inflow.y, face.y, texture2D(x, wrap(UV + vec2(0.0, delta_y))).y);
+        }
+        gl_FragColor = face;
+        return;
+    }
+
+    // the edge this texel is on, the offset to its neighbour inside, and the
+    // component of the velocity through the edge
+    int mode;
+    vec2 inflow;
+    vec2 offset;
+    vec2 normal;
+    if (UV.x - 0.0 < delta_x && !periodicX) {
+        mode = left;
+        inflow = left_inflow;
         offset = vec2(delta_x, 0.0);
-    } else if (1.0 - UV.x < eps) {
+        normal = vec2(-1.0, 1.0);
+    } else if (1.0 - UV.x < delta_x && !periodicX) {
+        mode = right;
+        inflow = right_inflow;
         offset = vec2(-delta_x, 0.0);
-    } else if (UV.y - 0.0 < eps) {
-        offset = vec2(0.0, delta_x);
-    } else if (1.0 - UV.y < eps) {
-        offset = vec2(0.0, -delta_x);
+        normal = vec2(-1.0, 1.0);
+    } else if (UV.y - 0.0 < delta_y && !periodicY) {
+        mode = bottom;
+        inflow = bottom_inflow;
+        offset = vec2(0.0, delta_y);
+        normal = vec2(1.0, -1.0);
+    } else if (1.0 - UV.y < delta_y && !periodicY) {
+        mode = top;
+        inflow = top_inflow;
+        offset = vec2(0.0, -delta_y);
+        normal = vec2(1.0, -1.0);
     } else {
         gl_FragColor = texture2D(x, UV);
         return;
     }
-    vec2 col = scale * texture2D(x, UV + offset).xy;
+
+    vec2 inside = texture2D(x, wrap(UV + offset)).xy;
+    vec2 col;
+    if (field == 1) {
+        // no pressure gradient through the edge, except where the fluid is free
+        // to leave
+        col = mode == 4 ? vec2(0.0) : inside;
+    } else if (mode == 0) {
+        col = -inside;
+    } else if (mode == 1) {
+        col = inside * normal;
+    } else if (mode == 3) {
+        col = inflow;
+    } else {
+        col = inside;
+    }
     gl_FragColor = vec4(col, 0.0, 1.0);
-}
\ No newline at end of file
+}
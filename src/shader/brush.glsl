@@ -0,0 +1,37 @@
+// The `Brush` shared by force.frag, dye.frag and heat.frag, which are compiled
+// after this prefix; wrap.glsl comes ahead of it with the precision.
+// `falloff` is 0: hard, 1: gaussian, 2: smoothstep and `blend` 0: replace,
+// 1: additive, 2: alpha-over
+uniform float delta_t;
+uniform float radius;
+uniform int falloff;
+uniform float strength;
+uniform int blend;
+
+// weight of the brush at `delta` domain widths from its center
+float brushWeight(vec2 delta) {
+    if (falloff == 0) {
+        return length(delta) < radius ? 1.0 : 0.0;
+    } else if (falloff == 1) {
+        return exp(-dot(delta, delta) / (radius * radius));
+    }
+    return 1.0 - smoothstep(0.0, radius, length(delta));
+}
+
+vec2 brushBlend(float weight, vec2 dst, vec2 value) {
+    if (blend == 0) {
+        return mix(dst, value, clamp(strength * weight, 0.0, 1.0));
+    } else if (blend == 1) {
+        return dst + delta_t * strength * weight * value;
+    }
+    return mix(dst, value, clamp(delta_t * strength * weight, 0.0, 1.0));
+}
+
+vec3 brushBlend(float weight, vec3 dst, vec3 value) {
+    if (blend == 0) {
+        return mix(dst, value, clamp(strength * weight, 0.0, 1.0));
+    } else if (blend == 1) {
+        return dst + delta_t * strength * weight * value;
+    }
+    return mix(dst, value, clamp(delta_t * strength * weight, 0.0, 1.0));
+}
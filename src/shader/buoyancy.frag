@@ -0,0 +1,28 @@
+// compiled after wrap.glsl, which has the precision and `sampleWrapped`
+uniform float delta_t;
+uniform float delta_y;
+uniform float ambient;
+uniform float lift;
+uniform float weight;
+uniform int staggered;
+uniform sampler2D velocity_field_texture;
+// temperature in red, smoke density in green
+uniform sampler2D smoke_field_texture;
+uniform vec2 smoke_texel;
+varying vec2 UV;
+
+void main() {
+    vec4 color = texture2D(velocity_field_texture, UV);
+
+    // on a staggered grid v is stored on the bottom face of the cell
+    vec2 coord = UV;
+    if (staggered == 1) {
+        coord -= vec2(0.0, 0.5 * delta_y);
+    }
+    vec2 smoke = sampleWrapped(smoke_field_texture, smoke_texel, coord).xy;
+
+    // hot air rises, smoke weighs it down
+    color.y += delta_t * (lift * (smoke.x - ambient) - weight * smoke.y);
+
+    gl_FragColor = color;
+}
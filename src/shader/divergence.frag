@@ -1,15 +1,95 @@
-precision mediump float;
-
+// compiled after wrap.glsl, which has the precision and `wrap`
 uniform float delta_x;
+uniform float delta_y;
+uniform float aspect;
+uniform int staggered;
+// the `Edge` modes of the edges, see boundary.frag
+uniform int left;
+uniform int right;
+uniform int bottom;
+uniform int top;
+uniform vec2 left_inflow;
+uniform vec2 right_inflow;
+uniform vec2 bottom_inflow;
+uniform vec2 top_inflow;
 uniform sampler2D w;
+uniform sampler2D obstacles;
 varying vec2 UV;
 
+// cells whose obstacle mask is set are solid
+bool solid(vec2 coord) {
+    return texture2D(obstacles, wrap(coord)).x > 0.5;
+}
+
+// velocity of the fluid moving with the solid at coord, zero where the mask is
+// at rest. The mask holds the surface's own velocity, and the advection passes
+// only move the fluid by 0.5 * delta_t of the field's.
+vec2 solidVelocity(vec2 coord) {
+    return texture2D(obstacles, wrap(coord)).yz / 0.5;
+}
+
+// flow through a face on an edge of the domain that is not periodic, given
+// the face across the cell from it
+float edgeFace(int mode, float inflow, float across) {
+    if (mode == 3) {
+        return inflow;
+    } else if (mode == 4) {
+        return across;
+    }
+    // no flow through a wall
+    return 0.0;
+}
+
 void main() {
-    vec2 wLeft  = texture2D(w, UV - vec2(delta_x, 0.0)).xy; 
-    vec2 wRight = texture2D(w, UV + vec2(delta_x, 0.0)).xy; 
-    vec2 wDown  = texture2D(w, UV - vec2(0.0, delta_x)).xy;
-    vec2 wUp    = texture2D(w, UV + vec2(0.0, delta_x)).xy;  
+    float hy = delta_y / aspect;
+
+    if (solid(UV)) {
+        gl_FragColor = vec4(0.0, 0.0, 0.0, 1.0);
+        return;
+    }
+
+    bool solidLeft  = solid(UV - vec2(delta_x, 0.0));
+    bool solidRight = solid(UV + vec2(delta_x, 0.0));
+    bool solidDown  = solid(UV - vec2(0.0, delta_y));
+    bool solidUp    = solid(UV + vec2(0.0, delta_y));
+
+    if (staggered == 1) {
+        // faces against a solid cell move with the solid, and faces on the
+        // edges of the domain follow the boundaries
+        float uLeft  = texture2D(w, UV).x;
+        float uRight = texture2D(w, wrap(UV + vec2(delta_x, 0.0))).x;
+        float vDown  = texture2D(w, UV).y;
+        float vUp    = texture2D(w, wrap(UV + vec2(0.0, delta_y))).y;
+        if (solidLeft)  uLeft  = solidVelocity(UV - vec2(delta_x, 0.0)).x;
+        if (solidRight) uRight = solidVelocity(UV + vec2(delta_x, 0.0)).x;
+        if (solidDown)  vDown  = solidVelocity(UV - vec2(0.0, delta_y)).y;
+        if (solidUp)    vUp    = solidVelocity(UV + vec2(0.0, delta_y)).y;
+
+        bool periodicX = left == 2 || right == 2;
+        bool periodicY = bottom == 2 || top == 2;
+        float u0 = uLeft;
+        float v0 = vDown;
+        if (UV.x < delta_x && !periodicX)       uLeft  = edgeFace(left, left_inflow.x, uRight);
+        if (UV.x + delta_x > 1.0 && !periodicX) uRight = edgeFace(right, right_inflow.x, u0);
+        if (UV.y < delta_y && !periodicY)       vDown  = edgeFace(bottom, bottom_inflow.y, vUp);
+        if (UV.y + delta_y > 1.0 && !periodicY) vUp    = edgeFace(top, top_inflow.y, v0);
+
+        gl_FragColor = vec4((uRight - uLeft) / delta_x + (vUp - vDown) / hy, 0.0, 0.0, 1.0);
+        return;
+    }
+
+    vec2 wLeft  = texture2D(w, wrap(UV - vec2(delta_x, 0.0))).xy; 
+    vec2 wRight = texture2D(w, wrap(UV + vec2(delta_x, 0.0))).xy; 
+    vec2 wDown  = texture2D(w, wrap(UV - vec2(0.0, delta_y))).xy;
+    vec2 wUp    = texture2D(w, wrap(UV + vec2(0.0, delta_y))).xy;  
+
+    // solid neighbours move with the solid
+    if (solidLeft)  wLeft  = solidVelocity(UV - vec2(delta_x, 0.0));
+    if (solidRight) wRight = solidVelocity(UV + vec2(delta_x, 0.0));
+    if (solidDown)  wDown  = solidVelocity(UV - vec2(0.0, delta_y));
+    if (solidUp)    wUp    = solidVelocity(UV + vec2(0.0, delta_y));
 
     float half_rdx = 1.0 / (2.0 * delta_x); 
-    gl_FragColor = vec4(half_rdx * ((wRight.x - wLeft.x) + (wUp.y - wDown.y)), 0.0, 0.0, 1.0);
+    float half_rdy = 1.0 / (2.0 * hy);
+    gl_FragColor = vec4(half_rdx * (wRight.x - wLeft.x) + half_rdy * (wUp.y - wDown.y), 0.0, 0.0, 1.0);
 }
\ No newline at end of file
@@ -1,7 +1,5 @@
-precision mediump float;
-
-uniform float delta_t;
-uniform float rho;
+// compiled after wrap.glsl and brush.glsl, like force.frag
+uniform float aspect;
 uniform vec3 color;
 uniform vec2 impulse_pos;
 uniform sampler2D color_field_texture;
@@ -10,11 +8,9 @@ varying vec2 UV;
 void main() {
     vec4 origColor = texture2D(color_field_texture, UV);
 
-    float eps = 0.025;
-    vec2 delta = UV - impulse_pos;
-    if (length(delta) < eps) {
-        origColor.xyz = color;
-    }
+    // same distance as in force.frag
+    vec2 delta = wrapDelta(UV - impulse_pos) * vec2(1.0, 1.0 / aspect);
+    origColor.xyz = brushBlend(brushWeight(delta), origColor.xyz, color);
 
     gl_FragColor = origColor;
-}
\ No newline at end of file
+}
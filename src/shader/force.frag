@@ -1,7 +1,6 @@
-precision mediump float;
-
-uniform float delta_t;
-uniform float rho;
+// compiled after wrap.glsl and brush.glsl, which have the precision,
+// `wrapDelta` and the brush uniforms
+uniform float aspect;
 uniform vec2 force;
 uniform vec2 impulse_pos;
 uniform sampler2D velocity_field_texture;
@@ -10,10 +9,10 @@ varying vec2 UV;
 void main() {
     vec4 color = texture2D(velocity_field_texture, UV);
 
-    vec2 delta = UV - impulse_pos;
-    float scale = delta_t * exp(-(pow(delta.x, 2.0) + pow(delta.y, 2.0))/rho);
-
-    color.xy += scale * force;
+    // distance in units of the domain width, which is 1 / aspect tall, the
+    // short way around the periodic edges
+    vec2 delta = wrapDelta(UV - impulse_pos) * vec2(1.0, 1.0 / aspect);
+    color.xy = brushBlend(brushWeight(delta), color.xy, force);
 
     gl_FragColor = color;
-}
\ No newline at end of file
+}
@@ -0,0 +1,17 @@
+// compiled after wrap.glsl and brush.glsl, like force.frag
+uniform float aspect;
+// temperature and smoke density added per second at the center
+uniform vec2 heat;
+uniform vec2 impulse_pos;
+uniform sampler2D smoke_field_texture;
+varying vec2 UV;
+
+void main() {
+    vec4 smoke = texture2D(smoke_field_texture, UV);
+
+    // same distance as in force.frag
+    vec2 delta = wrapDelta(UV - impulse_pos) * vec2(1.0, 1.0 / aspect);
+    smoke.xy = brushBlend(brushWeight(delta), smoke.xy, heat);
+
+    gl_FragColor = smoke;
+}
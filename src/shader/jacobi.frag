@@ -1,19 +1,48 @@
-precision mediump float;
-
+// compiled after wrap.glsl, which has the precision and `wrap`
 uniform float delta_x;
+uniform float delta_y;
+uniform float aspect;
 uniform float alpha;
 uniform float r_beta;
+uniform float omega;
+// 0 or 1 to only update the red or black texels, -1 to update all of them
+uniform int parity;
 uniform sampler2D x;
 uniform sampler2D b;
+uniform sampler2D obstacles;
 varying vec2 UV;
 
+// cells whose obstacle mask is set are solid
+bool solid(vec2 coord) {
+    return texture2D(obstacles, wrap(coord)).x > 0.5;
+}
+
 void main() {
-    vec2 xLeft  = texture2D(x, UV - vec2(delta_x, 0.0)).xy; 
-    vec2 xRight = texture2D(x, UV + vec2(delta_x, 0.0)).xy; 
-    vec2 xDown  = texture2D(x, UV - vec2(0.0, delta_x)).xy;
-    vec2 xUp    = texture2D(x, UV + vec2(0.0, delta_x)).xy;  
+    vec2 xCenter = texture2D(x, UV).xy;
+    bool skip = parity >= 0 && int(mod(floor(gl_FragCoord.x) + floor(gl_FragCoord.y), 2.0)) != parity;
+    if (skip || solid(UV)) {
+        gl_FragColor = vec4(xCenter, 0.0, 1.0);
+        return;
+    }
+
+    vec2 xLeft  = texture2D(x, wrap(UV - vec2(delta_x, 0.0))).xy; 
+    vec2 xRight = texture2D(x, wrap(UV + vec2(delta_x, 0.0))).xy; 
+    vec2 xDown  = texture2D(x, wrap(UV - vec2(0.0, delta_y))).xy;
+    vec2 xUp    = texture2D(x, wrap(UV + vec2(0.0, delta_y))).xy;  
+
+    // solid neighbours mirror the center, so no gradient crosses their faces
+    if (solid(UV - vec2(delta_x, 0.0))) xLeft  = xCenter;
+    if (solid(UV + vec2(delta_x, 0.0))) xRight = xCenter;
+    if (solid(UV - vec2(0.0, delta_y))) xDown  = xCenter;
+    if (solid(UV + vec2(0.0, delta_y))) xUp    = xCenter;
     
     vec2 bCenter = texture2D(b, UV).xy; 
 
-    gl_FragColor = vec4(r_beta * (xLeft + xRight + xUp + xDown + alpha*bCenter), 0.0, 1.0);
+    // cells are delta_x by delta_y / aspect, so the vertical neighbours are
+    // weighted by (hx / hy)^2
+    float hy = delta_y / aspect;
+    float wy = (delta_x * delta_x) / (hy * hy);
+
+    vec2 xNew = r_beta * (xLeft + xRight + wy * (xUp + xDown) + alpha*bCenter);
+    gl_FragColor = vec4(mix(xCenter, xNew, omega), 0.0, 1.0);
 }
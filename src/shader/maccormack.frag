@@ -0,0 +1,98 @@
+// compiled after wrap.glsl, which has the precision, `wrap` and `sampleWrapped`
+uniform float delta_x;
+uniform float delta_y;
+uniform float aspect;
+uniform float delta_t;
+// same as in advect.frag, applied after the correction
+uniform float dissipation;
+uniform int integrator;
+uniform sampler2D vec_field_texture;
+uniform sampler2D color_field_texture;
+uniform sampler2D forward_texture;
+uniform sampler2D backward_texture;
+uniform sampler2D obstacles;
+uniform int staggered;
+uniform int staggered_src;
+// texel size of the advected field, which may be finer or coarser than the
+// vector field's `delta_x` by `delta_y`
+uniform vec2 src_texel;
+varying vec2 UV;
+
+// same as in advect.frag
+bool solid(vec2 coord) {
+    return sampleWrapped(obstacles, vec2(delta_x, delta_y), coord).x > 0.5;
+}
+
+vec2 velocity(vec2 coord) {
+    vec2 v = sampleWrapped(vec_field_texture, vec2(delta_x, delta_y), coord).xy;
+    if (staggered == 1) {
+        v = vec2(
+            sampleWrapped(vec_field_texture, vec2(delta_x, delta_y), coord + vec2(0.5 * delta_x, 0.0)).x,
+            sampleWrapped(vec_field_texture, vec2(delta_x, delta_y), coord + vec2(0.0, 0.5 * delta_y)).y);
+    }
+    return v * vec2(1.0, aspect);
+}
+
+vec2 backtrace(vec2 coord) {
+    float h = 0.5 * delta_t;
+    vec2 k1 = velocity(coord);
+    if (integrator == 0) {
+        return coord - h * k1;
+    }
+
+    vec2 k2 = velocity(coord - 0.5 * h * k1);
+    if (integrator == 1) {
+        return coord - h * k2;
+    }
+
+    vec2 k3 = velocity(coord - 0.75 * h * k2);
+    return coord - h * ((2.0 * k1 + 3.0 * k2 + 4.0 * k3) / 9.0);
+}
+
+// range of the texels bilinear sampling at coord interpolates between
+void texel_range(vec2 coord, out vec4 lo, out vec4 hi) {
+    vec2 delta = src_texel;
+    vec2 texel = (floor(coord / delta - 0.5) + 0.5) * delta;
+    vec4 a = texture2D(color_field_texture, wrap(texel));
+    vec4 b = texture2D(color_field_texture, wrap(texel + vec2(delta.x, 0.0)));
+    vec4 c = texture2D(color_field_texture, wrap(texel + vec2(0.0, delta.y)));
+    vec4 d = texture2D(color_field_texture, wrap(texel + delta));
+
+    lo = min(min(a, b), min(c, d));
+    hi = max(max(a, b), max(c, d));
+}
+
+void main() {
+    if (solid(UV)) {
+        gl_FragColor = vec4(0.0, 0.0, 0.0, 1.0);
+        return;
+    }
+
+    // forward + half the error of the forward/backward round trip
+    vec4 forward = texture2D(forward_texture, UV);
+    vec4 backward = texture2D(backward_texture, UV);
+    vec4 corrected = forward + 0.5 * (texture2D(color_field_texture, UV) - backward);
+
+    // limit to the texels the semi-lagrangian step interpolated between
+    vec4 lo;
+    vec4 hi;
+    if (staggered_src == 0) {
+        texel_range(backtrace(UV), lo, hi);
+    } else {
+        vec2 uOffset = vec2(0.5 * delta_x, 0.0);
+        vec2 vOffset = vec2(0.0, 0.5 * delta_y);
+
+        vec4 uLo;
+        vec4 uHi;
+        vec4 vLo;
+        vec4 vHi;
+        texel_range(backtrace(UV - uOffset) + uOffset, uLo, uHi);
+        texel_range(backtrace(UV - vOffset) + vOffset, vLo, vHi);
+
+        lo = vec4(uLo.x, vLo.y, uLo.zw);
+        hi = vec4(uHi.x, vHi.y, uHi.zw);
+    }
+
+    vec4 color = clamp(corrected, lo, hi);
+    gl_FragColor = vec4(exp(-dissipation * delta_t) * color.xyz, color.w);
+}
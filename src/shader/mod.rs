@@ -3,17 +3,45 @@ use web_sys::{WebGlProgram, WebGlRenderingContext, WebGlShader};
 
 /* SHADERS */
 
-pub static STANDARD_VERTEX_SHADER: &'static str = include_str!("./standard.vert");
-pub static QUAD_FRAGMENT_SHADER: &'static str = include_str!("./quad.frag");
+pub static STANDARD_VERTEX_SHADER: &str = include_str!("./standard.vert");
+pub static QUAD_FRAGMENT_SHADER: &str = include_str!("./quad.frag");
 
-pub static ADVECT_FRAGMENT_SHADER: &'static str = include_str!("./advect.frag");
-pub static JACOBI_FRAGMENT_SHADER: &'static str = include_str!("./jacobi.frag");
-pub static DIVERGE_FRAGMENT_SHADER: &'static str = include_str!("./divergence.frag");
-pub static FORCE_FRAGMENT_SHADER:  &'static str = include_str!("./force.frag");
-pub static COLOR_FRAGMENT_SHADER:  &'static str = include_str!("./dye.frag");
-pub static SUB_FRAGMENT_SHADER:    &'static str = include_str!("./subtract.frag");
-pub static BOUND_FRAGMENT_SHADER:  &'static str = include_str!("./boundary.frag");
-pub static VORT_FRAGMENT_SHADER: &'static str = include_str!("./vorticity.frag");
+pub static ADVECT_FRAGMENT_SHADER: &str = include_str!("./advect.frag");
+pub static MACCORMACK_FRAGMENT_SHADER: &str = include_str!("./maccormack.frag");
+pub static JACOBI_FRAGMENT_SHADER: &str = include_str!("./jacobi.frag");
+pub static RESIDUAL_FRAGMENT_SHADER: &str = include_str!("./residual.frag");
+pub static RESTRICT_FRAGMENT_SHADER: &str = include_str!("./restrict.frag");
+pub static PROLONG_FRAGMENT_SHADER: &str = include_str!("./prolong.frag");
+pub static REDUCE_FRAGMENT_SHADER: &str = include_str!("./reduce.frag");
+pub static PACK_FRAGMENT_SHADER: &str = include_str!("./pack.frag");
+pub static DIVERGE_FRAGMENT_SHADER: &str = include_str!("./divergence.frag");
+pub static FORCE_FRAGMENT_SHADER:  &str = include_str!("./force.frag");
+pub static COLOR_FRAGMENT_SHADER:  &str = include_str!("./dye.frag");
+pub static SUB_FRAGMENT_SHADER:    &str = include_str!("./subtract.frag");
+pub static BOUND_FRAGMENT_SHADER:  &str = include_str!("./boundary.frag");
+pub static OBSTACLE_FRAGMENT_SHADER: &str = include_str!("./obstacle.frag");
+pub static BODY_FRAGMENT_SHADER: &str = include_str!("./body.frag");
+pub static VORT_FRAGMENT_SHADER: &str = include_str!("./vorticity.frag");
+pub static BUOYANCY_FRAGMENT_SHADER: &str = include_str!("./buoyancy.frag");
+pub static HEAT_FRAGMENT_SHADER: &str = include_str!("./heat.frag");
+
+// the `wrap` of the passes sampling past the edges of their fields
+static WRAP_PREFIX: &str = include_str!("./wrap.glsl");
+// the brush functions and uniforms of the splat passes, ahead of their source
+static BRUSH_PREFIX: &str = include_str!("./brush.glsl");
+
+/// `source` compiled after the shared `wrap` of coordinates past periodic
+/// edges, for the passes that sample neighbours or backtraces.
+pub fn with_wrap(source: &str) -> String {
+    format!("{}\n{}", WRAP_PREFIX, source)
+}
+
+/// `source` compiled after the shared brush code, for the force, color and
+/// heat passes. The brush has no precision of its own, so this goes inside
+/// `with_wrap`.
+pub fn with_brush(source: &str) -> String {
+    format!("{}\n{}", BRUSH_PREFIX, source)
+}
 
 pub fn compile_shader(
     context: &WebGlRenderingContext,
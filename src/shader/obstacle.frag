@@ -0,0 +1,74 @@
+// compiled after wrap.glsl, which has the precision and `wrap`
+uniform float delta_x;
+uniform float delta_y;
+// 0: no-slip, 1: free-slip
+uniform int slip;
+uniform int staggered;
+uniform sampler2D obstacles;
+uniform sampler2D x;
+
+varying vec2 UV;
+
+vec2 mirrored;
+float fluid;
+vec2 surface;
+
+// cells whose obstacle mask is set are solid
+bool solid(vec2 coord) {
+    return texture2D(obstacles, wrap(coord)).x > 0.5;
+}
+
+// velocity of the fluid moving with the solid at coord, zero where the mask is
+// at rest. The mask holds the surface's own velocity, and the advection passes
+// only move the fluid by 0.5 * delta_t of the field's.
+vec2 solidVelocity(vec2 coord) {
+    return texture2D(obstacles, wrap(coord)).yz / 0.5;
+}
+
+// adds the velocity of the neighbour at `offset` relative to the surface,
+// reflected across the face between it and this cell; `normal` flips the
+// component through that face
+void mirror(vec2 offset, vec2 normal) {
+    if (solid(UV + offset)) {
+        return;
+    }
+
+    vec2 v = texture2D(x, wrap(UV + offset)).xy - surface;
+    mirrored += surface + (slip == 0 ? -v : v * normal);
+    fluid += 1.0;
+}
+
+void main() {
+    vec4 center = texture2D(x, UV);
+
+    if (staggered == 1) {
+        // the faces of solid cells move with the solid
+        if (solid(UV)) {
+            center.xy = solidVelocity(UV);
+        } else {
+            if (solid(UV - vec2(delta_x, 0.0))) center.x = solidVelocity(UV - vec2(delta_x, 0.0)).x;
+            if (solid(UV - vec2(0.0, delta_y))) center.y = solidVelocity(UV - vec2(0.0, delta_y)).y;
+        }
+        gl_FragColor = center;
+        return;
+    }
+
+    if (!solid(UV)) {
+        gl_FragColor = center;
+        return;
+    }
+
+    // a solid cell holds the mirror image of the fluid next to it, so that
+    // interpolating across the face gives the surface's velocity for no-slip
+    // and its normal velocity for free-slip, like the walls in boundary.frag
+    surface = solidVelocity(UV);
+    mirrored = vec2(0.0);
+    fluid = 0.0;
+    mirror(vec2(-delta_x, 0.0), vec2(-1.0, 1.0));
+    mirror(vec2( delta_x, 0.0), vec2(-1.0, 1.0));
+    mirror(vec2(0.0, -delta_y), vec2(1.0, -1.0));
+    mirror(vec2(0.0,  delta_y), vec2(1.0, -1.0));
+
+    vec2 v = fluid > 0.0 ? mirrored / fluid : surface;
+    gl_FragColor = vec4(v, 0.0, 1.0);
+}
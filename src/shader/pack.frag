@@ -0,0 +1,49 @@
+// the bits of a float only fit in highp; in mediump the readback keeps what
+// the device computed with, which is all the field had anyway
+#ifdef GL_FRAGMENT_PRECISION_HIGH
+precision highp float;
+#else
+precision mediump float;
+#endif
+
+uniform sampler2D x;
+// picks the component of x to pack, one entry 1 and the others 0
+uniform vec4 channel;
+varying vec2 UV;
+
+// the ieee 754 bits of v, most significant byte first, for reading back
+// through an rgba8 target where float targets cannot be read. Denormals flush
+// to zero and values past the range clamp to the largest exponent.
+void main() {
+    float v = dot(texture2D(x, UV), channel);
+    float a = abs(v);
+    if (a == 0.0) {
+        gl_FragColor = vec4(0.0);
+        return;
+    }
+
+    // log2 can land one off either way near a power of two
+    float e = floor(log2(a));
+    float m = a * exp2(-e);
+    if (m >= 2.0) { e += 1.0; m *= 0.5; }
+    if (m < 1.0) { e -= 1.0; m *= 2.0; }
+
+    float biased = e + 127.0;
+    if (biased < 1.0) {
+        gl_FragColor = vec4(0.0);
+        return;
+    }
+    biased = min(biased, 254.0);
+
+    // the 23 mantissa bits as 7, 8 and 8
+    float high = (m - 1.0) * 128.0;
+    float b1 = floor(high);
+    float middle = (high - b1) * 256.0;
+    float b2 = floor(middle);
+    float b3 = min(floor((middle - b2) * 256.0 + 0.5), 255.0);
+
+    float sign_bit = v < 0.0 ? 128.0 : 0.0;
+    float b0 = sign_bit + floor(biased / 2.0);
+    b1 += mod(biased, 2.0) * 128.0;
+    gl_FragColor = vec4(b0, b1, b2, b3) / 255.0;
+}
@@ -0,0 +1,11 @@
+// compiled after wrap.glsl, which has the precision and `sampleWrapped`
+uniform sampler2D x;
+uniform sampler2D e;
+// texel size of the coarse grid e
+uniform vec2 coarse_texel;
+varying vec2 UV;
+
+// adds the coarse grid correction, bilinearly interpolated
+void main() {
+    gl_FragColor = vec4(texture2D(x, UV).xy + sampleWrapped(e, coarse_texel, UV).xy, 0.0, 1.0);
+}
@@ -0,0 +1,40 @@
+// the partial sums lose too much in mediump, where the device allows better
+#ifdef GL_FRAGMENT_PRECISION_HIGH
+precision highp float;
+#else
+precision mediump float;
+#endif
+
+uniform sampler2D x;
+uniform vec2 src_size;
+// 0 on the passes over partial sums; on the first pass, which reads the field
+// itself, 1 folds the red channel and 2 the length of xy
+uniform int first;
+
+// each texel folds a 2x2 block of the source into (sum of squares, max |x|)
+void main() {
+    vec2 base = floor(gl_FragCoord.xy) * 2.0;
+
+    float sum = 0.0;
+    float maximum = 0.0;
+    for (int j = 0; j < 2; j++) {
+        for (int i = 0; i < 2; i++) {
+            vec2 texel = base + vec2(float(i), float(j));
+            if (texel.x < src_size.x && texel.y < src_size.y) {
+                vec4 v = texture2D(x, (texel + 0.5) / src_size);
+                if (first == 1) {
+                    sum += v.x * v.x;
+                    maximum = max(maximum, abs(v.x));
+                } else if (first == 2) {
+                    sum += dot(v.xy, v.xy);
+                    maximum = max(maximum, length(v.xy));
+                } else {
+                    sum += v.x;
+                    maximum = max(maximum, v.y);
+                }
+            }
+        }
+    }
+
+    gl_FragColor = vec4(sum, maximum, 0.0, 1.0);
+}
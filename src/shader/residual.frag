@@ -0,0 +1,46 @@
+// compiled after wrap.glsl, which has the precision and `wrap`. The laplacian
+// divides differences of nearly equal pressures by dx^2, which mediump cannot
+// resolve on a fine grid, so this raises it where the device allows
+#ifdef GL_FRAGMENT_PRECISION_HIGH
+precision highp float;
+#endif
+
+uniform float delta_x;
+uniform float delta_y;
+uniform float aspect;
+uniform sampler2D x;
+uniform sampler2D b;
+uniform sampler2D obstacles;
+varying vec2 UV;
+
+// cells whose obstacle mask is set are solid
+bool solid(vec2 coord) {
+    return texture2D(obstacles, wrap(coord)).x > 0.5;
+}
+
+// r = b - laplacian(x)
+void main() {
+    if (solid(UV)) {
+        gl_FragColor = vec4(0.0, 0.0, 0.0, 1.0);
+        return;
+    }
+
+    vec2 xLeft   = texture2D(x, wrap(UV - vec2(delta_x, 0.0))).xy; 
+    vec2 xRight  = texture2D(x, wrap(UV + vec2(delta_x, 0.0))).xy; 
+    vec2 xDown   = texture2D(x, wrap(UV - vec2(0.0, delta_y))).xy;
+    vec2 xUp     = texture2D(x, wrap(UV + vec2(0.0, delta_y))).xy;  
+    vec2 xCenter = texture2D(x, UV).xy;
+
+    // the same neumann condition as the jacobi pass
+    if (solid(UV - vec2(delta_x, 0.0))) xLeft  = xCenter;
+    if (solid(UV + vec2(delta_x, 0.0))) xRight = xCenter;
+    if (solid(UV - vec2(0.0, delta_y))) xDown  = xCenter;
+    if (solid(UV + vec2(0.0, delta_y))) xUp    = xCenter;
+
+    vec2 bCenter = texture2D(b, UV).xy; 
+
+    float hy = delta_y / aspect;
+    vec2 laplacian = (xLeft + xRight - 2.0 * xCenter) / (delta_x * delta_x)
+                   + (xUp + xDown - 2.0 * xCenter) / (hy * hy);
+    gl_FragColor = vec4(bCenter - laplacian, 0.0, 1.0);
+}
@@ -0,0 +1,33 @@
+// the overlaps are worked out in fine texels, which mediump cannot count far
+// enough on a large grid, where the device allows better
+#ifdef GL_FRAGMENT_PRECISION_HIGH
+precision highp float;
+#else
+precision mediump float;
+#endif
+
+uniform sampler2D x;
+// texel sizes of the fine grid x and of the coarse grid drawn to
+uniform vec2 fine_texel;
+uniform vec2 coarse_texel;
+varying vec2 UV;
+
+// full weighting: the average of the fine texels the coarse texel's cell
+// covers, each weighted by how much of it the cell covers. A coarse cell is at
+// most two fine ones wide, so it overlaps at most three along each axis. The
+// cells never reach past an edge, so nothing here wraps.
+void main() {
+    vec2 lo = (UV - 0.5 * coarse_texel) / fine_texel;
+    vec2 hi = (UV + 0.5 * coarse_texel) / fine_texel;
+    vec2 first = floor(lo);
+
+    vec2 sum = vec2(0.0);
+    for (int j = 0; j < 3; j++) {
+        for (int i = 0; i < 3; i++) {
+            vec2 start = first + vec2(float(i), float(j));
+            vec2 weight = max(min(hi, start + 1.0) - max(lo, start), 0.0);
+            sum += weight.x * weight.y * texture2D(x, (start + 0.5) * fine_texel).xy;
+        }
+    }
+    gl_FragColor = vec4(sum / ((hi.x - lo.x) * (hi.y - lo.y)), 0.0, 1.0);
+}
@@ -1,19 +1,78 @@
-precision mediump float;
-
+// compiled after wrap.glsl, which has the precision and `wrap`
 uniform float delta_x;
+uniform float delta_y;
+uniform float aspect;
 uniform sampler2D p;
 uniform sampler2D w;
+uniform sampler2D obstacles;
+uniform int staggered;
 varying vec2 UV;
 
+// cells whose obstacle mask is set are solid
+bool solid(vec2 coord) {
+    return texture2D(obstacles, wrap(coord)).x > 0.5;
+}
+
+// velocity of the fluid moving with the solid at coord, zero where the mask is
+// at rest. The mask holds the surface's own velocity, and the advection passes
+// only move the fluid by 0.5 * delta_t of the field's.
+vec2 solidVelocity(vec2 coord) {
+    return texture2D(obstacles, wrap(coord)).yz / 0.5;
+}
+
 void main() {
-    float pLeft  = texture2D(p, UV - vec2(delta_x, 0.0)).x; 
-    float pRight = texture2D(p, UV + vec2(delta_x, 0.0)).x; 
-    float pDown  = texture2D(p, UV - vec2(0.0, delta_x)).x;
-    float pUp    = texture2D(p, UV + vec2(0.0, delta_x)).x;  
+    float hy = delta_y / aspect;
+
+    if (staggered == 1) {
+        // the pressure difference across each face
+        float pCenter = texture2D(p, UV).x;
+        float pLeft   = texture2D(p, wrap(UV - vec2(delta_x, 0.0))).x;
+        float pBelow  = texture2D(p, wrap(UV - vec2(0.0, delta_y))).x;
+
+        vec4 face = texture2D(w, UV);
+        face.xy -= vec2((pCenter - pLeft) / delta_x, (pCenter - pBelow) / hy);
+
+        // the faces of solid cells move with the solid
+        if (solid(UV)) {
+            face.xy = solidVelocity(UV);
+        } else {
+            if (solid(UV - vec2(delta_x, 0.0))) face.x = solidVelocity(UV - vec2(delta_x, 0.0)).x;
+            if (solid(UV - vec2(0.0, delta_y))) face.y = solidVelocity(UV - vec2(0.0, delta_y)).y;
+        }
+
+        gl_FragColor = face;
+        return;
+    }
 
     vec4 color = texture2D(w, UV);
+    if (solid(UV)) {
+        gl_FragColor = vec4(solidVelocity(UV), color.zw);
+        return;
+    }
+
+    float pCenter = texture2D(p, UV).x;
+    float pLeft  = texture2D(p, wrap(UV - vec2(delta_x, 0.0))).x; 
+    float pRight = texture2D(p, wrap(UV + vec2(delta_x, 0.0))).x; 
+    float pDown  = texture2D(p, wrap(UV - vec2(0.0, delta_y))).x;
+    float pUp    = texture2D(p, wrap(UV + vec2(0.0, delta_y))).x;  
+
+    // the pressure solve left no gradient across the faces of solid cells;
+    // `walls` sums the velocities of the solid neighbours on each axis
+    vec2 walls = vec2(0.0);
+    vec2 solids = vec2(0.0);
+    if (solid(UV - vec2(delta_x, 0.0))) { pLeft  = pCenter; walls.x += solidVelocity(UV - vec2(delta_x, 0.0)).x; solids.x += 1.0; }
+    if (solid(UV + vec2(delta_x, 0.0))) { pRight = pCenter; walls.x += solidVelocity(UV + vec2(delta_x, 0.0)).x; solids.x += 1.0; }
+    if (solid(UV - vec2(0.0, delta_y))) { pDown  = pCenter; walls.y += solidVelocity(UV - vec2(0.0, delta_y)).y; solids.y += 1.0; }
+    if (solid(UV + vec2(0.0, delta_y))) { pUp    = pCenter; walls.y += solidVelocity(UV + vec2(0.0, delta_y)).y; solids.y += 1.0; }
+
     float half_rdx = 1.0 / (2.0 * delta_x);
-    color.xy -= half_rdx * vec2((pRight - pLeft), (pUp - pDown));
+    float half_rdy = 1.0 / (2.0 * hy);
+    color.xy -= vec2(half_rdx * (pRight - pLeft), half_rdy * (pUp - pDown));
+
+    // the central differences cannot see a flow into a neighbouring solid,
+    // so match the solid's outright
+    if (solids.x > 0.0) color.x = walls.x / solids.x;
+    if (solids.y > 0.0) color.y = walls.y / solids.y;
 
     gl_FragColor = color;
 }
\ No newline at end of file
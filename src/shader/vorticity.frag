@@ -1,31 +1,58 @@
-precision mediump float;
-
+// compiled after wrap.glsl, which has the precision and `wrap`
 uniform float delta_t;
 uniform float delta_x;
+uniform float delta_y;
+uniform float aspect;
 uniform float vorticity;
+uniform int staggered;
 
 uniform sampler2D v;
 varying vec2 UV;
 
-float curl(in float x, in float y, in sampler2D v) {
-    float upx = texture2D(v, vec2(x, y + delta_x)).x;
-    float downx = texture2D(v, vec2(x, y - delta_x)).x; 
-    float lefty = texture2D(v, vec2(x - delta_x, y)).y; 
-    float righty = texture2D(v, vec2(x + delta_x, y)).y; 
+// same as in advect.frag, without the conversion to uv
+vec2 velocity(vec2 coord) {
+    if (staggered == 0) {
+        return texture2D(v, wrap(coord)).xy;
+    }
+    return vec2(
+        texture2D(v, wrap(coord + vec2(0.5 * delta_x, 0.0))).x,
+        texture2D(v, wrap(coord + vec2(0.0, 0.5 * delta_y))).y);
+}
 
-    return 0.5 * (upx - downx + lefty - righty);
+// ratio of the cell width to its height
+float cell_ratio() {
+    return delta_x * aspect / delta_y;
 }
 
-void main() {
-    float x = UV.x; 
-    float y = UV.y; 
+float curl(in float x, in float y) {
+    float upx = velocity(vec2(x, y + delta_y)).x;
+    float downx = velocity(vec2(x, y - delta_y)).x; 
+    float lefty = velocity(vec2(x - delta_x, y)).y; 
+    float righty = velocity(vec2(x + delta_x, y)).y; 
+
+    return 0.5 * ((upx - downx) * cell_ratio() + lefty - righty);
+}
 
-    float dx = abs(curl(x, y - delta_x, v)) - abs(curl(x, y + delta_x, v));
-    float dy = abs(curl(x + delta_x, y, v)) - abs(curl(x - delta_x, y, v));
+// confinement force at (x, y), scaled by the time step
+vec2 confinement(in float x, in float y) {
+    float dx = abs(curl(x, y - delta_y)) - abs(curl(x, y + delta_y));
+    float dy = abs(curl(x + delta_x, y)) - abs(curl(x - delta_x, y));
     
-    vec2 d = vec2(0.5 * dx, 0.5 * dy);
+    vec2 d = vec2(0.5 * dx * cell_ratio(), 0.5 * dy);
     float len = length(d) + 1e-9; 
     d = vorticity/len * d; 
 
-    gl_FragColor = texture2D(v, UV) + delta_t * curl(UV.x, UV.y, v) * vec4(d, 0, 0);
+    return delta_t * curl(x, y) * d;
+}
+
+void main() {
+    vec2 force = confinement(UV.x, UV.y);
+    if (staggered == 1) {
+        // each component is pushed by the force at its own face
+        force = vec2(
+            confinement(UV.x - 0.5 * delta_x, UV.y).x,
+            confinement(UV.x, UV.y - 0.5 * delta_y).y);
+    }
+
+    gl_FragColor = texture2D(v, UV) + vec4(force, 0, 0);
 }
\ No newline at end of file
@@ -0,0 +1,36 @@
+precision mediump float;
+
+// 1 along the axes the fields wrap around, 0 along those they clamp on. WebGL 1
+// only repeats textures whose sides are powers of two, so the fields always
+// clamp and the samples past a periodic edge are wrapped here instead, like
+// cpu_fluid's `rem_euclid`.
+uniform vec2 periodic;
+
+vec2 wrap(vec2 coord) {
+    return mix(coord, fract(coord), periodic);
+}
+
+// bilinear sample of a field with texels `texel` apart at any coord. Samples at
+// texel centers only need `wrap`, but one within half a texel of a periodic
+// edge has to blend with the texel across it, which the clamping sampler
+// cannot; this wraps both texels it blends between first.
+vec4 sampleWrapped(sampler2D field, vec2 texel, vec2 coord) {
+    if (periodic == vec2(0.0)) {
+        return texture2D(field, coord);
+    }
+
+    vec2 pos = coord / texel - 0.5;
+    vec2 i0 = floor(pos);
+    vec2 f = pos - i0;
+    vec2 lo = wrap((i0 + 0.5) * texel);
+    vec2 hi = wrap((i0 + 1.5) * texel);
+
+    vec4 bottom = mix(texture2D(field, lo), texture2D(field, vec2(hi.x, lo.y)), f.x);
+    vec4 top = mix(texture2D(field, vec2(lo.x, hi.y)), texture2D(field, hi), f.x);
+    return mix(bottom, top, f.y);
+}
+
+// offset in uv between two points, the short way around the periodic axes
+vec2 wrapDelta(vec2 delta) {
+    return delta - periodic * floor(delta + 0.5);
+}
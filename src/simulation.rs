@@ -0,0 +1,786 @@
+use web_sys::WebGlRenderingContext as GL;
+use wasm_bindgen::JsValue;
+use nalgebra::{Vector2, Vector3};
+
+use crate::backend::{vertical_weight, Boundaries, BoundaryField, DoubleField, GridLayout, Integrator, Slip, SolverBackend, WebGlBackend};
+use crate::brush::{Blend, Brush, Falloff};
+use crate::cpu_fluid::BACKTRACE_SCALE;
+use crate::diagnostics::Diagnostics;
+use crate::emitter::{Emitter, EMITTER_SPACING};
+use crate::multigrid::Multigrid;
+use crate::obstacle::RigidBody;
+use crate::pcg::PcgReport;
+use crate::texture;
+
+// steps between readbacks of the top speed for the substep count. Each one
+// stalls until the GPU has caught up, so the count works from a speed up to
+// this many steps old, read afresh when a splat or a new field changes it.
+const SPEED_READBACK_INTERVAL: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AdvectionScheme {
+    /// Single first order backtrace.
+    SemiLagrangian,
+    /// Forward and backward semi-lagrangian steps combined to cancel the
+    /// first order error, clamped to avoid new extrema.
+    MacCormack,
+}
+
+/// The smoother used for `jacobi_iterations` sweeps of an implicit solve.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Relaxation {
+    Jacobi,
+    /// Red-black gauss-seidel, over-relaxed by `omega` (1 is plain
+    /// gauss-seidel, values up to 2 converge faster on the pressure solve).
+    RedBlackSor { omega: f32 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PressureSolver {
+    /// `jacobi_iterations` sweeps of `pressure_relaxation`.
+    Jacobi,
+    /// `multigrid_cycles` geometric multigrid V-cycles.
+    Multigrid,
+    /// Preconditioned conjugate gradient run until the residual drops below
+    /// `pcg_tolerance`. Backends without it fall back to `Jacobi`.
+    ConjugateGradient,
+}
+
+pub struct SimulationParams {
+    /// Layout of the velocity field. Changing it between steps reinterprets
+    /// the current velocity rather than converting it.
+    pub grid:               GridLayout,
+    pub jacobi_iterations:  usize,
+    pub viscosity:          f32,
+    pub diffusion_relaxation: Relaxation,
+    pub vorticity:          f32,
+    pub advection:          AdvectionScheme,
+    pub integrator:         Integrator,
+    pub pressure_solver:    PressureSolver,
+    pub pressure_relaxation: Relaxation,
+    pub multigrid_cycles:   usize,
+    /// Smoothing iterations before and after each coarse grid correction.
+    pub multigrid_smoothing: usize,
+    /// Max norm of the pressure residual the conjugate gradient solve stops at.
+    pub pcg_tolerance:      f32,
+    pub pcg_max_iterations: usize,
+    /// Carry a temperature and smoke density field along with the dye and
+    /// let their buoyancy push the velocity.
+    pub smoke:              bool,
+    pub ambient_temperature: f32,
+    /// Upward acceleration per degree above `ambient_temperature`.
+    pub buoyancy_lift:      f32,
+    /// Downward acceleration per unit of smoke density.
+    pub smoke_weight:       f32,
+    /// Most cells an advection backtrace may cross in one substep. `step` splits
+    /// its `delta_t` into as many substeps as that takes.
+    pub cfl:                f32,
+    /// Cap on the substeps of one `step`; 1 turns the adaptive timestep off,
+    /// and with it the readback of the top speed.
+    pub max_substeps:       usize,
+    /// Fractions of the velocity, dye and smoke lost per second, applied as
+    /// exponential decay while advecting. 0 keeps them indefinitely.
+    pub velocity_dissipation: f32,
+    pub dye_dissipation:    f32,
+    pub smoke_dissipation:  f32,
+    /// What the surfaces of the obstacles do to the flow along them.
+    pub obstacle_slip:      Slip,
+    /// What each edge of the domain does to the flow.
+    pub boundaries:         Boundaries,
+    /// Brush the forces and heat are drawn with, by the splats and emitters.
+    pub force_brush:        Brush,
+    /// Brush the dye is drawn with.
+    pub dye_brush:          Brush,
+}
+
+impl Default for SimulationParams {
+    fn default() -> SimulationParams {
+        SimulationParams {
+            grid: GridLayout::Collocated,
+            jacobi_iterations: 20,
+            viscosity: 1e-6,
+            diffusion_relaxation: Relaxation::Jacobi,
+            vorticity: 10.0,
+            advection: AdvectionScheme::SemiLagrangian,
+            integrator: Integrator::Euler,
+            pressure_solver: PressureSolver::Jacobi,
+            pressure_relaxation: Relaxation::Jacobi,
+            multigrid_cycles: 2,
+            multigrid_smoothing: 2,
+            pcg_tolerance: 1e-3,
+            pcg_max_iterations: 200,
+            smoke: false,
+            ambient_temperature: 0.0,
+            buoyancy_lift: 1.0,
+            smoke_weight: 0.1,
+            cfl: 2.0,
+            max_substeps: 4,
+            velocity_dissipation: 0.0,
+            dye_dissipation: 0.0,
+            smoke_dissipation: 0.0,
+            obstacle_slip: Slip::NoSlip,
+            boundaries: Boundaries::default(),
+            force_brush: Brush::force(),
+            dye_brush: Brush::dye(),
+        }
+    }
+}
+
+/// The fluid solver state: every field needed to advance the simulation,
+/// independent of any page controls. The passes themselves run on `B`.
+pub struct FluidSimulation<B: SolverBackend = WebGlBackend> {
+    pub params: SimulationParams,
+    /// Solids moving through the fluid on top of the obstacle mask. Each
+    /// substep moves them by their velocities before the fluid.
+    pub bodies: Vec<RigidBody>,
+    /// Sources of momentum and dye applied every substep their schedule is
+    /// on, after the queued splats.
+    pub emitters: Vec<Emitter>,
+
+    backend:    B,
+    width:      i32,
+    height:     i32,
+    // the dye has a grid of its own, finer or coarser than the velocity's
+    dye_width:  i32,
+    dye_height: i32,
+
+    velocity:   DoubleField<B::Field>,
+    pressure:   DoubleField<B::Field>,
+    divergence: B::Field,
+    color:      DoubleField<B::Field>,
+    // temperature in red, smoke density in green
+    smoke:      DoubleField<B::Field>,
+    // solid cells in red, see `SolverBackend`
+    obstacles:  DoubleField<B::Field>,
+    // the obstacles with the bodies stamped over them, rebuilt every substep
+    solids:     DoubleField<B::Field>,
+
+    // pressure solve residual, kept for `diagnostics`
+    residual:   B::Field,
+
+    // intermediate steps of the maccormack scheme, on the velocity grid and
+    // on the dye grid
+    advect_scratch: DoubleField<B::Field>,
+    dye_scratch:    DoubleField<B::Field>,
+
+    multigrid:  Multigrid<B::Field>,
+    pcg_report: Option<PcgReport>,
+    // the periodic axes the fields wrap around, `None` until they are set
+    periodic:   Option<(bool, bool)>,
+    substeps:   usize,
+    // top speed at the last readback, and the steps since; `None` to read it
+    // on the next step
+    speed:      f32,
+    speed_age:  Option<usize>,
+    // simulated seconds so far, for the emitter schedules
+    time:       f32,
+
+    // splats queued since the last step, applied at the force stage
+    forces: Vec<(Vector2<f32>, Vector2<f32>)>,
+    dyes:   Vec<(Vector3<f32>, Vector2<f32>)>,
+    heats:  Vec<(Vector2<f32>, Vector2<f32>)>,
+}
+
+impl FluidSimulation<WebGlBackend> {
+    pub fn new(gl: &GL, width: i32, height: i32, params: SimulationParams) -> Result<FluidSimulation, JsValue> {
+        FluidSimulation::with_backend(WebGlBackend::new(gl)?, width, height, params)
+    }
+
+    /// Draws the dye field into `target`, or to the canvas when `None`.
+    pub fn render_to(&self, target: Option<&texture::Framebuffer>) {
+        self.backend.render_to(&self.color.read, target);
+    }
+}
+
+impl<B: SolverBackend> FluidSimulation<B> {
+    pub fn with_backend(mut backend: B, width: i32, height: i32, params: SimulationParams) -> Result<FluidSimulation<B>, B::Error> {
+        let velocity = DoubleField::new(backend.create_field(width, height)?, backend.create_field(width, height)?);
+        let pressure = DoubleField::new(backend.create_field(width, height)?, backend.create_field(width, height)?);
+        let divergence = backend.create_field(width, height)?;
+        let residual = backend.create_field(width, height)?;
+        let color = DoubleField::new(backend.create_field(width, height)?, backend.create_field(width, height)?);
+        let smoke = DoubleField::new(backend.create_field(width, height)?, backend.create_field(width, height)?);
+        let mut obstacles = DoubleField::new(backend.create_field(width, height)?, backend.create_field(width, height)?);
+        backend.clear_field(&mut obstacles.read);
+        let solids = DoubleField::new(backend.create_field(width, height)?, backend.create_field(width, height)?);
+        let advect_scratch = DoubleField::new(backend.create_field(width, height)?, backend.create_field(width, height)?);
+        let dye_scratch = DoubleField::new(backend.create_field(width, height)?, backend.create_field(width, height)?);
+        let multigrid = Multigrid::new(&mut backend, width, height)?;
+
+        Ok(FluidSimulation {
+            params,
+            bodies: Vec::new(),
+            emitters: Vec::new(),
+            backend,
+            width,
+            height,
+            dye_width:  width,
+            dye_height: height,
+
+            velocity,
+            pressure,
+            divergence,
+            color,
+            smoke,
+            obstacles,
+            solids,
+
+            residual,
+
+            advect_scratch,
+            dye_scratch,
+
+            multigrid,
+            pcg_report: None,
+            periodic:   None,
+            substeps:   1,
+            speed:      0.0,
+            speed_age:  None,
+            time:       0.0,
+
+            forces: Vec::new(),
+            dyes:   Vec::new(),
+            heats:  Vec::new(),
+        })
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    pub fn dye_width(&self) -> i32 {
+        self.dye_width
+    }
+
+    pub fn dye_height(&self) -> i32 {
+        self.dye_height
+    }
+
+    pub fn backend(&mut self) -> &mut B {
+        &mut self.backend
+    }
+
+    /// Norms of the divergence left after the last projection and of the
+    /// residual of its pressure solve. Reads back from the backend, so only
+    /// call it when the numbers are wanted.
+    pub fn diagnostics(&mut self) -> Result<Diagnostics, B::Error> {
+        Ok(Diagnostics {
+            divergence: self.backend.norms(&self.divergence)?,
+            residual:   self.backend.norms(&self.residual)?,
+        })
+    }
+
+    /// Iterations and final residual of the last conjugate gradient pressure
+    /// solve, `None` if the last step used another solver.
+    pub fn pcg_report(&self) -> Option<PcgReport> {
+        self.pcg_report
+    }
+
+    /// Seconds simulated since the simulation was created.
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Number of substeps the last `step` was split into.
+    pub fn substeps(&self) -> usize {
+        self.substeps
+    }
+
+    /// Reallocates the velocity grid, and every field on it, at `width` by
+    /// `height`. The velocity, pressure, smoke and obstacles are resampled
+    /// onto the new grid so the flow carries on where it was. The dye keeps
+    /// its own grid, see `resize_dye`. On an error the simulation keeps the
+    /// grid it had.
+    pub fn resize(&mut self, width: i32, height: i32) -> Result<(), B::Error> {
+        if width == self.width && height == self.height {
+            return Ok(());
+        }
+
+        let backend = &mut self.backend;
+        let multigrid = Multigrid::new(backend, width, height)?;
+
+        let mut fields = Vec::new();
+        for field in [&mut self.velocity, &mut self.pressure, &mut self.smoke, &mut self.obstacles] {
+            fields.push((&mut field.read, true));
+            fields.push((&mut field.write, false));
+        }
+        for field in [&mut self.solids, &mut self.advect_scratch] {
+            fields.push((&mut field.read, false));
+            fields.push((&mut field.write, false));
+        }
+        fields.push((&mut self.divergence, false));
+        fields.push((&mut self.residual, false));
+
+        if let Err(e) = reallocate(backend, fields, width, height) {
+            multigrid.delete(backend);
+            return Err(e);
+        }
+        std::mem::replace(&mut self.multigrid, multigrid).delete(backend);
+
+        self.width = width;
+        self.height = height;
+        self.periodic = None;
+
+        Ok(())
+    }
+
+    /// Reallocates the dye grid at `width` by `height`, resampling the dye.
+    /// The dye is advected by the velocity wherever their texels fall, but
+    /// should keep the velocity grid's aspect ratio for its texels to stay
+    /// square. On an error the dye keeps the grid it had.
+    pub fn resize_dye(&mut self, width: i32, height: i32) -> Result<(), B::Error> {
+        if width == self.dye_width && height == self.dye_height {
+            return Ok(());
+        }
+
+        let fields = vec![
+            (&mut self.color.read, true), (&mut self.color.write, false),
+            (&mut self.dye_scratch.read, false), (&mut self.dye_scratch.write, false),
+        ];
+        reallocate(&mut self.backend, fields, width, height)?;
+
+        self.dye_width = width;
+        self.dye_height = height;
+        self.periodic = None;
+
+        Ok(())
+    }
+
+    /// Replaces the velocity field with `data` (RGBA, `width * height * 4`
+    /// floats) and resets the pressure.
+    pub fn set_velocity_field(&mut self, data: Vec<f32>) -> Result<(), B::Error> {
+        let velocity = self.backend.create_field_with_data(self.width, self.height, data)?;
+        let pressure = self.backend.create_field(self.width, self.height)?;
+
+        let old_velocity = std::mem::replace(&mut self.velocity.read, velocity);
+        let old_pressure = std::mem::replace(&mut self.pressure.read, pressure);
+        self.backend.delete_field(old_velocity);
+        self.backend.delete_field(old_pressure);
+        self.periodic = None;
+        self.speed_age = None;
+
+        Ok(())
+    }
+
+    /// Replaces the dye field with `data` (RGBA, `dye_width * dye_height * 4`
+    /// floats).
+    pub fn set_color_field(&mut self, data: Vec<f32>) -> Result<(), B::Error> {
+        let color = self.backend.create_field_with_data(self.dye_width, self.dye_height, data)?;
+
+        let old_color = std::mem::replace(&mut self.color.read, color);
+        self.backend.delete_field(old_color);
+        self.periodic = None;
+
+        Ok(())
+    }
+
+    /// Replaces the obstacle mask with `data` (RGBA, `width * height * 4`
+    /// floats); cells with red above 0.5 are solid.
+    pub fn set_obstacles(&mut self, data: Vec<f32>) -> Result<(), B::Error> {
+        let obstacles = self.backend.create_field_with_data(self.width, self.height, data)?;
+
+        let old_obstacles = std::mem::replace(&mut self.obstacles.read, obstacles);
+        self.backend.delete_field(old_obstacles);
+        self.periodic = None;
+
+        Ok(())
+    }
+
+    /// Removes every obstacle.
+    pub fn clear_obstacles(&mut self) {
+        self.backend.clear_field(&mut self.obstacles.read);
+    }
+
+    /// Paints a solid disk, or erases one when `solid` is false, into the
+    /// obstacle mask at `pos` (uv coordinates). The disk is the size of a
+    /// dye splat, with a hard edge.
+    pub fn paint_obstacle(&mut self, pos: Vector2<f32>, solid: bool) {
+        let aspect = self.width as f32 / self.height as f32;
+        let brush = Brush { falloff: Falloff::Hard, strength: 1.0, blend: Blend::Replace, ..self.params.dye_brush };
+        let value = if solid { Vector3::new(1.0, 0.0, 0.0) } else { Vector3::zeros() };
+
+        let o = &mut self.obstacles;
+        self.backend.color(0.0, &brush, aspect, &value, &pos, &o.read, &mut o.write);
+        o.swap();
+    }
+
+    pub fn read_obstacles(&mut self) -> Result<Vec<f32>, B::Error> {
+        self.backend.read_field(&self.obstacles.read)
+    }
+
+    pub fn read_velocity_field(&mut self) -> Result<Vec<f32>, B::Error> {
+        self.backend.read_field(&self.velocity.read)
+    }
+
+    /// The dye, `dye_width * dye_height` RGBA texels.
+    pub fn read_color_field(&mut self) -> Result<Vec<f32>, B::Error> {
+        self.backend.read_field(&self.color.read)
+    }
+
+    /// Temperature in red and smoke density in green.
+    pub fn read_smoke_field(&mut self) -> Result<Vec<f32>, B::Error> {
+        self.backend.read_field(&self.smoke.read)
+    }
+
+    /// Queues an impulse at `impulse_pos` (uv coordinates) for the next step.
+    pub fn apply_force(&mut self, force: Vector2<f32>, impulse_pos: Vector2<f32>) {
+        self.forces.push((force, impulse_pos));
+    }
+
+    /// Queues a dye splat at `impulse_pos` (uv coordinates) for the next step.
+    pub fn add_dye(&mut self, color: Vector3<f32>, impulse_pos: Vector2<f32>) {
+        self.dyes.push((color, impulse_pos));
+    }
+
+    /// Queues a splat adding `temperature` degrees and `density` units of
+    /// smoke per second at `impulse_pos` (uv coordinates), over the next
+    /// step. Only has an effect with `params.smoke` on.
+    pub fn add_heat(&mut self, temperature: f32, density: f32, impulse_pos: Vector2<f32>) {
+        self.heats.push((Vector2::new(temperature, density), impulse_pos));
+    }
+
+    /// Advances the simulation by `delta_t`, in as many substeps as keep the
+    /// current velocity within `cfl` cells per substep, up to `max_substeps`.
+    pub fn step(&mut self, delta_t: f32) {
+        self.wrap_periodic_edges();
+        self.substeps = self.substeps_for(delta_t);
+
+        // the speed a force leaves is only known after the step
+        if !self.forces.is_empty() {
+            self.speed_age = None;
+        }
+
+        let substep_t = delta_t / self.substeps as f32;
+        for _ in 0..self.substeps {
+            // the splats queued for this frame act over all of it
+            self.substep(substep_t, delta_t);
+        }
+    }
+
+    // makes every field wrap around the periodic edges of `params.boundaries`,
+    // if they have changed since the last step
+    fn wrap_periodic_edges(&mut self) {
+        let periodic = (self.params.boundaries.periodic_x(), self.params.boundaries.periodic_y());
+        if self.periodic == Some(periodic) {
+            return;
+        }
+
+        let (x, y) = periodic;
+        let fields = [
+            &mut self.velocity.read, &mut self.velocity.write,
+            &mut self.pressure.read, &mut self.pressure.write,
+            &mut self.divergence, &mut self.residual,
+            &mut self.color.read, &mut self.color.write,
+            &mut self.smoke.read, &mut self.smoke.write,
+            &mut self.obstacles.read, &mut self.obstacles.write,
+            &mut self.solids.read, &mut self.solids.write,
+            &mut self.advect_scratch.read, &mut self.advect_scratch.write,
+            &mut self.dye_scratch.read, &mut self.dye_scratch.write,
+        ];
+        for field in fields {
+            self.backend.set_periodic(field, x, y);
+        }
+        self.multigrid.set_periodic(&mut self.backend, x, y);
+
+        self.periodic = Some(periodic);
+    }
+
+    fn substeps_for(&mut self, delta_t: f32) -> usize {
+        if self.params.max_substeps <= 1 {
+            return 1;
+        }
+
+        match self.speed_age {
+            Some(age) if age + 1 < SPEED_READBACK_INTERVAL => self.speed_age = Some(age + 1),
+            _ => {
+                // a speed that cannot be read back leaves the step whole
+                self.speed = self.backend.max_speed(&self.velocity.read).unwrap_or(0.0);
+                self.speed_age = Some(0);
+            }
+        }
+        let speed = self.speed;
+
+        // smallest side of a cell, in units of the domain width
+        let delta_x = 1.0/self.width as f32;
+        let delta_y = 1.0/self.height as f32;
+        let aspect  = self.width as f32 / self.height as f32;
+        let cell    = delta_x.min(delta_y / aspect);
+
+        let cells = BACKTRACE_SCALE * speed * delta_t / cell;
+        ((cells / self.params.cfl).ceil() as usize).max(1).min(self.params.max_substeps)
+    }
+
+    // one step of `delta_t`, applying the queued forces over `splat_t`
+    fn substep(&mut self, delta_t: f32, splat_t: f32) {
+        let backend = &mut self.backend;
+        let delta_x = 1.0/self.width as f32;
+        let delta_y = 1.0/self.height as f32;
+        let aspect  = self.width as f32 / self.height as f32;
+        let iter = self.params.jacobi_iterations;
+        let grid = self.params.grid;
+
+        // move the bodies, and stamp them over the obstacles where they are now
+        for body in self.bodies.iter_mut() {
+            body.advance(aspect, delta_t);
+        }
+        let obstacles = if self.bodies.is_empty() {
+            &self.obstacles.read
+        } else {
+            let s = &mut self.solids;
+            backend.resample(&self.obstacles.read, &mut s.read);
+            for body in &self.bodies {
+                backend.body(aspect, body, &s.read, &mut s.write);
+                s.swap();
+            }
+            &self.solids.read
+        };
+
+        {
+            // advect vector field
+            let v = &mut self.velocity;
+            advect(backend, self.params.advection, self.params.integrator, grid, grid, delta_x, delta_y, aspect, delta_t,
+                self.params.velocity_dissipation, obstacles, &v.read, &v.read, &mut self.advect_scratch, &mut v.write);
+            v.swap();
+        }
+
+        {
+            // viscuous diffusion
+            let wy      = vertical_weight(delta_x, delta_y, aspect);
+            let alpha   = delta_x.powf(2.0) / (self.params.viscosity * delta_t);
+            let r_beta  = 1.0/(2.0 + 2.0 * wy + alpha);
+
+            let v = &mut self.velocity;
+            match self.params.diffusion_relaxation {
+                Relaxation::Jacobi => {
+                    for k in 0..iter {
+                        if k % 2 == 0 {
+                            backend.jacobi_iteration(delta_x, delta_y, aspect, alpha, r_beta, obstacles, &v.read, &v.read, &mut v.write);
+                        } else {
+                            backend.jacobi_iteration(delta_x, delta_y, aspect, alpha, r_beta, obstacles, &v.write, &v.write, &mut v.read);
+                        }
+                    }
+                }
+                Relaxation::RedBlackSor { omega } => {
+                    // gauss-seidel works in place, so solve on a copy with the
+                    // advected velocity kept as the right hand side
+                    backend.resample(&v.read, &mut v.write);
+                    for _ in 0..iter {
+                        backend.red_black_iteration(delta_x, delta_y, aspect, alpha, r_beta, omega, obstacles,
+                            &mut v.write, &v.read, &mut self.advect_scratch.read);
+                    }
+                    v.swap();
+                }
+            }
+        }
+
+        {
+            // add forces
+            let v = &mut self.velocity;
+            for (force, impulse_pos) in self.forces.drain(..) {
+                backend.force(splat_t, &self.params.force_brush, aspect, &force, &impulse_pos, &v.read, &mut v.write);
+                v.swap();
+            }
+
+            // add dye
+            let c = &mut self.color;
+            for (color, impulse_pos) in self.dyes.drain(..) {
+                backend.color(splat_t, &self.params.dye_brush, aspect, &color, &impulse_pos, &c.read, &mut c.write);
+                c.swap();
+            }
+
+            // emitters, over this substep
+            let time = self.time;
+            for emitter in self.emitters.iter().filter(|e| e.schedule.is_on(time)) {
+                let force = emitter.force();
+                for pos in emitter.source.points(aspect, EMITTER_SPACING) {
+                    if force != Vector2::zeros() {
+                        backend.force(delta_t, &self.params.force_brush, aspect, &force, &pos, &v.read, &mut v.write);
+                        v.swap();
+                    }
+                    if let Some(color) = emitter.color {
+                        backend.color(delta_t, &self.params.dye_brush, aspect, &color, &pos, &c.read, &mut c.write);
+                        c.swap();
+                    }
+                }
+            }
+        }
+
+        if self.params.smoke {
+            // add heat
+            let h = &mut self.smoke;
+            for (heat, impulse_pos) in self.heats.drain(..) {
+                backend.heat(splat_t, &self.params.force_brush, aspect, &heat, &impulse_pos, &h.read, &mut h.write);
+                h.swap();
+            }
+
+            // buoyancy
+            let v = &mut self.velocity;
+            backend.buoyancy(delta_t, delta_y, self.params.ambient_temperature, self.params.buoyancy_lift,
+                self.params.smoke_weight, grid, &self.smoke.read, &v.read, &mut v.write);
+            v.swap();
+        } else {
+            self.heats.clear();
+        }
+
+        {
+            // compute pressure
+            backend.divergence(delta_x, delta_y, aspect, grid, &self.params.boundaries, obstacles, &self.velocity.read, &mut self.divergence);
+
+            let p = &mut self.pressure;
+            self.pcg_report = None;
+            match self.params.pressure_solver {
+                PressureSolver::Jacobi => {
+                    relax_pressure(backend, self.params.pressure_relaxation, iter, delta_x, delta_y, aspect, obstacles, p, &self.divergence);
+                }
+                PressureSolver::Multigrid => {
+                    self.multigrid.solve(backend, self.params.multigrid_cycles, self.params.multigrid_smoothing,
+                        delta_x, delta_y, aspect, obstacles, p, &self.divergence);
+                }
+                PressureSolver::ConjugateGradient => {
+                    self.pcg_report = backend.conjugate_gradient(delta_x, delta_y, aspect, self.params.pcg_tolerance,
+                        self.params.pcg_max_iterations, &self.params.boundaries, obstacles, &mut p.read, &self.divergence);
+
+                    if self.pcg_report.is_none() {
+                        relax_pressure(backend, self.params.pressure_relaxation, iter, delta_x, delta_y, aspect, obstacles, p, &self.divergence);
+                    }
+                }
+            }
+
+            backend.residual(delta_x, delta_y, aspect, obstacles, &self.pressure.read, &self.divergence, &mut self.residual);
+        }
+
+        {
+            // gradient subtraction
+            let v = &mut self.velocity;
+            backend.subtract(delta_x, delta_y, aspect, grid, obstacles, &self.pressure.read, &v.read, &mut v.write);
+            v.swap();
+
+            // what is left of the divergence, for `diagnostics`
+            backend.divergence(delta_x, delta_y, aspect, grid, &self.params.boundaries, obstacles, &v.read, &mut self.divergence);
+        }
+
+        {
+            // boundary conditions
+            let v = &mut self.velocity;
+            backend.boundary(delta_x, delta_y, &self.params.boundaries, BoundaryField::Velocity, grid, &v.read, &mut v.write);
+            v.swap();
+            backend.obstacle_boundary(delta_x, delta_y, self.params.obstacle_slip, grid, obstacles, &v.read, &mut v.write);
+            v.swap();
+
+            let p = &mut self.pressure;
+            backend.boundary(delta_x, delta_y, &self.params.boundaries, BoundaryField::Pressure, GridLayout::Collocated, &p.read, &mut p.write);
+            p.swap();
+        }
+
+        {
+            let v = &mut self.velocity;
+            backend.vorticity_confinement(delta_t, delta_x, delta_y, aspect, self.params.vorticity, grid, &v.read, &mut v.write);
+            v.swap();
+        }
+
+        {
+            // advect color field, and the smoke with it
+            let c = &mut self.color;
+            advect(backend, self.params.advection, self.params.integrator, grid, GridLayout::Collocated, delta_x, delta_y, aspect, delta_t,
+                self.params.dye_dissipation, obstacles, &c.read, &self.velocity.read, &mut self.dye_scratch, &mut c.write);
+            c.swap();
+
+            if self.params.smoke {
+                let h = &mut self.smoke;
+                advect(backend, self.params.advection, self.params.integrator, grid, GridLayout::Collocated, delta_x, delta_y, aspect, delta_t,
+                    self.params.smoke_dissipation, obstacles, &h.read, &self.velocity.read, &mut self.advect_scratch, &mut h.write);
+                h.swap();
+            }
+        }
+
+        self.time += delta_t;
+    }
+}
+
+// replaces each of `fields` with one of `width` by `height`, resampling those
+// flagged to keep their contents. Every new field is created before any is
+// swapped in, so an error leaves them all as they were.
+fn reallocate<B: SolverBackend>(backend: &mut B, fields: Vec<(&mut B::Field, bool)>, width: i32, height: i32) -> Result<(), B::Error> {
+    let mut created = Vec::with_capacity(fields.len());
+    for _ in 0..fields.len() {
+        match backend.create_field(width, height) {
+            Ok(field) => created.push(field),
+            Err(e) => {
+                for field in created {
+                    backend.delete_field(field);
+                }
+                return Err(e);
+            }
+        }
+    }
+
+    for ((field, keep), mut resized) in fields.into_iter().zip(created) {
+        if keep {
+            backend.resample(field, &mut resized);
+        }
+        let old = std::mem::replace(field, resized);
+        backend.delete_field(old);
+    }
+    Ok(())
+}
+
+// `iter` sweeps on `laplacian(p) = b`, the result is left in `p.read`
+#[allow(clippy::too_many_arguments)]
+fn relax_pressure<B: SolverBackend>(backend: &mut B,
+    relaxation: Relaxation,
+    iter:       usize,
+    delta_x:    f32,
+    delta_y:    f32,
+    aspect:     f32,
+    obstacles:  &B::Field,
+    p:          &mut DoubleField<B::Field>,
+    b:          &B::Field,
+) {
+    let alpha   = -(delta_x.powf(2.0));
+    let r_beta  = 1.0/(2.0 + 2.0 * vertical_weight(delta_x, delta_y, aspect));
+
+    match relaxation {
+        Relaxation::Jacobi => {
+            backend.jacobi_method(iter, delta_x, delta_y, aspect, alpha, r_beta, obstacles, &mut p.read, b, &mut p.write);
+        }
+        Relaxation::RedBlackSor { omega } => {
+            for _ in 0..iter {
+                backend.red_black_iteration(delta_x, delta_y, aspect, alpha, r_beta, omega, obstacles, &mut p.read, b, &mut p.write);
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn advect<B: SolverBackend>(backend: &mut B,
+    scheme:         AdvectionScheme,
+    integrator:     Integrator,
+    layout:         GridLayout,
+    src_layout:     GridLayout,
+    delta_x:        f32,
+    delta_y:        f32,
+    aspect:         f32,
+    delta_t:        f32,
+    dissipation:    f32,
+    obstacles:      &B::Field,
+    src:            &B::Field,
+    vector_field:   &B::Field,
+    scratch:        &mut DoubleField<B::Field>,
+    dst:            &mut B::Field,
+) {
+    match scheme {
+        AdvectionScheme::SemiLagrangian => {
+            backend.advection(delta_x, delta_y, aspect, delta_t, dissipation, integrator, layout, src_layout, obstacles, src, vector_field, dst);
+        }
+        AdvectionScheme::MacCormack => {
+            let DoubleField { read: forward, write: backward } = scratch;
+            backend.advection(delta_x, delta_y, aspect, delta_t, 0.0, integrator, layout, src_layout, obstacles, src, vector_field, forward);
+            backend.advection(delta_x, delta_y, aspect, -delta_t, 0.0, integrator, layout, src_layout, obstacles, forward, vector_field, backward);
+            backend.maccormack(delta_x, delta_y, aspect, delta_t, dissipation, integrator, layout, src_layout, obstacles, src, vector_field, forward, backward, dst);
+        }
+    }
+}
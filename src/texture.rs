@@ -7,6 +7,7 @@ use nalgebra::{Vector3};
 use palette::rgb::Rgb;
 use palette::encoding::srgb::Srgb;
 
+use std::cell::Cell;
 use std::f32;
 use std::f32::consts::PI;
 
@@ -17,6 +18,8 @@ pub struct Framebuffer{
     h_: i32,
     fb_: WebGlFramebuffer,
     c_: WebGlTexture,
+    // the axes the shaders wrap samples around along, see `set_periodic`
+    periodic_: Cell<(bool, bool)>,
 }
 
 impl Framebuffer {
@@ -26,7 +29,7 @@ impl Framebuffer {
         gl.bind_framebuffer(GL::FRAMEBUFFER, Some(&fb));
 
         // create rgb texture
-        let c = Framebuffer::create_float_texture(&gl, width, height)?;
+        let c = Framebuffer::create_render_texture(gl, width, height, GL::FLOAT)?;
         
         let attachment0 = GL::COLOR_ATTACHMENT0;
         gl.framebuffer_texture_2d(GL::FRAMEBUFFER, attachment0, GL::TEXTURE_2D, Some(&c), 0);
@@ -38,6 +41,26 @@ impl Framebuffer {
             h_: height, 
             fb_: fb,
             c_: c,
+            periodic_: Cell::new((false, false)),
+        })
+    }
+
+    /// A framebuffer of 8 bit channels, which can always be read back; see
+    /// `read_bytes`.
+    pub fn new_bytes(gl: &GL, width: i32, height: i32) -> Result<Framebuffer, JsValue> {
+        let fb = gl.create_framebuffer().ok_or("failed to create framebuffer")?;
+        let c = Framebuffer::create_render_texture(gl, width, height, GL::UNSIGNED_BYTE)?;
+
+        gl.bind_framebuffer(GL::FRAMEBUFFER, Some(&fb));
+        gl.framebuffer_texture_2d(GL::FRAMEBUFFER, GL::COLOR_ATTACHMENT0, GL::TEXTURE_2D, Some(&c), 0);
+        gl.bind_framebuffer(GL::FRAMEBUFFER, None);
+
+        Ok(Framebuffer {
+            w_: width,
+            h_: height,
+            fb_: fb,
+            c_: c,
+            periodic_: Cell::new((false, false)),
         })
     }
 
@@ -48,7 +71,7 @@ impl Framebuffer {
 
     pub fn create_with_data(gl: &GL, width: i32, height: i32, texture_data: Vec<f32>) -> Result<Framebuffer, JsValue>{
         let fb = gl.create_framebuffer().ok_or("failed to create framebuffer")?;
-        let texture = create_texture(&gl, width, height, &texture_data)?;
+        let texture = create_texture(gl, width, height, &texture_data)?;
         gl.bind_framebuffer(GL::FRAMEBUFFER, Some(&fb));
         
         let attachment0 = GL::COLOR_ATTACHMENT0;
@@ -61,28 +84,80 @@ impl Framebuffer {
             h_: height, 
             fb_: fb,
             c_: texture,
+            periodic_: Cell::new((false, false)),
         })
     }
 
     pub fn bind(&self, gl: &GL) {
         gl.bind_framebuffer(GL::FRAMEBUFFER, Some(&(self.fb_)));
+        gl.viewport(0, 0, self.w_, self.h_);
     }
 
     pub fn unbind(&self, gl: &GL) {
         gl.bind_framebuffer(GL::FRAMEBUFFER, None);
+        gl.viewport(0, 0, gl.drawing_buffer_width(), gl.drawing_buffer_height());
     }
 
     pub fn get_texture(&self) -> &WebGlTexture {
         &self.c_
     }
 
-    // create the rgb texture for the framebuffer
-    fn create_float_texture(gl: &GL, width: i32, height: i32) -> Result<WebGlTexture, JsValue> {
+    pub fn width(&self) -> i32 {
+        self.w_
+    }
+
+    pub fn height(&self) -> i32 {
+        self.h_
+    }
+
+    // read the rgba contents back, bottom row first like `create_with_data`.
+    // WebGL only reads floats with WEBGL_color_buffer_float enabled, and
+    // reports it through the error flag rather than throwing otherwise.
+    pub fn read_pixels(&self, gl: &GL) -> Result<Vec<f32>, JsValue> {
+        let pixels = js_sys::Float32Array::new_with_length((self.w_ * self.h_ * 4) as u32);
+
+        // clear the flag of anything earlier
+        while gl.get_error() != GL::NO_ERROR {}
+
+        self.bind(gl);
+        gl.read_pixels_with_opt_array_buffer_view(0, 0, self.w_, self.h_, GL::RGBA, GL::FLOAT, Some(&pixels))?;
+        self.unbind(gl);
+
+        if gl.get_error() != GL::NO_ERROR {
+            return Err(JsValue::from_str("float readback is not supported"));
+        }
+        Ok(pixels.to_vec())
+    }
+
+    // read the contents of a `new_bytes` framebuffer back, in the same order
+    pub fn read_bytes(&self, gl: &GL) -> Result<Vec<u8>, JsValue> {
+        let pixels = js_sys::Uint8Array::new_with_length((self.w_ * self.h_ * 4) as u32);
+
+        self.bind(gl);
+        gl.read_pixels_with_opt_array_buffer_view(0, 0, self.w_, self.h_, GL::RGBA, GL::UNSIGNED_BYTE, Some(&pixels))?;
+        self.unbind(gl);
+
+        Ok(pixels.to_vec())
+    }
+
+    /// Makes the passes wrap their samples past the edges around to the
+    /// opposite edge, along x and y, instead of clamping to the edge texel.
+    /// The texture itself keeps clamping; see wrap.glsl.
+    pub fn set_periodic(&self, periodic_x: bool, periodic_y: bool) {
+        self.periodic_.set((periodic_x, periodic_y));
+    }
+
+    pub fn periodic(&self) -> (bool, bool) {
+        self.periodic_.get()
+    }
+
+    // create the rgb texture for the framebuffer, of FLOAT or UNSIGNED_BYTE
+    fn create_render_texture(gl: &GL, width: i32, height: i32, data_type: u32) -> Result<WebGlTexture, JsValue> {
         let render_texture = gl.create_texture().ok_or("failed to create rgb texture")?;
         gl.bind_texture(GL::TEXTURE_2D, Some(&(render_texture)));
         // uh what lol 
         gl.tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_array_buffer_view(
-            GL::TEXTURE_2D, 0, GL::RGBA as i32, width, height, 0, GL::RGBA, GL::FLOAT, None)?;
+            GL::TEXTURE_2D, 0, GL::RGBA as i32, width, height, 0, GL::RGBA, data_type, None)?;
         
         gl.tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_MIN_FILTER, GL::LINEAR as i32);
         gl.tex_parameteri(GL::TEXTURE_2D, GL::TEXTURE_WRAP_S, GL::CLAMP_TO_EDGE as i32);
@@ -184,7 +259,7 @@ pub fn make_rainbow_array(width: i32, height: i32) -> Vec<f32> {
     for r in 0..width {
         for c in 0..height {
             let size = colors.len() as i32;
-            let mut sub = c as i32 - r as i32;
+            let mut sub = c - r;
             while sub < size {
                 sub += size;
             }
@@ -211,8 +286,8 @@ pub fn make_black_array(width: i32, height: i32) -> Vec<f32> {
 
     let black = Rgb::<Srgb, f32>::new(0.0, 0.0, 0.0);
 
-    for _ in 0..(height as i32){
-        for _ in 0..(width as i32) {
+    for _ in 0..height {
+        for _ in 0..width {
             data.push(black.red);
             data.push(black.green);
             data.push(black.blue);
@@ -229,8 +304,8 @@ pub fn make_black_white_array(width: i32, height: i32) -> Vec<f32> {
     let white = Rgb::<Srgb, f32>::new(1.0, 1.0, 1.0);
     let black = Rgb::<Srgb, f32>::new(0.0, 0.0, 0.0);
 
-    for _ in 0..(height as i32){
-        for c in 0..(width as i32) {
+    for _ in 0..height {
+        for c in 0..width {
             if c < height / 2 {
                 data.push(white.red);
                 data.push(white.green);
@@ -254,8 +329,8 @@ pub fn make_red_blue_array(width: i32, height: i32) -> Vec<f32> {
     let red = Rgb::<Srgb, f32>::new(1.0, 0.1, 0.1);
     let blue = Rgb::<Srgb, f32>::new(0.0, 0.5, 1.0);
 
-    for _ in 0..(height as i32){
-        for c in 0..(width as i32) {
+    for _ in 0..height {
+        for c in 0..width {
             if c < height / 2 {
                 data.push(red.red);
                 data.push(red.green);
@@ -281,7 +356,7 @@ pub fn make_sine_vector_field(width: f32, height: f32) -> Vec<f32> {
             // sine vector field is given by f(x, y) = [1, sin(2*pi*y)]
             let x: f32 = (c as f32 - width / 2.0)/(width/2.0);
 
-            let v = Vector3::new(1.0, 0.5*(2.0*PI*(x as f32)).sin(), 0.0);
+            let v = Vector3::new(1.0, 0.5*(2.0*PI*x).sin(), 0.0);
             
             data.push(v.x); 
             data.push(v.y); 
@@ -302,7 +377,7 @@ pub fn make_rotational_vector_field(width: f32, height: f32) -> Vec<f32> {
             let x: f32 = (c as f32 - width / 2.0)/(width/2.0);
             let y: f32 = (height - r as f32 - height / 2.0)/(height/2.0);
 
-            let v = Vector3::new((-2.0*PI*(y as f32)).sin(), (2.0*PI*(x as f32)).sin(), 0.0);
+            let v = Vector3::new((-2.0*PI*y).sin(), (2.0*PI*x).sin(), 0.0);
             
             data.push(v.x); 
             data.push(v.y); 
@@ -406,6 +481,18 @@ pub fn make_waves_vector_field(width: f32, height: f32) -> Vec<f32> {
     data
 }
 
+// names of the initial fields, in the order of their `get_*_with_value` values
+pub static VECTOR_FIELD_NAMES: [&str; 7] = ["Static", "Constant", "Divergent", "Sine", "Circle", "Rotational", "Waves"];
+pub static COLOR_FIELD_NAMES: [&str; 6] = ["White", "Black", "Checkerboard", "White/Black", "Red/Blue", "Rainbow"];
+
+pub fn vector_field_value(name: &str) -> Option<i32> {
+    VECTOR_FIELD_NAMES.iter().position(|n| n.eq_ignore_ascii_case(name)).map(|i| i as i32 + 1)
+}
+
+pub fn color_field_value(name: &str) -> Option<i32> {
+    COLOR_FIELD_NAMES.iter().position(|n| n.eq_ignore_ascii_case(name)).map(|i| i as i32 + 1)
+}
+
 pub fn get_vector_field_with_value(val: i32, width: i32,  height: i32) -> Vec<f32> {
     if val == 1 {
         make_static_vector_field(width as f32, height as f32)
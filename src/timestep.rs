@@ -0,0 +1,52 @@
+// Fixed timestep driven by the wall clock: the time between animation frames
+// is accumulated and spent in whole steps of the same size, so the simulation
+// runs at the same speed whatever the display's refresh rate.
+//
+// Frames are not interpolated between steps: that would mean keeping the
+// previous dye field and blending it into every draw, which costs a full
+// field copy per step. A frame without a step shows the last one again, so on
+// a display faster than the step rate the motion holds for a frame now and
+// then instead of moving smoothly.
+
+pub struct FixedTimestep {
+    /// Seconds of simulated time per step.
+    pub step:       f32,
+    /// Most steps taken for one frame. Time beyond that is dropped, so a
+    /// stall such as a background tab does not leave a backlog of steps that
+    /// makes every following frame slower still.
+    pub max_steps:  usize,
+
+    // seconds not yet spent on a step
+    accumulator:    f64,
+    // timestamp of the previous frame, in milliseconds
+    last_frame:     Option<f64>,
+}
+
+impl FixedTimestep {
+    pub fn new(step: f32, max_steps: usize) -> FixedTimestep {
+        FixedTimestep {
+            step,
+            max_steps,
+            accumulator: 0.0,
+            last_frame: None,
+        }
+    }
+
+    /// Adds the time since the previous frame and returns how many steps to
+    /// take for the frame at `now`, a `requestAnimationFrame` timestamp in
+    /// milliseconds. The first frame only starts the clock.
+    pub fn advance(&mut self, now: f64) -> usize {
+        let elapsed = match self.last_frame.replace(now) {
+            Some(last) => ((now - last) / 1000.0).max(0.0),
+            None => return 0,
+        };
+
+        let step = self.step as f64;
+        self.accumulator = (self.accumulator + elapsed).min(step * self.max_steps as f64);
+
+        // frames one step apart should not come out a rounding error short
+        let steps = (self.accumulator / step + 1e-6).floor() as usize;
+        self.accumulator = (self.accumulator - steps as f64 * step).max(0.0);
+        steps
+    }
+}
@@ -0,0 +1,37 @@
+//! Brush falloff curves and blend modes.
+
+use fluids::brush::{Blend, Brush, Falloff};
+
+use nalgebra::Vector2;
+
+#[test]
+fn falloffs_fade_from_the_center() {
+    let brush = |falloff| Brush { radius: 0.1, falloff, strength: 1.0, blend: Blend::Replace };
+    let at = |d: f32| Vector2::new(d, 0.0);
+
+    let hard = brush(Falloff::Hard);
+    assert_eq!(hard.weight(at(0.09)), 1.0);
+    assert_eq!(hard.weight(at(0.11)), 0.0);
+
+    let gaussian = brush(Falloff::Gaussian);
+    assert_eq!(gaussian.weight(at(0.0)), 1.0);
+    assert!((gaussian.weight(at(0.1)) - (-1.0_f32).exp()).abs() < 1e-6);
+
+    let smooth = brush(Falloff::Smoothstep);
+    assert_eq!(smooth.weight(at(0.0)), 1.0);
+    assert!((smooth.weight(at(0.05)) - 0.5).abs() < 1e-6);
+    assert_eq!(smooth.weight(at(0.2)), 0.0);
+}
+
+#[test]
+fn default_brushes_match_the_old_splats() {
+    // the force gaussian was exp(-d² / 1e-3)
+    let d = Vector2::new(0.03, 0.01);
+    assert!((Brush::force().weight(d) - (-d.norm_squared() / 1e-3).exp()).abs() < 1e-6);
+    assert_eq!(Brush::force().apply(0.5, 1.0, 1.0, 2.0), 2.0);
+
+    // the dye overwrote a disk of radius 0.025
+    assert_eq!(Brush::dye().weight(Vector2::new(0.02, 0.0)), 1.0);
+    assert_eq!(Brush::dye().weight(Vector2::new(0.03, 0.0)), 0.0);
+    assert_eq!(Brush::dye().apply(0.5, 1.0, 0.2, 0.7), 0.7);
+}
@@ -0,0 +1,321 @@
+//! Checks for the CPU reference solver. These run natively under `cargo test`.
+
+use fluids::backend::{Boundaries, BoundaryField, Edge, GridLayout, Integrator, Slip};
+use fluids::brush::{Blend, Brush, Falloff};
+use fluids::cpu_fluid::{self, Field};
+use fluids::obstacle::{self, BodyShape, RigidBody, Shape};
+use fluids::texture;
+
+use nalgebra::{Vector2, Vector3, Vector4};
+
+const N: i32 = 32;
+
+fn field_from(data: Vec<f32>) -> Field {
+    Field::create_with_data(N, N, data).unwrap()
+}
+
+fn no_obstacles() -> Field {
+    Field::new(N, N)
+}
+
+#[test]
+fn sample_at_texel_center_returns_texel() {
+    let field = field_from(texture::make_rainbow_array(N, N));
+
+    let uv = Vector2::new(3.5 / N as f32, 7.5 / N as f32);
+    assert_eq!(field.sample(uv), field.texel(3, 7));
+}
+
+#[test]
+fn advection_with_static_field_is_identity() {
+    let color = field_from(texture::make_checkerboard_array(N, N));
+    let velocity = field_from(texture::make_static_vector_field(N as f32, N as f32));
+    let mut dst = Field::new(N, N);
+
+    cpu_fluid::advection(1.0 / N as f32, 1.0 / N as f32, 1.0, 1.0 / 60.0, 0.0, Integrator::Euler, GridLayout::Collocated, GridLayout::Collocated, &no_obstacles(), &color, &velocity, &mut dst);
+
+    assert_eq!(dst, color);
+}
+
+#[test]
+fn divergence_of_constant_field_is_zero() {
+    let velocity = field_from(texture::make_constant_vector_field(N as f32, N as f32));
+    let mut div = Field::new(N, N);
+
+    cpu_fluid::divergence(1.0 / N as f32, 1.0 / N as f32, 1.0, GridLayout::Collocated, &Boundaries::default(), &no_obstacles(), &velocity, &mut div);
+
+    assert!(div.data().chunks(4).all(|t| t[0] == 0.0));
+}
+
+#[test]
+fn boundary_reflects_velocity_at_walls() {
+    let delta_x = 1.0 / N as f32;
+    let velocity = field_from(texture::make_constant_vector_field(N as f32, N as f32));
+    let mut dst = Field::new(N, N);
+
+    cpu_fluid::boundary(delta_x, delta_x, &Boundaries::default(), BoundaryField::Velocity, GridLayout::Collocated, &velocity, &mut dst);
+
+    assert_eq!(dst.texel(0, N / 2).xy(), Vector2::new(-1.0, -1.0));
+    assert_eq!(dst.texel(N / 2, N / 2).xy(), Vector2::new(1.0, 1.0));
+}
+
+#[test]
+fn projection_reduces_divergence() {
+    let delta_x = 1.0 / N as f32;
+    let velocity = field_from(texture::make_divergent_vector_field(N as f32, N as f32));
+
+    let mut div = Field::new(N, N);
+    cpu_fluid::divergence(delta_x, delta_x, 1.0, GridLayout::Collocated, &Boundaries::default(), &no_obstacles(), &velocity, &mut div);
+    let before: f32 = div.data().chunks(4).map(|t| t[0] * t[0]).sum();
+
+    let mut pressure = Field::new(N, N);
+    let mut scratch = Field::new(N, N);
+    cpu_fluid::jacobi_method(40, delta_x, delta_x, 1.0, -(delta_x * delta_x), 0.25, &no_obstacles(), &mut pressure, &div, &mut scratch);
+
+    let mut projected = Field::new(N, N);
+    cpu_fluid::subtract(delta_x, delta_x, 1.0, GridLayout::Collocated, &no_obstacles(), &pressure, &velocity, &mut projected);
+    cpu_fluid::divergence(delta_x, delta_x, 1.0, GridLayout::Collocated, &Boundaries::default(), &no_obstacles(), &projected, &mut div);
+    let after: f32 = div.data().chunks(4).map(|t| t[0] * t[0]).sum();
+
+    assert!(after < before);
+}
+
+#[test]
+fn color_splat_overwrites_disk() {
+    let color = field_from(texture::make_black_array(N, N));
+    let mut dst = Field::new(N, N);
+    let red = Vector3::new(1.0, 0.0, 0.0);
+
+    cpu_fluid::color(1.0 / 60.0, &Brush::dye(), 1.0, &red, &Vector2::new(0.5, 0.5), &color, &mut dst);
+
+    assert_eq!(dst.texel(N / 2, N / 2).xyz(), red);
+    assert_eq!(dst.texel(0, 0).xyz(), Vector3::zeros());
+}
+
+#[test]
+fn color_splat_blends_by_brush() {
+    let color = field_from(vec![0.5; (N * N * 4) as usize]);
+    let mut dst = Field::new(N, N);
+    let white = Vector3::new(1.0, 1.0, 1.0);
+    let center = Vector2::new(0.5, 0.5);
+
+    // adds a tenth of the color over a tenth of a second
+    let additive = Brush { radius: 0.1, falloff: Falloff::Hard, strength: 1.0, blend: Blend::Additive };
+    cpu_fluid::color(0.1, &additive, 1.0, &white, &center, &color, &mut dst);
+    assert!((dst.texel(N / 2, N / 2).x - 0.6).abs() < 1e-6);
+
+    // covers with a quarter of the color over a tenth of a second
+    let alpha = Brush { strength: 2.5, blend: Blend::AlphaOver, ..additive };
+    cpu_fluid::color(0.1, &alpha, 1.0, &white, &center, &color, &mut dst);
+    assert!((dst.texel(N / 2, N / 2).x - 0.625).abs() < 1e-6);
+
+    // a soft edge blends less towards the rim, and not at all past it
+    let soft = Brush { falloff: Falloff::Smoothstep, blend: Blend::Replace, ..additive };
+    cpu_fluid::color(0.1, &soft, 1.0, &white, &center, &color, &mut dst);
+    let middle = dst.texel(N / 2, N / 2).x;
+    let rim = dst.texel(N / 2 + 2, N / 2).x;
+    assert!(middle > rim && rim > 0.5, "{} {}", middle, rim);
+    assert_eq!(dst.texel(N / 2 + 4, N / 2).x, 0.5);
+}
+
+// largest jump between horizontal neighbours, a proxy for how sharp edges stay
+fn max_step(field: &Field) -> f32 {
+    let mut max = 0.0_f32;
+    for y in 0..field.height() {
+        for x in 1..field.width() {
+            max = max.max((field.texel(x, y).x - field.texel(x - 1, y).x).abs());
+        }
+    }
+    max
+}
+
+#[test]
+fn maccormack_keeps_edges_sharper_than_semi_lagrangian() {
+    let delta_x = 1.0 / N as f32;
+    let delta_t = 1.0 / 60.0;
+    let velocity = field_from([0.6, 0.0, 0.0, 1.0].repeat((N * N) as usize));
+
+    let mut semi_lagrangian = field_from(texture::make_black_white_array(N, N));
+    let mut maccormack = semi_lagrangian.clone();
+    let mut scratch = Field::new(N, N);
+    let mut forward = Field::new(N, N);
+    let mut backward = Field::new(N, N);
+
+    for _ in 0..20 {
+        cpu_fluid::advection(delta_x, delta_x, 1.0, delta_t, 0.0, Integrator::Euler, GridLayout::Collocated, GridLayout::Collocated, &no_obstacles(), &semi_lagrangian, &velocity, &mut scratch);
+        std::mem::swap(&mut semi_lagrangian, &mut scratch);
+
+        cpu_fluid::advection(delta_x, delta_x, 1.0, delta_t, 0.0, Integrator::Euler, GridLayout::Collocated, GridLayout::Collocated, &no_obstacles(), &maccormack, &velocity, &mut forward);
+        cpu_fluid::advection(delta_x, delta_x, 1.0, -delta_t, 0.0, Integrator::Euler, GridLayout::Collocated, GridLayout::Collocated, &no_obstacles(), &forward, &velocity, &mut backward);
+        cpu_fluid::maccormack(delta_x, delta_x, 1.0, delta_t, 0.0, Integrator::Euler, GridLayout::Collocated, GridLayout::Collocated, &no_obstacles(), &maccormack, &velocity, &forward, &backward, &mut scratch);
+        std::mem::swap(&mut maccormack, &mut scratch);
+    }
+
+    assert!(max_step(&maccormack) > max_step(&semi_lagrangian));
+    assert!(maccormack.data().iter().all(|&c| (0.0..=1.0).contains(&c)));
+}
+
+// distance of the dye's center of mass from the middle of the grid
+fn dye_radius(field: &Field) -> f32 {
+    let (mut total, mut center) = (0.0, Vector2::zeros());
+    for y in 0..field.height() {
+        for x in 0..field.width() {
+            let dye = field.texel(x, y).x;
+            total += dye;
+            center += dye * Vector2::new(x as f32 + 0.5, y as f32 + 0.5) / N as f32;
+        }
+    }
+    (center / total - Vector2::new(0.5, 0.5)).norm()
+}
+
+#[test]
+fn higher_order_backtrace_drifts_less_in_circular_flow() {
+    let delta_x = 1.0 / N as f32;
+    let velocity = field_from(texture::make_circular_vector_field(N as f32, N as f32));
+
+    let mut start = Field::new(N, N);
+    let spot = Vector2::new(0.75, 0.5);
+    start.shade(|uv| if (uv - spot).norm() < 0.1 { nalgebra::Vector4::new(1.0, 0.0, 0.0, 1.0) } else { nalgebra::Vector4::zeros() });
+    let radius = dye_radius(&start);
+
+    let drift = |integrator| {
+        let mut dye = start.clone();
+        let mut scratch = Field::new(N, N);
+        for _ in 0..10 {
+            cpu_fluid::advection(delta_x, delta_x, 1.0, 0.2, 0.0, integrator, GridLayout::Collocated, GridLayout::Collocated, &no_obstacles(), &dye, &velocity, &mut scratch);
+            std::mem::swap(&mut dye, &mut scratch);
+        }
+        (dye_radius(&dye) - radius).abs()
+    };
+
+    let euler = drift(Integrator::Euler);
+    let midpoint = drift(Integrator::Midpoint);
+    let rk3 = drift(Integrator::Rk3);
+    assert!(midpoint < euler, "midpoint {} euler {}", midpoint, euler);
+    assert!(rk3 < euler, "rk3 {} euler {}", rk3, euler);
+}
+
+#[test]
+fn divergence_accounts_for_rectangular_domain() {
+    // a 2:1 domain, 1 wide and 0.5 tall, with square cells
+    let (width, height) = (2 * N, N);
+    let (delta_x, delta_y, aspect) = (1.0 / width as f32, 1.0 / height as f32, 2.0);
+
+    // u = x, v = -y in domain units is divergence free
+    let mut velocity = Field::new(width, height);
+    velocity.shade(|uv| nalgebra::Vector4::new(uv.x, -uv.y / aspect, 0.0, 1.0));
+
+    let mut div = Field::new(width, height);
+    cpu_fluid::divergence(delta_x, delta_y, aspect, GridLayout::Collocated, &Boundaries::default(), &Field::new(width, height), &velocity, &mut div);
+
+    let interior = div.texel(width / 2, height / 2).x;
+    assert!(interior.abs() < 1e-3, "{}", interior);
+}
+
+#[test]
+fn dissipation_decays_exponentially() {
+    let color = field_from(texture::make_white_array(N, N));
+    let velocity = field_from(texture::make_static_vector_field(N as f32, N as f32));
+    let mut dst = Field::new(N, N);
+
+    let (delta_t, rate) = (0.5, 0.4);
+    cpu_fluid::advection(1.0 / N as f32, 1.0 / N as f32, 1.0, delta_t, rate, Integrator::Euler, GridLayout::Collocated, GridLayout::Collocated, &no_obstacles(), &color, &velocity, &mut dst);
+
+    let texel = dst.texel(N / 2, N / 2);
+    assert!((texel.x - (-rate * delta_t).exp()).abs() < 1e-6, "{}", texel.x);
+    assert_eq!(texel.w, 1.0);
+}
+
+#[test]
+fn obstacle_boundary_mirrors_velocity() {
+    let delta_x = 1.0 / N as f32;
+    // the right half of the grid is solid
+    let obstacles = field_from(obstacle::make_shapes_mask(N, N, &[Shape::Rect { min: Vector2::new(0.5, 0.0), max: Vector2::new(1.0, 1.0) }]));
+    let mut velocity = Field::new(N, N);
+    velocity.shade(|_| Vector4::new(1.0, 2.0, 0.0, 1.0));
+    let mut dst = Field::new(N, N);
+
+    cpu_fluid::obstacle_boundary(delta_x, delta_x, Slip::NoSlip, GridLayout::Collocated, &obstacles, &velocity, &mut dst);
+    assert_eq!(dst.texel(N / 2, N / 2).xy(), Vector2::new(-1.0, -2.0));
+    assert_eq!(dst.texel(N - 4, N / 2).xy(), Vector2::new(0.0, 0.0));
+    assert_eq!(dst.texel(N / 2 - 1, N / 2).xy(), Vector2::new(1.0, 2.0));
+
+    // free-slip only turns back the flow through the wall
+    cpu_fluid::obstacle_boundary(delta_x, delta_x, Slip::FreeSlip, GridLayout::Collocated, &obstacles, &velocity, &mut dst);
+    assert_eq!(dst.texel(N / 2, N / 2).xy(), Vector2::new(-1.0, 2.0));
+}
+
+#[test]
+fn body_stamps_its_surface_velocity() {
+    let mut body = RigidBody::new(BodyShape::Circle { radius: 0.2 }, Vector2::new(0.5, 0.5));
+    body.velocity = Vector2::new(0.5, 0.0);
+    body.angular_velocity = 1.0;
+    let mut dst = Field::new(N, N);
+
+    cpu_fluid::body(1.0, &body, &no_obstacles(), &mut dst);
+
+    // stored as it is, and scaled into the velocity field's units on reading
+    let uv = Vector2::new(20.5 / N as f32, 16.5 / N as f32);
+    let expected = body.surface_velocity(1.0, uv);
+    assert!(cpu_fluid::solid(&dst, uv));
+    assert!((dst.sample(uv).yz() - expected).norm() < 1e-6);
+    assert!((cpu_fluid::solid_velocity(&dst, uv) - expected / cpu_fluid::BACKTRACE_SCALE).norm() < 1e-6);
+    assert_eq!(dst.texel(2, 2), Vector4::zeros());
+}
+
+#[test]
+fn boundary_applies_each_edge_mode() {
+    let delta_x = 1.0 / N as f32;
+    let velocity = field_from(texture::make_constant_vector_field(N as f32, N as f32));
+    let mut dst = Field::new(N, N);
+    let boundaries = Boundaries {
+        left:   Edge::FreeSlip,
+        right:  Edge::Outflow,
+        bottom: Edge::Inflow(Vector2::new(0.5, 2.0)),
+        top:    Edge::NoSlip,
+    };
+
+    cpu_fluid::boundary(delta_x, delta_x, &boundaries, BoundaryField::Velocity, GridLayout::Collocated, &velocity, &mut dst);
+    assert_eq!(dst.texel(0, N / 2).xy(), Vector2::new(-1.0, 1.0));
+    assert_eq!(dst.texel(N - 1, N / 2).xy(), Vector2::new(1.0, 1.0));
+    assert_eq!(dst.texel(N / 2, 0).xy(), Vector2::new(0.5, 2.0));
+    assert_eq!(dst.texel(N / 2, N - 1).xy(), Vector2::new(-1.0, -1.0));
+
+    // the pressure is only pinned where the fluid leaves
+    cpu_fluid::boundary(delta_x, delta_x, &boundaries, BoundaryField::Pressure, GridLayout::Collocated, &velocity, &mut dst);
+    assert_eq!(dst.texel(0, N / 2).x, 1.0);
+    assert_eq!(dst.texel(N - 1, N / 2).x, 0.0);
+}
+
+#[test]
+fn periodic_edges_wrap_samples_and_skip_the_boundary() {
+    let delta_x = 1.0 / N as f32;
+    let mut velocity = field_from(texture::make_constant_vector_field(N as f32, N as f32));
+    velocity.set_texel(N - 1, N / 2, Vector4::new(3.0, 0.0, 0.0, 1.0));
+    velocity.set_periodic(true, false);
+
+    // half a texel past the left edge is halfway to the last column
+    let uv = Vector2::new(0.0, (N / 2) as f32 * delta_x + 0.5 * delta_x);
+    assert_eq!(velocity.sample(uv).x, 2.0);
+
+    let mut dst = Field::new(N, N);
+    let boundaries = Boundaries { left: Edge::Periodic, ..Boundaries::default() };
+    cpu_fluid::boundary(delta_x, delta_x, &boundaries, BoundaryField::Velocity, GridLayout::Collocated, &velocity, &mut dst);
+    assert_eq!(dst.texel(N - 1, N / 2).xy(), Vector2::new(3.0, 0.0));
+    assert_eq!(dst.texel(N / 2, 0).xy(), Vector2::new(-1.0, -1.0));
+}
+
+#[test]
+fn splat_reaches_across_a_periodic_edge() {
+    let mut color = field_from(texture::make_black_array(N, N));
+    color.set_periodic(true, false);
+    let mut dst = Field::new(N, N);
+    let red = Vector3::new(1.0, 0.0, 0.0);
+
+    // a disk centered on the left edge covers the last column too
+    cpu_fluid::color(1.0 / 60.0, &Brush::dye(), 1.0, &red, &Vector2::new(0.0, 0.5), &color, &mut dst);
+
+    assert_eq!(dst.texel(0, N / 2).xyz(), red);
+    assert_eq!(dst.texel(N - 1, N / 2).xyz(), red);
+    assert_eq!(dst.texel(N / 2, N / 2).xyz(), Vector3::zeros());
+}
@@ -0,0 +1,54 @@
+//! Emitter sources and schedules.
+
+use fluids::emitter::{Emitter, Schedule, Source};
+
+use nalgebra::Vector2;
+
+#[test]
+fn line_is_covered_by_evenly_spaced_points() {
+    let line = Source::Line { start: Vector2::new(0.1, 0.5), end: Vector2::new(0.3, 0.5) };
+    let points = line.points(1.0, 0.05);
+
+    assert_eq!(points.len(), 5);
+    assert!((points[0] - Vector2::new(0.1, 0.5)).norm() < 1e-6);
+    assert!((points[2] - Vector2::new(0.2, 0.5)).norm() < 1e-6);
+    assert!((points[4] - Vector2::new(0.3, 0.5)).norm() < 1e-6);
+
+    // a point is a single splat
+    assert_eq!(Source::Point(Vector2::new(0.5, 0.5)).points(1.0, 0.05), vec![Vector2::new(0.5, 0.5)]);
+}
+
+#[test]
+fn region_spacing_is_in_domain_widths() {
+    // on a domain twice as wide as tall, 0.2 uv tall is 0.1 domain widths
+    let region = Source::Region { min: Vector2::new(0.0, 0.0), max: Vector2::new(0.1, 0.2) };
+    let points = region.points(2.0, 0.05);
+
+    assert_eq!(points.len(), 9);
+    assert!(points.iter().all(|p| (0.0..=0.1).contains(&p.x) && (0.0..=0.2).contains(&p.y)));
+    assert!(points.contains(&Vector2::new(0.1, 0.2)));
+}
+
+#[test]
+fn schedules_switch_on_simulated_time() {
+    assert!(Schedule::Always.is_on(100.0));
+
+    let window = Schedule::Between { start: 1.0, end: 2.0 };
+    assert!(!window.is_on(0.5));
+    assert!(window.is_on(1.5));
+    assert!(!window.is_on(2.0));
+
+    let pulse = Schedule::Pulse { period: 1.0, on: 0.25 };
+    assert!(pulse.is_on(0.1));
+    assert!(!pulse.is_on(0.5));
+    assert!(pulse.is_on(3.2));
+}
+
+#[test]
+fn force_follows_direction_at_rate() {
+    let emitter = Emitter::new(Source::Point(Vector2::new(0.5, 0.5)), Vector2::new(0.0, 2.0), 3.0);
+    assert!((emitter.force() - Vector2::new(0.0, 3.0)).norm() < 1e-6);
+
+    let dye_only = Emitter::new(Source::Point(Vector2::new(0.5, 0.5)), Vector2::zeros(), 3.0);
+    assert_eq!(dye_only.force(), Vector2::zeros());
+}
@@ -0,0 +1,56 @@
+//! Obstacle masks and the rigid bodies moving through the fluid.
+
+use fluids::obstacle::{self, BodyShape, RigidBody, Shape};
+
+use nalgebra::Vector2;
+
+use std::f32::consts::FRAC_PI_2;
+
+#[test]
+fn shapes_mask_marks_cells_inside() {
+    let mask = obstacle::make_shapes_mask(8, 4, &[Shape::Rect { min: Vector2::new(0.0, 0.0), max: Vector2::new(0.5, 0.5) }]);
+
+    let solid = |x: usize, y: usize| mask[(y * 8 + x) * 4];
+    assert_eq!(solid(0, 0), 1.0);
+    assert_eq!(solid(3, 1), 1.0);
+    assert_eq!(solid(4, 1), 0.0);
+    assert_eq!(solid(0, 2), 0.0);
+}
+
+#[test]
+fn rotated_rect_contains_its_turned_footprint() {
+    let mut body = RigidBody::new(BodyShape::Rect { half_size: Vector2::new(0.2, 0.05) }, Vector2::new(0.5, 0.5));
+    assert!(body.contains(1.0, Vector2::new(0.65, 0.5)));
+    assert!(!body.contains(1.0, Vector2::new(0.5, 0.65)));
+
+    // a quarter turn stands it upright
+    body.angle = FRAC_PI_2;
+    assert!(!body.contains(1.0, Vector2::new(0.65, 0.5)));
+    assert!(body.contains(1.0, Vector2::new(0.5, 0.65)));
+}
+
+#[test]
+fn polygon_contains_by_even_odd_rule() {
+    let triangle = vec![Vector2::new(-0.1, -0.1), Vector2::new(0.1, -0.1), Vector2::new(0.0, 0.1)];
+    let body = RigidBody::new(BodyShape::Polygon { vertices: triangle }, Vector2::new(0.5, 0.5));
+
+    assert!(body.contains(1.0, Vector2::new(0.5, 0.5)));
+    assert!(!body.contains(1.0, Vector2::new(0.58, 0.58)));
+    assert!(!body.contains(1.0, Vector2::new(0.5, 0.35)));
+}
+
+#[test]
+fn body_advances_and_spins_its_surface() {
+    let mut body = RigidBody::new(BodyShape::Circle { radius: 0.1 }, Vector2::new(0.5, 0.5));
+    body.velocity = Vector2::new(0.5, 0.25);
+    body.angular_velocity = 2.0;
+
+    // on a domain twice as wide as tall, a domain width is two uv vertically
+    body.advance(2.0, 0.1);
+    assert!((body.position - Vector2::new(0.55, 0.55)).norm() < 1e-6, "{}", body.position);
+    assert!((body.angle - 0.2).abs() < 1e-6);
+
+    // the rim to the right moves up on top of the body's own velocity
+    let rim = body.position + Vector2::new(0.1, 0.0);
+    assert!((body.surface_velocity(2.0, rim) - Vector2::new(0.5, 0.45)).norm() < 1e-6);
+}
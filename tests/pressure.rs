@@ -0,0 +1,226 @@
+//! Convergence of the multigrid and conjugate gradient pressure solves, on the
+//! CPU backend.
+
+use fluids::backend::{Boundaries, CpuBackend, DoubleField, Edge, GridLayout};
+use fluids::cpu_fluid::{self, Field};
+use fluids::multigrid::Multigrid;
+use fluids::obstacle::{self, Shape};
+use fluids::pcg;
+use fluids::simulation::{FluidSimulation, PressureSolver, SimulationParams};
+use fluids::texture;
+
+use nalgebra::{Vector2, Vector4};
+
+use std::f32::consts::PI;
+
+const N: i32 = 32;
+
+fn residual_norm(delta_x: f32, x: &Field, b: &Field) -> f32 {
+    let mut r = Field::new(N, N);
+    cpu_fluid::residual(delta_x, delta_x, 1.0, &Field::new(N, N), x, b, &mut r);
+    r.data().chunks(4).map(|t| t[0] * t[0]).sum::<f32>().sqrt()
+}
+
+// smooth zero mean right hand side, compatible with the neumann walls
+fn smooth_rhs() -> Field {
+    let mut b = Field::new(N, N);
+    b.shade(|uv| Vector4::new((2.0 * PI * uv.x).cos() * (PI * uv.y).cos(), 0.0, 0.0, 1.0));
+    b
+}
+
+#[test]
+fn multigrid_converges_faster_than_jacobi() {
+    let delta_x = 1.0 / N as f32;
+    let mut backend = CpuBackend::new();
+
+    let b = smooth_rhs();
+    let initial = residual_norm(delta_x, &Field::new(N, N), &b);
+
+    let mut jacobi = Field::new(N, N);
+    let mut scratch = Field::new(N, N);
+    cpu_fluid::jacobi_method(20, delta_x, delta_x, 1.0, -(delta_x * delta_x), 0.25, &Field::new(N, N), &mut jacobi, &b, &mut scratch);
+
+    let mut multigrid = Multigrid::new(&mut backend, N, N).unwrap();
+    let mut x = DoubleField::new(Field::new(N, N), Field::new(N, N));
+    multigrid.solve(&mut backend, 2, 2, delta_x, delta_x, 1.0, &Field::new(N, N), &mut x, &b);
+
+    let jacobi_residual = residual_norm(delta_x, &jacobi, &b);
+    let multigrid_residual = residual_norm(delta_x, &x.read, &b);
+    assert!(multigrid_residual < 0.1 * initial, "{} vs {}", multigrid_residual, initial);
+    assert!(multigrid_residual < jacobi_residual, "{} vs {}", multigrid_residual, jacobi_residual);
+}
+
+#[test]
+fn multigrid_converges_on_an_odd_grid() {
+    // 33 coarsens to 17 and 9, whose texel centers do not line up with the
+    // finer grids'
+    const M: i32 = 33;
+    let delta_x = 1.0 / M as f32;
+    let mut backend = CpuBackend::new();
+    let residual_norm = |x: &Field, b: &Field| {
+        let mut r = Field::new(M, M);
+        cpu_fluid::residual(delta_x, delta_x, 1.0, &Field::new(M, M), x, b, &mut r);
+        r.data().chunks(4).map(|t| t[0] * t[0]).sum::<f32>().sqrt()
+    };
+
+    let mut b = Field::new(M, M);
+    b.shade(|uv| Vector4::new((2.0 * PI * uv.x).cos() * (PI * uv.y).cos(), 0.0, 0.0, 1.0));
+    let initial = residual_norm(&Field::new(M, M), &b);
+
+    let mut multigrid = Multigrid::new(&mut backend, M, M).unwrap();
+    let mut x = DoubleField::new(Field::new(M, M), Field::new(M, M));
+    multigrid.solve(&mut backend, 2, 2, delta_x, delta_x, 1.0, &Field::new(M, M), &mut x, &b);
+
+    let multigrid_residual = residual_norm(&x.read, &b);
+    assert!(multigrid_residual < 0.1 * initial, "{} vs {}", multigrid_residual, initial);
+}
+
+#[test]
+fn conjugate_gradient_reaches_tolerance() {
+    let delta_x = 1.0 / N as f32;
+    let b = smooth_rhs();
+
+    let mut x = Field::new(N, N);
+    let report = pcg::solve_pressure(delta_x, delta_x, 1.0, 1e-3, 200, &Boundaries::default(), &Field::new(N, N), &mut x, &b);
+
+    assert!(report.converged, "{:?}", report);
+    assert!(report.iterations < 50, "{:?}", report);
+
+    let mut r = Field::new(N, N);
+    cpu_fluid::residual(delta_x, delta_x, 1.0, &Field::new(N, N), &x, &b, &mut r);
+    let max = r.data().chunks(4).fold(0.0_f32, |m, t| m.max(t[0].abs()));
+    assert!(max < 1e-2, "{}", max);
+
+    // a warm start from the solution needs no iterations
+    let again = pcg::solve_pressure(delta_x, delta_x, 1.0, 1e-2, 200, &Boundaries::default(), &Field::new(N, N), &mut x, &b);
+    assert_eq!(again.iterations, 0);
+}
+
+#[test]
+fn diagnostics_report_projection_quality() {
+    let mut sim = FluidSimulation::with_backend(CpuBackend::new(), N, N, SimulationParams::default()).unwrap();
+    sim.set_velocity_field(texture::make_divergent_vector_field(N as f32, N as f32)).unwrap();
+
+    let mut before = Field::new(N, N);
+    cpu_fluid::divergence(1.0 / N as f32, 1.0 / N as f32, 1.0, GridLayout::Collocated, &Boundaries::default(), &Field::new(N, N), &Field::create_with_data(N, N, sim.read_velocity_field().unwrap()).unwrap(), &mut before);
+    let before = cpu_fluid::norms(&before);
+
+    sim.params.pressure_solver = PressureSolver::ConjugateGradient;
+    sim.step(1.0 / 60.0);
+    let d = sim.diagnostics().unwrap();
+
+    assert!(d.divergence.l2 < before.l2, "{:?} vs {:?}", d.divergence, before);
+    assert!(d.divergence.l2 <= d.divergence.linf);
+    assert!(d.residual.l2 < before.l2, "{:?} vs {:?}", d.residual, before);
+}
+
+#[test]
+fn red_black_sor_converges_faster_than_jacobi() {
+    let delta_x = 1.0 / N as f32;
+    let alpha = -(delta_x * delta_x);
+    let b = smooth_rhs();
+
+    let mut jacobi = Field::new(N, N);
+    let mut scratch = Field::new(N, N);
+    cpu_fluid::jacobi_method(60, delta_x, delta_x, 1.0, alpha, 0.25, &Field::new(N, N), &mut jacobi, &b, &mut scratch);
+
+    let mut sor = Field::new(N, N);
+    for _ in 0..30 {
+        cpu_fluid::red_black_iteration(delta_x, delta_x, 1.0, alpha, 0.25, 1.8, &Field::new(N, N), &mut sor, &b, &mut scratch);
+    }
+
+    // a red-black sweep is two passes, so this is the same work as the jacobi
+    // solve; over-relaxation overshoots at first, so it takes a few dozen
+    // sweeps to pull ahead
+    let jacobi_residual = residual_norm(delta_x, &jacobi, &b);
+    let sor_residual = residual_norm(delta_x, &sor, &b);
+    assert!(sor_residual < 0.5 * jacobi_residual, "{} vs {}", sor_residual, jacobi_residual);
+}
+
+#[test]
+fn conjugate_gradient_handles_anisotropic_cells() {
+    // a square domain on a 32x16 grid, so each cell is twice as tall as wide
+    let (width, height) = (N, N / 2);
+    let (delta_x, delta_y) = (1.0 / width as f32, 1.0 / height as f32);
+
+    let mut b = Field::new(width, height);
+    b.shade(|uv| Vector4::new((2.0 * PI * uv.x).cos() * (PI * uv.y).cos(), 0.0, 0.0, 1.0));
+
+    let mut x = Field::new(width, height);
+    let report = pcg::solve_pressure(delta_x, delta_y, 1.0, 1e-3, 200, &Boundaries::default(), &Field::new(width, height), &mut x, &b);
+    assert!(report.converged, "{:?}", report);
+
+    let mut r = Field::new(width, height);
+    cpu_fluid::residual(delta_x, delta_y, 1.0, &Field::new(width, height), &x, &b, &mut r);
+    let max = r.data().chunks(4).fold(0.0_f32, |m, t| m.max(t[0].abs()));
+    assert!(max < 1e-2, "{}", max);
+}
+
+#[test]
+fn conjugate_gradient_handles_obstacles() {
+    let delta_x = 1.0 / N as f32;
+    let obstacles = Field::create_with_data(N, N, obstacle::make_shapes_mask(N, N, &[Shape::Rect { min: Vector2::new(0.3, 0.3), max: Vector2::new(0.6, 0.5) }])).unwrap();
+
+    // no source inside the solid, and zero mean over the fluid
+    let fluid: Vec<bool> = obstacles.data().chunks(4).map(|t| t[0] <= 0.5).collect();
+    let mut data = smooth_rhs().data().to_vec();
+    let sum: f32 = data.chunks(4).zip(&fluid).filter(|(_, f)| **f).map(|(t, _)| t[0]).sum();
+    let mean = sum / fluid.iter().filter(|f| **f).count() as f32;
+    for (t, f) in data.chunks_mut(4).zip(&fluid) {
+        t[0] = if *f { t[0] - mean } else { 0.0 };
+    }
+    let b = Field::create_with_data(N, N, data).unwrap();
+
+    let mut x = Field::new(N, N);
+    let report = pcg::solve_pressure(delta_x, delta_x, 1.0, 1e-3, 200, &Boundaries::default(), &obstacles, &mut x, &b);
+    assert!(report.converged, "{:?}", report);
+
+    let mut r = Field::new(N, N);
+    cpu_fluid::residual(delta_x, delta_x, 1.0, &obstacles, &x, &b, &mut r);
+    let max = r.data().chunks(4).fold(0.0_f32, |m, t| m.max(t[0].abs()));
+    assert!(max < 1e-2, "{}", max);
+}
+
+#[test]
+fn conjugate_gradient_holds_outflow_edges_at_zero() {
+    let delta_x = 1.0 / N as f32;
+    let boundaries = Boundaries { right: Edge::Outflow, ..Boundaries::default() };
+
+    // a source everywhere, which only leaves through the right edge; with the
+    // wall on the left the pressure is the parabola that is flat there and
+    // zero half a texel past the right edge, which the stencil solves exactly
+    let mut b = Field::new(N, N);
+    b.shade(|_| Vector4::new(1.0, 0.0, 0.0, 1.0));
+    let mut x = Field::new(N, N);
+
+    let report = pcg::solve_pressure(delta_x, delta_x, 1.0, 1e-4, 200, &boundaries, &Field::new(N, N), &mut x, &b);
+    assert!(report.converged, "{:?}", report);
+
+    let edge = 1.0 + 0.5 * delta_x;
+    for (k, t) in x.data().chunks(4).enumerate() {
+        let u = ((k as i32 % N) as f32 + 0.5) * delta_x;
+        let expected = 0.5 * (u * u - edge * edge);
+        assert!((t[0] - expected).abs() < 1e-3, "{} vs {} at {}", t[0], expected, k);
+    }
+}
+
+#[test]
+fn conjugate_gradient_couples_periodic_edges() {
+    let delta_x = 1.0 / N as f32;
+
+    // a source that only has a solution once the left and right edges meet:
+    // zero mean, but flowing out through one side and in through the other
+    let mut b = Field::new(N, N);
+    b.shade(|uv| Vector4::new((2.0 * PI * uv.x).sin(), 0.0, 0.0, 1.0));
+    b.set_periodic(true, false);
+    let mut x = Field::new(N, N);
+    x.set_periodic(true, false);
+
+    let report = pcg::solve_pressure(delta_x, delta_x, 1.0, 1e-3, 200, &Boundaries::default(), &Field::new(N, N), &mut x, &b);
+    assert!(report.converged, "{:?}", report);
+
+    let mut r = Field::new(N, N);
+    cpu_fluid::residual(delta_x, delta_x, 1.0, &Field::new(N, N), &x, &b, &mut r);
+    let max = r.data().chunks(4).fold(0.0_f32, |m, t| m.max(t[0].abs()));
+    assert!(max < 1e-2, "{}", max);
+}
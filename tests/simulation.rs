@@ -0,0 +1,270 @@
+//! Drives `FluidSimulation` on the CPU backend, without WebGL or the page.
+
+use fluids::backend::{Boundaries, CpuBackend, Edge, GridLayout};
+use fluids::emitter::{Emitter, Schedule, Source};
+use fluids::obstacle::{self, BodyShape, RigidBody, Shape};
+use fluids::simulation::{AdvectionScheme, FluidSimulation, PressureSolver, SimulationParams};
+use fluids::texture;
+
+use nalgebra::{Vector2, Vector3};
+
+const N: i32 = 32;
+
+fn cpu_simulation() -> FluidSimulation<CpuBackend> {
+    FluidSimulation::with_backend(CpuBackend::new(), N, N, SimulationParams::default()).unwrap()
+}
+
+#[test]
+fn static_field_stays_at_rest() {
+    let mut sim = cpu_simulation();
+    sim.set_velocity_field(texture::make_static_vector_field(N as f32, N as f32)).unwrap();
+    sim.set_color_field(texture::make_checkerboard_array(N, N)).unwrap();
+
+    sim.step(1.0 / 60.0);
+
+    // a field at rest needs no more than one substep
+    assert_eq!(sim.substeps(), 1);
+    assert!(sim.read_velocity_field().unwrap().chunks(4).all(|t| t[0] == 0.0 && t[1] == 0.0));
+    assert_eq!(sim.read_color_field().unwrap(), texture::make_checkerboard_array(N, N));
+}
+
+#[test]
+fn splat_adds_dye_and_momentum() {
+    let mut sim = cpu_simulation();
+    sim.set_color_field(texture::make_black_array(N, N)).unwrap();
+
+    sim.apply_force(Vector2::new(500.0, 0.0), Vector2::new(0.5, 0.5));
+    sim.add_dye(Vector3::new(1.0, 0.0, 0.0), Vector2::new(0.5, 0.5));
+    sim.step(1.0 / 60.0);
+
+    let velocity = sim.read_velocity_field().unwrap();
+    let color = sim.read_color_field().unwrap();
+    assert!(velocity.chunks(4).any(|t| t[0] > 0.0));
+    assert!(color.chunks(4).any(|t| t[0] > 0.5));
+}
+
+#[test]
+fn staggered_grid_projects_out_more_divergence() {
+    let divergence_after_step = |grid| {
+        let mut sim = cpu_simulation();
+        sim.params.grid = grid;
+        sim.params.pressure_solver = PressureSolver::ConjugateGradient;
+        sim.params.vorticity = 0.0;
+        sim.set_velocity_field(texture::make_circular_vector_field(N as f32, N as f32)).unwrap();
+
+        sim.apply_force(Vector2::new(500.0, 0.0), Vector2::new(0.5, 0.5));
+        sim.step(1.0 / 60.0);
+        sim.diagnostics().unwrap().divergence
+    };
+
+    let collocated = divergence_after_step(GridLayout::Collocated);
+    let staggered = divergence_after_step(GridLayout::Staggered);
+    assert!(staggered.l2 < 0.1 * collocated.l2, "{:?} vs {:?}", staggered, collocated);
+}
+
+#[test]
+fn fast_flow_is_split_into_substeps() {
+    let mut sim = cpu_simulation();
+
+    // 3 domain widths per second backtraces 0.8 cells of a 32 grid per frame
+    sim.set_velocity_field([3.0, 0.0, 0.0, 1.0].repeat((N * N) as usize)).unwrap();
+    sim.params.cfl = 0.3;
+    sim.step(1.0 / 60.0);
+    assert_eq!(sim.substeps(), 3);
+
+    sim.params.max_substeps = 2;
+    sim.step(1.0 / 60.0);
+    assert_eq!(sim.substeps(), 2);
+}
+
+#[test]
+fn speed_is_read_again_after_a_splat() {
+    let mut sim = cpu_simulation();
+    sim.params.cfl = 0.3;
+    sim.step(1.0 / 60.0);
+    assert_eq!(sim.substeps(), 1);
+
+    // the splat speeds the flow up during this step, so the next one splits
+    // without waiting for the regular readback
+    sim.apply_force(Vector2::new(5000.0, 0.0), Vector2::new(0.5, 0.5));
+    sim.step(1.0 / 60.0);
+    sim.step(1.0 / 60.0);
+    assert!(sim.substeps() > 1, "{}", sim.substeps());
+}
+
+#[test]
+fn heat_rises_in_smoke_mode() {
+    let mut sim = cpu_simulation();
+    sim.params.smoke = true;
+    sim.params.vorticity = 0.0;
+    sim.set_velocity_field(texture::make_static_vector_field(N as f32, N as f32)).unwrap();
+
+    sim.add_heat(100.0, 0.0, Vector2::new(0.5, 0.25));
+    sim.step(1.0 / 60.0);
+
+    let smoke = sim.read_smoke_field().unwrap();
+    let velocity = sim.read_velocity_field().unwrap();
+    let center = ((N / 4 * N + N / 2) * 4) as usize;
+    assert!(smoke[center] > 0.0);
+    assert!(velocity[center + 1] > 0.0, "{}", velocity[center + 1]);
+}
+
+#[test]
+fn obstacle_blocks_the_flow() {
+    let mut sim = cpu_simulation();
+    sim.set_obstacles(obstacle::make_shapes_mask(N, N, &[Shape::Circle { center: Vector2::new(0.5, 0.5), radius: 0.15 }])).unwrap();
+    sim.set_color_field(texture::make_white_array(N, N)).unwrap();
+
+    // push the fluid right along the middle row, straight at the circle
+    sim.params.pressure_solver = PressureSolver::ConjugateGradient;
+    for _ in 0..3 {
+        sim.apply_force(Vector2::new(800.0, 0.0), Vector2::new(0.2, 0.5));
+        sim.step(1.0 / 60.0);
+    }
+
+    let velocity = sim.read_velocity_field().unwrap();
+    let color = sim.read_color_field().unwrap();
+    let texel = |x: i32, y: i32| ((y * N + x) * 4) as usize;
+
+    // the jet stops at the upstream face and leaves the wake behind it still
+    let (upstream, wake) = (velocity[texel(8, 16)], velocity[texel(22, 16)]);
+    assert!(upstream > 0.0);
+    assert!(wake.abs() < 0.1 * upstream, "{} vs {}", wake, upstream);
+
+    // and no dye is left inside
+    assert_eq!(color[texel(16, 16)], 0.0);
+}
+
+#[test]
+fn moving_body_pushes_the_fluid() {
+    let mut sim = cpu_simulation();
+    sim.params.pressure_solver = PressureSolver::ConjugateGradient;
+    let mut paddle = RigidBody::new(BodyShape::Rect { half_size: Vector2::new(0.05, 0.15) }, Vector2::new(0.4, 0.5));
+    paddle.velocity = Vector2::new(0.5, 0.0);
+    sim.bodies.push(paddle);
+
+    for _ in 0..3 {
+        sim.step(1.0 / 60.0);
+    }
+
+    assert!((sim.bodies[0].position.x - 0.425).abs() < 1e-4, "{}", sim.bodies[0].position.x);
+
+    // the fluid ahead of the paddle is pushed along with it
+    let velocity = sim.read_velocity_field().unwrap();
+    let texel = |x: i32, y: i32| ((y * N + x) * 4) as usize;
+    assert!(velocity[texel(18, 16)] > 0.1, "{}", velocity[texel(18, 16)]);
+}
+
+#[test]
+fn periodic_edges_carry_dye_around() {
+    let mut sim = cpu_simulation();
+    sim.params.max_substeps = 1;
+    sim.params.boundaries = Boundaries { left: Edge::Periodic, right: Edge::Periodic, ..Boundaries::default() };
+
+    // a rightward stream, with a dye stripe at the right edge
+    let mut velocity = vec![0.0; (N * N * 4) as usize];
+    let mut color = vec![0.0; (N * N * 4) as usize];
+    for (i, (v, c)) in velocity.chunks_mut(4).zip(color.chunks_mut(4)).enumerate() {
+        v.copy_from_slice(&[8.0, 0.0, 0.0, 1.0]);
+        let x = i as i32 % N;
+        let dye = if x >= N - 3 { 1.0 } else { 0.0 };
+        c.copy_from_slice(&[dye, dye, dye, 1.0]);
+    }
+    sim.set_velocity_field(velocity).unwrap();
+    sim.set_color_field(color).unwrap();
+
+    for _ in 0..3 {
+        sim.step(1.0 / 60.0);
+    }
+
+    // the stripe moves about two cells a step, and comes back in through the
+    // left edge
+    let color = sim.read_color_field().unwrap();
+    let texel = |x: i32, y: i32| ((y * N + x) * 4) as usize;
+    assert!(color[texel(4, N / 2)] > 0.5, "{}", color[texel(4, N / 2)]);
+    assert_eq!(color[texel(N / 2, N / 2)], 0.0);
+}
+
+#[test]
+fn emitter_adds_dye_and_momentum_while_on() {
+    let mut sim = cpu_simulation();
+    sim.params.max_substeps = 1;
+    sim.set_color_field(texture::make_black_array(N, N)).unwrap();
+
+    let mut jet = Emitter::new(Source::Point(Vector2::new(0.5, 0.5)), Vector2::new(1.0, 0.0), 500.0);
+    jet.color = Some(Vector3::new(0.0, 1.0, 0.0));
+    jet.schedule = Schedule::Between { start: 0.01, end: 1.0 };
+    sim.emitters.push(jet);
+
+    // off for the first step
+    sim.step(1.0 / 60.0);
+    assert!(sim.read_velocity_field().unwrap().chunks(4).all(|t| t[0] == 0.0 && t[1] == 0.0));
+    assert!(sim.read_color_field().unwrap().chunks(4).all(|t| t[1] == 0.0));
+
+    sim.step(1.0 / 60.0);
+    let velocity = sim.read_velocity_field().unwrap();
+    let color = sim.read_color_field().unwrap();
+    let texel = |x: i32, y: i32| ((y * N + x) * 4) as usize;
+    assert!(velocity[texel(N / 2, N / 2)] > 0.0);
+    assert!(color[texel(N / 2, N / 2) + 1] > 0.5);
+}
+
+#[test]
+fn resize_resamples_the_fields() {
+    let mut sim = cpu_simulation();
+    sim.set_color_field(texture::make_black_array(N, N)).unwrap();
+    sim.apply_force(Vector2::new(500.0, 0.0), Vector2::new(0.25, 0.5));
+    sim.add_dye(Vector3::new(1.0, 0.0, 0.0), Vector2::new(0.25, 0.5));
+    sim.step(1.0 / 60.0);
+
+    // the velocity grid doubles, and the push stays where it was in uv
+    sim.resize(2 * N, N).unwrap();
+    assert_eq!((sim.width(), sim.height()), (2 * N, N));
+    let velocity = sim.read_velocity_field().unwrap();
+    assert_eq!(velocity.len(), (2 * N * N * 4) as usize);
+    assert!(velocity[((N / 2 * 2 * N + N / 2) * 4) as usize] > 0.0);
+
+    // the dye keeps its own grid until it is resized too
+    assert_eq!(sim.read_color_field().unwrap().len(), (N * N * 4) as usize);
+    sim.resize_dye(2 * N, N).unwrap();
+    let color = sim.read_color_field().unwrap();
+    assert_eq!(color.len(), (2 * N * N * 4) as usize);
+    let texel = |x: i32, y: i32| ((y * 2 * N + x) * 4) as usize;
+    assert!(color[texel(N / 2, N / 2)] > 0.5);
+    assert_eq!(color[texel(3 * N / 2, N / 2)], 0.0);
+
+    // and the simulation carries on at the new size
+    sim.step(1.0 / 60.0);
+    assert_eq!(sim.read_velocity_field().unwrap().len(), (2 * N * N * 4) as usize);
+}
+
+#[test]
+fn fine_dye_is_advected_by_a_coarse_velocity() {
+    let mut sim = cpu_simulation();
+    sim.params.max_substeps = 1;
+    sim.params.advection = AdvectionScheme::MacCormack;
+    sim.resize_dye(4 * N, 4 * N).unwrap();
+
+    // a rightward stream over a dye stripe a few fine texels wide
+    let dye = 4 * N;
+    let mut color = vec![0.0; (dye * dye * 4) as usize];
+    for (i, c) in color.chunks_mut(4).enumerate() {
+        let x = i as i32 % dye;
+        let value = if (dye / 4..dye / 4 + 4).contains(&x) { 1.0 } else { 0.0 };
+        c.copy_from_slice(&[value, value, value, 1.0]);
+    }
+    sim.set_color_field(color).unwrap();
+    sim.set_velocity_field(vec![[2.0, 0.0, 0.0, 1.0]; (N * N) as usize].concat()).unwrap();
+
+    sim.step(1.0 / 60.0);
+
+    // the stripe moves right by under a coarse cell and stays sharp on the
+    // fine grid
+    let color = sim.read_color_field().unwrap();
+    let row: Vec<f32> = (0..dye).map(|x| color[((dye / 2 * dye + x) * 4) as usize]).collect();
+    let peak = row.iter().cloned().fold(0.0, f32::max);
+    let center = row.iter().enumerate().map(|(x, c)| x as f32 * c).sum::<f32>() / row.iter().sum::<f32>();
+    assert!(peak > 0.9, "{}", peak);
+    assert!(center > (dye / 4) as f32 + 2.0, "{}", center);
+    assert!(row[..dye as usize / 4 - 2].iter().all(|&c| c < 1e-3));
+}
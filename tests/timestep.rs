@@ -0,0 +1,29 @@
+//! The fixed timestep accumulator the page's main loop steps the solver with.
+
+use fluids::timestep::FixedTimestep;
+
+// frame timestamps in milliseconds at `hz`, starting from 1000
+fn frames(hz: f64, count: usize) -> impl Iterator<Item = f64> {
+    (0..count).map(move |k| 1000.0 + k as f64 * 1000.0 / hz)
+}
+
+#[test]
+fn steps_track_wall_time_at_any_refresh_rate() {
+    for &hz in &[30.0, 60.0, 120.0, 144.0] {
+        let mut timestep = FixedTimestep::new(1.0 / 60.0, 4);
+        let steps: usize = frames(hz, hz as usize * 2 + 1).map(|now| timestep.advance(now)).sum();
+
+        // two seconds of frames, give or take the step still accumulating
+        assert!((119..=120).contains(&steps), "{} Hz: {} steps", hz, steps);
+    }
+}
+
+#[test]
+fn stalls_are_capped() {
+    let mut timestep = FixedTimestep::new(1.0 / 60.0, 4);
+    assert_eq!(timestep.advance(1000.0), 0);
+    assert_eq!(timestep.advance(6000.0), 4);
+
+    // the dropped time is not owed to later frames
+    assert_eq!(timestep.advance(6000.0 + 1000.0 / 60.0), 1);
+}
@@ -5,9 +5,75 @@
 extern crate wasm_bindgen_test;
 use wasm_bindgen_test::*;
 
+use fluids::backend::{Boundaries, CpuBackend, Edge, SolverBackend};
+use fluids::simulation::{FluidSimulation, SimulationParams};
+
+use nalgebra::{Vector2, Vector3};
+use wasm_bindgen::JsCast;
+use web_sys::WebGlRenderingContext as GL;
+
 wasm_bindgen_test_configure!(run_in_browser);
 
+fn webgl_context() -> GL {
+    let document = web_sys::window().unwrap().document().unwrap();
+    let canvas = document.create_element("canvas").unwrap()
+        .dyn_into::<web_sys::HtmlCanvasElement>().unwrap();
+    let gl = canvas.get_context("webgl").unwrap().unwrap().dyn_into::<GL>().unwrap();
+    gl.get_extension("OES_texture_float").unwrap();
+    gl.get_extension("OES_texture_float_linear").unwrap();
+    gl.get_extension("WEBGL_color_buffer_float").unwrap();
+    gl
+}
+
 #[wasm_bindgen_test]
 fn pass() {
     assert_eq!(1 + 1, 2);
 }
+
+const W: i32 = 30;
+const H: i32 = 20;
+
+// a rightward stream carrying a dye stripe across the periodic right edge of a
+// grid whose width is not a power of two, which WebGL 1 will not repeat
+fn carry_stripe<B: SolverBackend>(sim: &mut FluidSimulation<B>) -> Vec<f32>
+    where B::Error: std::fmt::Debug
+{
+    sim.params.max_substeps = 1;
+    sim.params.boundaries = Boundaries { left: Edge::Periodic, right: Edge::Periodic, ..Boundaries::default() };
+
+    let mut velocity = vec![0.0; (W * H * 4) as usize];
+    let mut color = vec![0.0; (W * H * 4) as usize];
+    for (i, (v, c)) in velocity.chunks_mut(4).zip(color.chunks_mut(4)).enumerate() {
+        v.copy_from_slice(&[4.0, 0.0, 0.0, 1.0]);
+        let x = i as i32 % W;
+        let dye = if x >= W - 3 { 1.0 } else { 0.0 };
+        c.copy_from_slice(&[dye, dye, dye, 1.0]);
+    }
+    sim.set_velocity_field(velocity).unwrap();
+    sim.set_color_field(color).unwrap();
+
+    // a splat on the edge spills over to the first column
+    sim.add_dye(Vector3::new(0.0, 1.0, 0.0), Vector2::new(1.0, 0.25));
+
+    for _ in 0..6 {
+        sim.step(1.0 / 60.0);
+    }
+    sim.read_color_field().unwrap()
+}
+
+#[wasm_bindgen_test]
+fn periodic_edges_wrap_on_any_grid_size() {
+    let gl = webgl_context();
+    let mut sim = FluidSimulation::new(&gl, W, H, SimulationParams::default()).unwrap();
+    let color = carry_stripe(&mut sim);
+
+    let texel = |x: i32, y: i32| ((y * W + x) * 4) as usize;
+    assert!(color[texel(4, H / 2)] > 0.5, "{}", color[texel(4, H / 2)]);
+    assert!(color[texel(W / 2, H / 2)] < 0.01, "{}", color[texel(W / 2, H / 2)]);
+
+    // and blends across the edge the same way the CPU reference does
+    let mut cpu = FluidSimulation::with_backend(CpuBackend::new(), W, H, SimulationParams::default()).unwrap();
+    let reference = carry_stripe(&mut cpu);
+    let error = color.iter().zip(&reference).map(|(a, b)| (a - b).abs()).fold(0.0, f32::max);
+    assert!(error < 1e-2, "{}", error);
+}